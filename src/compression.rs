@@ -1,5 +1,9 @@
 //! Possible ZIP compression methods.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
 use std::{fmt, io};
 
 #[allow(deprecated)]
@@ -10,7 +14,7 @@ use std::{fmt, io};
 ///
 /// When creating ZIP files, you may choose the method to use with
 /// [`crate::write::FileOptions::compression_method`]
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 #[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
 #[non_exhaustive]
 pub enum CompressionMethod {
@@ -26,6 +30,18 @@ pub enum CompressionMethod {
     /// Compress the file using BZIP2
     #[cfg(feature = "bzip2")]
     Bzip2,
+    /// Compressed with the shrink method of PKZIP 1.x.
+    /// Only decompression is supported.
+    #[cfg(feature = "legacy")]
+    Shrink,
+    /// Compressed with the reduce method of PKZIP 0.9, with the given compression factor from
+    /// 1 to 4. Only decompression is supported.
+    #[cfg(feature = "legacy")]
+    Reduce(u8),
+    /// Compressed with the implode method of PKZIP 1.x.
+    /// Only decompression is supported.
+    #[cfg(feature = "legacy")]
+    Implode,
     /// Encrypted using AES.
     ///
     /// The actual compression method has to be taken from the AES extra data field
@@ -41,7 +57,7 @@ pub enum CompressionMethod {
     /// Compress the file using XZ
     #[cfg(feature = "xz")]
     Xz,
-    /// Unsupported compression method
+    /// A compression method that this build doesn't implement, with its method ID
     #[cfg_attr(
         not(fuzzing),
         deprecated(since = "0.5.7", note = "use the constants instead")
@@ -52,11 +68,29 @@ pub enum CompressionMethod {
 /// All compression methods defined for the ZIP format
 impl CompressionMethod {
     pub const STORE: Self = CompressionMethod::Stored;
+    #[cfg(feature = "legacy")]
+    pub const SHRINK: Self = CompressionMethod::Shrink;
+    #[cfg(not(feature = "legacy"))]
     pub const SHRINK: Self = CompressionMethod::Unsupported(1);
+    #[cfg(feature = "legacy")]
+    pub const REDUCE_1: Self = CompressionMethod::Reduce(1);
+    #[cfg(not(feature = "legacy"))]
     pub const REDUCE_1: Self = CompressionMethod::Unsupported(2);
+    #[cfg(feature = "legacy")]
+    pub const REDUCE_2: Self = CompressionMethod::Reduce(2);
+    #[cfg(not(feature = "legacy"))]
     pub const REDUCE_2: Self = CompressionMethod::Unsupported(3);
+    #[cfg(feature = "legacy")]
+    pub const REDUCE_3: Self = CompressionMethod::Reduce(3);
+    #[cfg(not(feature = "legacy"))]
     pub const REDUCE_3: Self = CompressionMethod::Unsupported(4);
+    #[cfg(feature = "legacy")]
+    pub const REDUCE_4: Self = CompressionMethod::Reduce(4);
+    #[cfg(not(feature = "legacy"))]
     pub const REDUCE_4: Self = CompressionMethod::Unsupported(5);
+    #[cfg(feature = "legacy")]
+    pub const IMPLODE: Self = CompressionMethod::Implode;
+    #[cfg(not(feature = "legacy"))]
     pub const IMPLODE: Self = CompressionMethod::Unsupported(6);
     #[cfg(feature = "_deflate-any")]
     pub const DEFLATE: Self = CompressionMethod::Deflated;
@@ -99,6 +133,12 @@ impl CompressionMethod {
     pub(crate) const fn parse_from_u16(val: u16) -> Self {
         match val {
             0 => CompressionMethod::Stored,
+            #[cfg(feature = "legacy")]
+            1 => CompressionMethod::Shrink,
+            #[cfg(feature = "legacy")]
+            2..=5 => CompressionMethod::Reduce((val - 1) as u8),
+            #[cfg(feature = "legacy")]
+            6 => CompressionMethod::Implode,
             #[cfg(feature = "_deflate-any")]
             8 => CompressionMethod::Deflated,
             #[cfg(feature = "deflate64")]
@@ -118,11 +158,18 @@ impl CompressionMethod {
         }
     }
 
-    /// Converts a u16 to its corresponding CompressionMethod
-    #[deprecated(
-        since = "0.5.7",
-        note = "use a constant to construct a compression method"
-    )]
+    /// Converts the method ID stored in a ZIP header to its corresponding `CompressionMethod`.
+    ///
+    /// IDs that this build doesn't implement, including those of disabled features, are kept in
+    /// [`CompressionMethod::Unsupported`], so [`CompressionMethod::to_u16`] always returns `val`.
+    ///
+    /// ```
+    /// use zip::CompressionMethod;
+    ///
+    /// assert_eq!(CompressionMethod::from_u16(95), CompressionMethod::XZ);
+    /// assert_eq!(CompressionMethod::from_u16(98), CompressionMethod::PPMD);
+    /// assert_eq!(CompressionMethod::from_u16(98).to_u16(), 98);
+    /// ```
     pub const fn from_u16(val: u16) -> CompressionMethod {
         Self::parse_from_u16(val)
     }
@@ -130,6 +177,12 @@ impl CompressionMethod {
     pub(crate) const fn serialize_to_u16(self) -> u16 {
         match self {
             CompressionMethod::Stored => 0,
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Shrink => 1,
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Reduce(factor) => 1 + factor as u16,
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Implode => 6,
             #[cfg(feature = "_deflate-any")]
             CompressionMethod::Deflated => 8,
             #[cfg(feature = "deflate64")]
@@ -149,11 +202,7 @@ impl CompressionMethod {
         }
     }
 
-    /// Converts a CompressionMethod to a u16
-    #[deprecated(
-        since = "0.5.7",
-        note = "to match on other compression methods, use a constant"
-    )]
+    /// Converts a `CompressionMethod` to the method ID stored in a ZIP header.
     pub const fn to_u16(self) -> u16 {
         self.serialize_to_u16()
     }
@@ -187,9 +236,148 @@ pub const SUPPORTED_COMPRESSION_METHODS: &[CompressionMethod] = &[
     CompressionMethod::Bzip2,
     #[cfg(feature = "zstd")]
     CompressionMethod::Zstd,
+    #[cfg(feature = "legacy")]
+    CompressionMethod::Shrink,
+    #[cfg(feature = "legacy")]
+    CompressionMethod::Reduce(1),
+    #[cfg(feature = "legacy")]
+    CompressionMethod::Reduce(2),
+    #[cfg(feature = "legacy")]
+    CompressionMethod::Reduce(3),
+    #[cfg(feature = "legacy")]
+    CompressionMethod::Reduce(4),
+    #[cfg(feature = "legacy")]
+    CompressionMethod::Implode,
 ];
 
-pub(crate) enum Decompressor<R: io::BufRead> {
+/// Creates a decoder for a compression method, reading the compressed data from the given reader.
+pub(crate) type DecoderFactory =
+    dyn for<'r> Fn(Box<dyn io::Read + 'r>) -> io::Result<Box<dyn io::Read + 'r>> + Send + Sync;
+
+/// Creates an encoder for a compression method, writing the compressed data to the given writer.
+pub(crate) type EncoderFactory =
+    dyn Fn(Box<dyn io::Write + Send>) -> io::Result<Box<dyn io::Write + Send>> + Send + Sync;
+
+/// Decoders and encoders for compression methods that this crate doesn't implement, such as
+/// vendor-specific ones, or to replace the built-in implementation of a method.
+///
+/// [`ZipArchive::set_compression_registry`](crate::ZipArchive::set_compression_registry) and
+/// [`ZipWriter::set_compression_registry`](crate::ZipWriter::set_compression_registry) make
+/// an archive consult the registry first, and fall back to the built-in methods for those it
+/// doesn't have.
+///
+/// ```
+/// # fn doit() -> zip::result::ZipResult<()> {
+/// use std::io::{Read, Write};
+/// use zip::write::SimpleFileOptions;
+/// use zip::{CompressionMethod, CompressionRegistry, ZipArchive, ZipWriter};
+///
+/// // A "compression" method that inverts every bit
+/// struct Invert<T>(T);
+/// impl<R: Read> Read for Invert<R> {
+///     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+///         let count = self.0.read(buf)?;
+///         buf[..count].iter_mut().for_each(|byte| *byte = !*byte);
+///         Ok(count)
+///     }
+/// }
+/// impl<W: Write> Write for Invert<W> {
+///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+///         let inverted: Vec<u8> = buf.iter().map(|byte| !byte).collect();
+///         self.0.write_all(&inverted)?;
+///         Ok(buf.len())
+///     }
+///     fn flush(&mut self) -> std::io::Result<()> {
+///         self.0.flush()
+///     }
+/// }
+///
+/// let registry = CompressionRegistry::default()
+///     .with_decoder(CompressionMethod::PPMD, |reader| Ok(Box::new(Invert(reader))))
+///     .with_encoder(CompressionMethod::PPMD, |writer| Ok(Box::new(Invert(writer))));
+///
+/// let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+/// writer.set_compression_registry(registry.clone());
+/// let options = SimpleFileOptions::default().compression_method(CompressionMethod::PPMD);
+/// writer.start_file("inverted.txt", options)?;
+/// writer.write_all(b"Hello, World!")?;
+///
+/// let mut archive = ZipArchive::new(writer.finish()?)?;
+/// archive.set_compression_registry(registry);
+/// let mut contents = String::new();
+/// archive.by_name("inverted.txt")?.read_to_string(&mut contents)?;
+/// assert_eq!(contents, "Hello, World!");
+/// # Ok(())
+/// # }
+/// # doit().unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct CompressionRegistry {
+    decoders: HashMap<CompressionMethod, Arc<DecoderFactory>>,
+    encoders: HashMap<CompressionMethod, Arc<EncoderFactory>>,
+}
+
+impl CompressionRegistry {
+    /// Registers a decoder for `method`, which wraps the reader of an entry's compressed data
+    /// in a reader of its decompressed data.
+    #[must_use]
+    pub fn with_decoder<F>(mut self, method: CompressionMethod, factory: F) -> Self
+    where
+        F: for<'r> Fn(Box<dyn io::Read + 'r>) -> io::Result<Box<dyn io::Read + 'r>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.decoders.insert(method, Arc::new(factory));
+        self
+    }
+
+    /// Registers an encoder for `method`, which wraps the writer of an entry's compressed data
+    /// in a writer of its uncompressed data.
+    ///
+    /// The encoder is dropped when the entry is finished, so it must write out everything it
+    /// still holds when it's dropped, as `flate2`'s encoders do. The compression level of
+    /// [`FileOptions`](crate::write::FileOptions) isn't passed on to it.
+    #[must_use]
+    pub fn with_encoder<F>(mut self, method: CompressionMethod, factory: F) -> Self
+    where
+        F: Fn(Box<dyn io::Write + Send>) -> io::Result<Box<dyn io::Write + Send>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.encoders.insert(method, Arc::new(factory));
+        self
+    }
+
+    pub(crate) fn has_decoder(&self, method: CompressionMethod) -> bool {
+        self.decoders.contains_key(&method)
+    }
+
+    pub(crate) fn encoder(&self, method: CompressionMethod) -> Option<&EncoderFactory> {
+        self.encoders.get(&method).map(|factory| &**factory)
+    }
+}
+
+impl fmt::Debug for CompressionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressionRegistry")
+            .field("decoders", &self.decoders.keys().collect::<Vec<_>>())
+            .field("encoders", &self.encoders.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Lets a custom decoder read from a reader that [`Decompressor::into_inner`] can take back.
+pub(crate) struct SharedReader<R>(pub(crate) Rc<RefCell<R>>);
+
+impl<R: io::Read> io::Read for SharedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+pub(crate) enum Decompressor<'a, R: io::BufRead> {
     Stored(R),
     #[cfg(feature = "_deflate-any")]
     Deflated(flate2::bufread::DeflateDecoder<R>),
@@ -203,9 +391,19 @@ pub(crate) enum Decompressor<R: io::BufRead> {
     Lzma(Box<crate::read::lzma::LzmaDecoder<R>>),
     #[cfg(feature = "xz")]
     Xz(crate::read::xz::XzDecoder<R>),
+    #[cfg(feature = "legacy")]
+    Shrink(crate::read::shrink::ShrinkDecoder<R>),
+    #[cfg(feature = "legacy")]
+    Reduce(crate::read::reduce::ReduceDecoder<R>),
+    #[cfg(feature = "legacy")]
+    Implode(crate::read::implode::ImplodeDecoder<R>),
+    Custom {
+        decoder: Box<dyn io::Read + 'a>,
+        inner: Rc<RefCell<R>>,
+    },
 }
 
-impl<R: io::BufRead> io::Read for Decompressor<R> {
+impl<R: io::BufRead> io::Read for Decompressor<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match self {
             Decompressor::Stored(r) => r.read(buf),
@@ -221,12 +419,40 @@ impl<R: io::BufRead> io::Read for Decompressor<R> {
             Decompressor::Lzma(r) => r.read(buf),
             #[cfg(feature = "xz")]
             Decompressor::Xz(r) => r.read(buf),
+            #[cfg(feature = "legacy")]
+            Decompressor::Shrink(r) => r.read(buf),
+            #[cfg(feature = "legacy")]
+            Decompressor::Reduce(r) => r.read(buf),
+            #[cfg(feature = "legacy")]
+            Decompressor::Implode(r) => r.read(buf),
+            Decompressor::Custom { decoder, .. } => decoder.read(buf),
         }
     }
 }
 
-impl<R: io::BufRead> Decompressor<R> {
-    pub fn new(reader: R, compression_method: CompressionMethod) -> crate::result::ZipResult<Self> {
+/// The largest Zstd window log supported on this target.
+#[cfg(feature = "zstd")]
+pub(crate) const ZSTD_WINDOW_LOG_MAX: u32 = if cfg!(target_pointer_width = "32") {
+    30
+} else {
+    31
+};
+
+impl<'a, R: io::BufRead + 'a> Decompressor<'a, R> {
+    #[cfg_attr(not(all(feature = "legacy", feature = "xz")), allow(unused_variables))]
+    pub fn new(
+        reader: R,
+        compression_method: CompressionMethod,
+        uncompressed_size: u64,
+        flags: u16,
+        options: crate::read::ReadOptions,
+        registry: Option<&CompressionRegistry>,
+    ) -> crate::result::ZipResult<Self> {
+        if let Some(factory) = registry.and_then(|r| r.decoders.get(&compression_method)) {
+            let inner = Rc::new(RefCell::new(reader));
+            let decoder = factory(Box::new(SharedReader(inner.clone())))?;
+            return Ok(Decompressor::Custom { decoder, inner });
+        }
         Ok(match compression_method {
             CompressionMethod::Stored => Decompressor::Stored(reader),
             #[cfg(feature = "_deflate-any")]
@@ -240,13 +466,41 @@ impl<R: io::BufRead> Decompressor<R> {
             #[cfg(feature = "bzip2")]
             CompressionMethod::Bzip2 => Decompressor::Bzip2(bzip2::bufread::BzDecoder::new(reader)),
             #[cfg(feature = "zstd")]
-            CompressionMethod::Zstd => Decompressor::Zstd(zstd::Decoder::with_buffer(reader)?),
+            CompressionMethod::Zstd => {
+                let mut decoder = zstd::Decoder::with_buffer(reader)?;
+                if let Some(window_log_max) = options.zstd_window_log_max {
+                    decoder.window_log_max(window_log_max)?;
+                }
+                Decompressor::Zstd(decoder)
+            }
             #[cfg(feature = "lzma")]
             CompressionMethod::Lzma => {
-                Decompressor::Lzma(Box::new(crate::read::lzma::LzmaDecoder::new(reader)))
+                Decompressor::Lzma(Box::new(crate::read::lzma::LzmaDecoder::new(
+                    reader,
+                    // Bit 1 is set when the stream ends with an end-of-stream marker
+                    (flags & (1 << 1) == 0).then_some(uncompressed_size),
+                )))
             }
             #[cfg(feature = "xz")]
-            CompressionMethod::Xz => Decompressor::Xz(crate::read::xz::XzDecoder::new(reader)),
+            CompressionMethod::Xz => {
+                let mut decoder = crate::read::xz::XzDecoder::new(reader);
+                if let Some(max_dict_size) = options.max_dictionary_size {
+                    decoder = decoder.with_max_dict_size(max_dict_size);
+                }
+                Decompressor::Xz(decoder)
+            }
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Shrink => Decompressor::Shrink(
+                crate::read::shrink::ShrinkDecoder::new(reader, uncompressed_size),
+            ),
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Reduce(factor @ 1..=4) => Decompressor::Reduce(
+                crate::read::reduce::ReduceDecoder::new(reader, uncompressed_size, factor),
+            ),
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Implode => Decompressor::Implode(
+                crate::read::implode::ImplodeDecoder::new(reader, uncompressed_size, flags),
+            ),
             _ => {
                 return Err(crate::result::ZipError::UnsupportedArchive(
                     "Compression method not supported",
@@ -256,8 +510,8 @@ impl<R: io::BufRead> Decompressor<R> {
     }
 
     /// Consumes this decoder, returning the underlying reader.
-    pub fn into_inner(self) -> R {
-        match self {
+    pub fn into_inner(self) -> io::Result<R> {
+        Ok(match self {
             Decompressor::Stored(r) => r,
             #[cfg(feature = "_deflate-any")]
             Decompressor::Deflated(r) => r.into_inner(),
@@ -271,7 +525,24 @@ impl<R: io::BufRead> Decompressor<R> {
             Decompressor::Lzma(r) => r.into_inner(),
             #[cfg(feature = "xz")]
             Decompressor::Xz(r) => r.into_inner(),
-        }
+            #[cfg(feature = "legacy")]
+            Decompressor::Shrink(r) => r.into_inner(),
+            #[cfg(feature = "legacy")]
+            Decompressor::Reduce(r) => r.into_inner(),
+            #[cfg(feature = "legacy")]
+            Decompressor::Implode(r) => r.into_inner(),
+            Decompressor::Custom { decoder, inner } => {
+                drop(decoder);
+                Rc::try_unwrap(inner)
+                    .map_err(|_| {
+                        io::Error::new(
+                            io::ErrorKind::Other,
+                            "Custom decoder kept its reader after being dropped",
+                        )
+                    })?
+                    .into_inner()
+            }
+        })
     }
 }
 
@@ -314,4 +585,14 @@ mod test {
             check_match(method);
         }
     }
+
+    #[test]
+    fn public_u16_conversion() {
+        assert_eq!(CompressionMethod::from_u16(95), CompressionMethod::XZ);
+        assert_eq!(CompressionMethod::XZ.to_u16(), 95);
+        assert_eq!(CompressionMethod::from_u16(98), CompressionMethod::PPMD);
+        for v in [0, 8, 12, 14, 93, 95, 98, 99, 0x4242, u16::MAX] {
+            assert_eq!(CompressionMethod::from_u16(v).to_u16(), v);
+        }
+    }
 }
@@ -5,30 +5,87 @@ use std::io::prelude::*;
 
 use crc32fast::Hasher;
 
-/// Reader that validates the CRC32 when it reaches the EOF.
+use crate::result::ZipError;
+
+/// Reader that validates the CRC32, and optionally the size, when it reaches the EOF.
 pub struct Crc32Reader<R> {
     inner: R,
     hasher: Hasher,
     check: u32,
-    /// Signals if `inner` stores aes encrypted data.
+    /// Signals if the CRC32 should be checked.
     /// AE-2 encrypted data doesn't use crc and sets the value to 0.
     enabled: bool,
+    /// The number of bytes the data should have, if it's checked.
+    expected_size: Option<u64>,
+    bytes_read: u64,
+    /// Whether the EOF has been reached.
+    finished: bool,
+    /// Name of the entry being read, for error messages.
+    entry: Box<str>,
 }
 
 impl<R> Crc32Reader<R> {
     /// Get a new Crc32Reader which checks the inner reader against checksum.
-    /// The check is disabled if `ae2_encrypted == true`.
-    pub(crate) fn new(inner: R, checksum: u32, ae2_encrypted: bool) -> Crc32Reader<R> {
+    /// The check is skipped if `enabled == false`. The number of bytes read is checked against
+    /// `expected_size` if there is one.
+    pub(crate) fn new(
+        inner: R,
+        checksum: u32,
+        enabled: bool,
+        expected_size: Option<u64>,
+        entry: &str,
+    ) -> Crc32Reader<R> {
         Crc32Reader {
             inner,
             hasher: Hasher::new(),
             check: checksum,
-            enabled: !ae2_encrypted,
+            enabled,
+            expected_size,
+            bytes_read: 0,
+            finished: false,
+            entry: entry.into(),
         }
     }
 
-    fn check_matches(&self) -> bool {
-        self.check == self.hasher.clone().finalize()
+    /// Counts the bytes read, failing as soon as there are more than expected.
+    fn count(&mut self, count: usize) -> io::Result<()> {
+        self.bytes_read += count as u64;
+        match self.expected_size {
+            Some(expected) if self.bytes_read > expected => {
+                Err(size_mismatch(expected, self.bytes_read, &self.entry))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Called at the EOF.
+    fn check_end(&mut self) -> io::Result<()> {
+        self.finished = true;
+        match self.expected_size {
+            Some(expected) if self.bytes_read != expected => {
+                return Err(size_mismatch(expected, self.bytes_read, &self.entry))
+            }
+            _ => {}
+        }
+        if self.enabled {
+            self.check_matches()?;
+        }
+        Ok(())
+    }
+
+    fn check_matches(&self) -> io::Result<()> {
+        let actual = self.hasher.clone().finalize();
+        if self.check == actual {
+            Ok(())
+        } else {
+            Err(invalid_checksum(self.check, actual, &self.entry))
+        }
+    }
+
+    /// The CRC32 of the data, once it has been read to the EOF. `None` if the CRC32 isn't being
+    /// computed.
+    pub(crate) fn computed_crc32(&self) -> Option<u32> {
+        (self.enabled && self.finished).then(|| self.hasher.clone().finalize())
     }
 
     pub fn into_inner(self) -> R {
@@ -37,18 +94,34 @@ impl<R> Crc32Reader<R> {
 }
 
 #[cold]
-fn invalid_checksum() -> io::Error {
-    io::Error::new(io::ErrorKind::InvalidData, "Invalid checksum")
+fn size_mismatch(expected: u64, actual: u64, entry: &str) -> io::Error {
+    ZipError::SizeMismatch {
+        expected,
+        actual,
+        entry: entry.to_string(),
+    }
+    .into()
+}
+
+#[cold]
+fn invalid_checksum(expected: u32, actual: u32, entry: &str) -> io::Error {
+    ZipError::Crc32Mismatch {
+        expected,
+        actual,
+        entry: entry.to_string(),
+    }
+    .into()
 }
 
 impl<R: Read> Read for Crc32Reader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let count = self.inner.read(buf)?;
 
+        if count == 0 && !buf.is_empty() {
+            self.check_end()?;
+        }
+        self.count(count)?;
         if self.enabled {
-            if count == 0 && !buf.is_empty() && !self.check_matches() {
-                return Err(invalid_checksum());
-            }
             self.hasher.update(&buf[..count]);
         }
         Ok(count)
@@ -58,12 +131,11 @@ impl<R: Read> Read for Crc32Reader<R> {
         let start = buf.len();
         let n = self.inner.read_to_end(buf)?;
 
+        self.count(n)?;
         if self.enabled {
             self.hasher.update(&buf[start..]);
-            if !self.check_matches() {
-                return Err(invalid_checksum());
-            }
         }
+        self.check_end()?;
 
         Ok(n)
     }
@@ -72,12 +144,11 @@ impl<R: Read> Read for Crc32Reader<R> {
         let start = buf.len();
         let n = self.inner.read_to_string(buf)?;
 
+        self.count(n)?;
         if self.enabled {
             self.hasher.update(&buf.as_bytes()[start..]);
-            if !self.check_matches() {
-                return Err(invalid_checksum());
-            }
         }
+        self.check_end()?;
 
         Ok(n)
     }
@@ -92,10 +163,10 @@ mod test {
         let data: &[u8] = b"";
         let mut buf = [0; 1];
 
-        let mut reader = Crc32Reader::new(data, 0, false);
+        let mut reader = Crc32Reader::new(data, 0, true, None, "empty");
         assert_eq!(reader.read(&mut buf).unwrap(), 0);
 
-        let mut reader = Crc32Reader::new(data, 1, false);
+        let mut reader = Crc32Reader::new(data, 1, true, None, "empty");
         assert!(reader
             .read(&mut buf)
             .unwrap_err()
@@ -108,7 +179,7 @@ mod test {
         let data: &[u8] = b"1234";
         let mut buf = [0; 1];
 
-        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, false);
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, true, None, "1234");
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
@@ -123,8 +194,33 @@ mod test {
         let data: &[u8] = b"1234";
         let mut buf = [0; 5];
 
-        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, false);
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, true, None, "1234");
         assert_eq!(reader.read(&mut buf[..0]).unwrap(), 0);
         assert_eq!(reader.read(&mut buf).unwrap(), 4);
     }
+
+    #[test]
+    fn test_size_mismatch() {
+        let data: &[u8] = b"1234";
+        let mut buf = [0; 5];
+
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, true, Some(4), "1234");
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, true, Some(5), "1234");
+        assert_eq!(reader.read(&mut buf).unwrap(), 4);
+        assert!(reader
+            .read(&mut buf)
+            .unwrap_err()
+            .to_string()
+            .contains("Size mismatch"));
+
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, true, Some(3), "1234");
+        assert!(reader
+            .read_to_end(&mut Vec::new())
+            .unwrap_err()
+            .to_string()
+            .contains("Size mismatch"));
+    }
 }
@@ -17,14 +17,31 @@ impl ExtraFieldVersion for LocalHeaderVersion {}
 impl ExtraFieldVersion for CentralHeaderVersion {}
 
 mod extended_timestamp;
+mod ntfs;
+mod unix;
 mod zipinfo_utf8;
 
 pub use extended_timestamp::*;
+pub use ntfs::*;
+pub use unix::*;
 pub use zipinfo_utf8::*;
 
 /// contains one extra field
+///
+/// More variants may be added as other extra fields are parsed, so a `match` on it needs a
+/// wildcard arm.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ExtraField {
     /// extended timestamp, as described in <https://libzip.org/specifications/extrafld.txt>
     ExtendedTimestamp(ExtendedTimestamp),
+
+    /// NTFS timestamps, as described in APPNOTE 4.5.5
+    NtfsTimes(NtfsTimes),
+
+    /// Info-ZIP Unix user and group IDs, as described in <https://libzip.org/specifications/extrafld.txt>
+    InfoZipUnix(InfoZipUnix),
+
+    /// Info-ZIP Unix2 user and group IDs, as described in <https://libzip.org/specifications/extrafld.txt>
+    InfoZipUnix2(InfoZipUnix2),
 }
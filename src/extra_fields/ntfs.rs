@@ -0,0 +1,99 @@
+use crate::result::ZipResult;
+use crate::unstable::LittleEndianReadExt;
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between 1601-01-01, the start of the NTFS epoch, and the UNIX epoch.
+const NTFS_EPOCH_OFFSET_SECS: u64 = 11_644_473_600;
+
+/// NTFS timestamps, as described in APPNOTE 4.5.5
+///
+/// The times are Windows `FILETIME` values: the number of 100-nanosecond intervals since
+/// 1601-01-01 UTC. A value of 0 means the time wasn't recorded.
+#[derive(Debug, Clone)]
+pub struct NtfsTimes {
+    mtime: u64,
+    atime: u64,
+    ctime: u64,
+}
+
+impl NtfsTimes {
+    /// creates an NTFS timestamps struct by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument. Returns `None` if the field doesn't contain
+    /// the timestamps attribute.
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Option<Self>>
+    where
+        R: Read,
+    {
+        let mut remaining = len as usize;
+        if remaining < 4 {
+            reader.read_exact(&mut vec![0u8; remaining])?;
+            return Ok(None);
+        }
+        // reserved
+        reader.read_u32_le()?;
+        remaining -= 4;
+
+        let mut times = None;
+        while remaining >= 4 {
+            let tag = reader.read_u16_le()?;
+            let size = reader.read_u16_le()? as usize;
+            remaining -= 4;
+            let size = size.min(remaining);
+            if tag == 0x0001 && size == 24 {
+                times = Some(Self {
+                    mtime: reader.read_u64_le()?,
+                    atime: reader.read_u64_le()?,
+                    ctime: reader.read_u64_le()?,
+                });
+            } else {
+                reader.read_exact(&mut vec![0u8; size])?;
+            }
+            remaining -= size;
+        }
+        reader.read_exact(&mut vec![0u8; remaining])?;
+        Ok(times)
+    }
+
+    /// returns the last modification time as a `FILETIME` value
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    /// returns the last access time as a `FILETIME` value
+    pub fn atime(&self) -> u64 {
+        self.atime
+    }
+
+    /// returns the creation time as a `FILETIME` value
+    pub fn ctime(&self) -> u64 {
+        self.ctime
+    }
+
+    /// returns the last modification time, if recorded
+    pub fn modified(&self) -> Option<SystemTime> {
+        filetime_to_system_time(self.mtime)
+    }
+
+    /// returns the last access time, if recorded
+    pub fn accessed(&self) -> Option<SystemTime> {
+        filetime_to_system_time(self.atime)
+    }
+
+    /// returns the creation time, if recorded
+    pub fn created(&self) -> Option<SystemTime> {
+        filetime_to_system_time(self.ctime)
+    }
+}
+
+fn filetime_to_system_time(filetime: u64) -> Option<SystemTime> {
+    if filetime == 0 {
+        return None;
+    }
+    let since_ntfs_epoch =
+        Duration::new(filetime / 10_000_000, (filetime % 10_000_000) as u32 * 100);
+    let ntfs_epoch = UNIX_EPOCH.checked_sub(Duration::from_secs(NTFS_EPOCH_OFFSET_SECS))?;
+    ntfs_epoch.checked_add(since_ntfs_epoch)
+}
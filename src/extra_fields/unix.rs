@@ -0,0 +1,187 @@
+use super::ExtraField;
+use crate::result::{ZipError, ZipResult};
+use std::io::Read;
+
+/// Info-ZIP Unix extra field (0x7875), holding the user and group IDs of a file, as described in
+/// <https://libzip.org/specifications/extrafld.txt>
+#[derive(Debug, Clone)]
+pub struct InfoZipUnix {
+    uid: u64,
+    gid: u64,
+}
+
+impl InfoZipUnix {
+    /// creates an Info-ZIP Unix struct by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument. Returns `None` for versions of the field
+    /// other than 1 and for IDs that don't fit in a `u64`.
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Option<Self>>
+    where
+        R: Read,
+    {
+        let mut data = vec![0u8; len as usize];
+        reader.read_exact(&mut data)?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+        let [version, uid_size, rest @ ..] = &data[..] else {
+            return Err(ZipError::InvalidArchive(
+                "Info-ZIP Unix extra field is too short",
+            ));
+        };
+        if *version != 1 {
+            return Ok(None);
+        }
+        let uid_size = *uid_size as usize;
+        let (uid, [gid_size, gid @ ..]) = rest.split_at(uid_size.min(rest.len())) else {
+            return Err(ZipError::InvalidArchive(
+                "Info-ZIP Unix extra field is too short",
+            ));
+        };
+        if gid.len() != *gid_size as usize {
+            return Err(ZipError::InvalidArchive(
+                "Info-ZIP Unix extra field has the wrong length",
+            ));
+        }
+        Ok(read_id(uid)
+            .zip(read_id(gid))
+            .map(|(uid, gid)| Self { uid, gid }))
+    }
+
+    /// returns the user ID of the file's owner
+    pub fn uid(&self) -> u64 {
+        self.uid
+    }
+
+    /// returns the group ID of the file's owner
+    pub fn gid(&self) -> u64 {
+        self.gid
+    }
+}
+
+/// Info-ZIP Unix2 extra field (0x7855), holding the 16-bit user and group IDs of a file in the
+/// local header, as described in <https://libzip.org/specifications/extrafld.txt>
+///
+/// Older versions of Info-ZIP write this instead of [`InfoZipUnix`].
+#[derive(Debug, Clone)]
+pub struct InfoZipUnix2 {
+    uid: u16,
+    gid: u16,
+}
+
+impl InfoZipUnix2 {
+    /// creates an Info-ZIP Unix2 struct by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument. Returns `None` for the empty field of a central header,
+    /// and for a field of any other unexpected length, since the IDs aren't needed to read the
+    /// file.
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Option<Self>>
+    where
+        R: Read,
+    {
+        let mut data = vec![0u8; len as usize];
+        reader.read_exact(&mut data)?;
+        match data[..] {
+            [uid_low, uid_high, gid_low, gid_high] => Ok(Some(Self {
+                uid: u16::from_le_bytes([uid_low, uid_high]),
+                gid: u16::from_le_bytes([gid_low, gid_high]),
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    /// returns the user ID of the file's owner
+    pub fn uid(&self) -> u16 {
+        self.uid
+    }
+
+    /// returns the group ID of the file's owner
+    pub fn gid(&self) -> u16 {
+        self.gid
+    }
+}
+
+/// Reads a little-endian ID of any size up to 8 bytes.
+fn read_id(bytes: &[u8]) -> Option<u64> {
+    let mut id = [0u8; 8];
+    id.get_mut(..bytes.len())?.copy_from_slice(bytes);
+    Some(u64::from_le_bytes(id))
+}
+
+/// Unix metadata of a file, gathered from the extended timestamp (0x5455), Info-ZIP Unix
+/// (0x7875) and Info-ZIP Unix2 (0x7855) extra fields. The IDs in a Unix field take precedence
+/// over those in a Unix2 field.
+///
+/// The central directory only records the modification time, so the access and creation times
+/// are only available when the local header is parsed, for example with
+/// [`read_zipfile_from_stream`](crate::read::read_zipfile_from_stream). The same goes for the
+/// IDs of a Unix2 field.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnixExtra {
+    mtime: Option<u32>,
+    atime: Option<u32>,
+    ctime: Option<u32>,
+    uid: Option<u64>,
+    gid: Option<u64>,
+}
+
+impl UnixExtra {
+    pub(crate) fn from_extra_fields<'a>(
+        fields: impl IntoIterator<Item = &'a ExtraField>,
+    ) -> Option<Self> {
+        let mut unix_extra = None;
+        let mut has_unix_ids = false;
+        for field in fields {
+            match field {
+                ExtraField::ExtendedTimestamp(ts) => {
+                    let unix_extra = unix_extra.get_or_insert_with(Self::default);
+                    unix_extra.mtime = ts.mod_time();
+                    unix_extra.atime = ts.ac_time();
+                    unix_extra.ctime = ts.cr_time();
+                }
+                ExtraField::InfoZipUnix(ids) => {
+                    let unix_extra = unix_extra.get_or_insert_with(Self::default);
+                    unix_extra.uid = Some(ids.uid());
+                    unix_extra.gid = Some(ids.gid());
+                    has_unix_ids = true;
+                }
+                ExtraField::InfoZipUnix2(ids) => {
+                    let unix_extra = unix_extra.get_or_insert_with(Self::default);
+                    if !has_unix_ids {
+                        unix_extra.uid = Some(ids.uid().into());
+                        unix_extra.gid = Some(ids.gid().into());
+                    }
+                }
+                _ => {}
+            }
+        }
+        unix_extra
+    }
+
+    /// returns the last modification timestamp, if defined, as UNIX epoch seconds
+    pub fn mtime(&self) -> Option<u32> {
+        self.mtime
+    }
+
+    /// returns the last access timestamp, if defined, as UNIX epoch seconds
+    pub fn atime(&self) -> Option<u32> {
+        self.atime
+    }
+
+    /// returns the creation timestamp, if defined, as UNIX epoch seconds
+    pub fn ctime(&self) -> Option<u32> {
+        self.ctime
+    }
+
+    /// returns the user ID of the file's owner, if defined
+    pub fn uid(&self) -> Option<u64> {
+        self.uid
+    }
+
+    /// returns the group ID of the file's owner, if defined
+    pub fn gid(&self) -> Option<u64> {
+        self.gid
+    }
+}
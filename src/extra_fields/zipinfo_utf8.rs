@@ -7,6 +7,7 @@ use std::io::Read;
 /// specified in APPNOTE 4.6.8 and 4.6.9
 #[derive(Clone, Debug)]
 pub struct UnicodeExtraField {
+    version: u8,
     crc32: u32,
     content: Box<[u8]>,
 }
@@ -24,12 +25,27 @@ impl UnicodeExtraField {
         }
         Ok(self.content)
     }
+
+    /// Only version 1 of the field is defined; readers skip fields with any other version.
+    pub(crate) const fn is_supported_version(&self) -> bool {
+        self.version == 1
+    }
+
+    /// Returns the UTF-8 content if the field is version 1 and its checksum matches
+    /// `ascii_field`. Otherwise the field is stale, left by a tool that changed the header
+    /// without updating it, and should be ignored.
+    pub(crate) fn valid_utf8(self, ascii_field: &[u8]) -> Option<String> {
+        if !self.is_supported_version() || self.crc32 != crc32fast::hash(ascii_field) {
+            return None;
+        }
+        String::from_utf8(self.content.into_vec()).ok()
+    }
 }
 
 impl UnicodeExtraField {
     pub(crate) fn try_from_reader<R: Read>(reader: &mut R, len: u16) -> ZipResult<Self> {
-        // Read and discard version byte
-        reader.read_exact(&mut [0u8])?;
+        let mut version = [0u8];
+        reader.read_exact(&mut version)?;
 
         let crc32 = reader.read_u32_le()?;
         let content_len = (len as usize)
@@ -37,6 +53,10 @@ impl UnicodeExtraField {
             .ok_or(ZipError::InvalidArchive("Unicode extra field is too small"))?;
         let mut content = vec![0u8; content_len].into_boxed_slice();
         reader.read_exact(&mut content)?;
-        Ok(Self { crc32, content })
+        Ok(Self {
+            version: version[0],
+            crc32,
+            content,
+        })
     }
 }
@@ -29,11 +29,13 @@
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 #![warn(missing_docs)]
 #![allow(unexpected_cfgs)] // Needed for cfg(fuzzing) on nightly as of 2024-05-06
-pub use crate::compression::{CompressionMethod, SUPPORTED_COMPRESSION_METHODS};
+pub use crate::compression::{
+    CompressionMethod, CompressionRegistry, SUPPORTED_COMPRESSION_METHODS,
+};
 pub use crate::read::HasZipMetadata;
 pub use crate::read::ZipArchive;
 pub use crate::spec::{ZIP64_BYTES_THR, ZIP64_ENTRY_THR};
-pub use crate::types::{AesMode, DateTime};
+pub use crate::types::{AesMode, DateTime, DeflateHint};
 pub use crate::write::ZipWriter;
 
 #[cfg(feature = "aes-crypto")]
@@ -2,56 +2,76 @@
 
 #[cfg(feature = "aes-crypto")]
 use crate::aes::{AesReader, AesReaderValid};
-use crate::compression::{CompressionMethod, Decompressor};
+use crate::compression::{CompressionMethod, CompressionRegistry, Decompressor, SharedReader};
 use crate::cp437::FromCp437;
 use crate::crc32::Crc32Reader;
-use crate::extra_fields::{ExtendedTimestamp, ExtraField};
+use crate::extra_fields::{
+    ExtendedTimestamp, ExtraField, InfoZipUnix, InfoZipUnix2, NtfsTimes, UnixExtra,
+};
 use crate::read::zip_archive::{Shared, SharedBuilder};
 use crate::result::{ZipError, ZipResult};
 use crate::spec::{self, FixedSizeBlock, Pod, Zip32CentralDirectoryEnd, ZIP64_ENTRY_THR};
 use crate::types::{
-    AesMode, AesVendorVersion, DateTime, System, ZipCentralEntryBlock, ZipFileData,
+    AesMode, AesVendorVersion, DateTime, DeflateHint, System, ZipCentralEntryBlock, ZipFileData,
     ZipLocalEntryBlock,
 };
 use crate::zipcrypto::{ZipCryptoReader, ZipCryptoReaderValid, ZipCryptoValidator};
 use indexmap::IndexMap;
 use std::borrow::Cow;
-use std::ffi::OsString;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::create_dir_all;
 use std::io::{self, copy, prelude::*, sink, SeekFrom};
 use std::mem;
 use std::mem::size_of;
-use std::ops::Deref;
+use std::ops::{Deref, Range};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 
 mod config;
+mod glob;
+mod multi_volume;
 
 pub use config::*;
+pub use multi_volume::MultiVolumeReader;
 
 /// Provides high level API for reading from a stream.
 pub(crate) mod stream;
 
+#[cfg(feature = "legacy")]
+mod bit_reader;
+#[cfg(feature = "legacy")]
+pub(crate) mod implode;
+#[cfg(feature = "legacy")]
+pub(crate) mod reduce;
+#[cfg(feature = "legacy")]
+pub(crate) mod shrink;
+
 #[cfg(feature = "lzma")]
 pub(crate) mod lzma;
 
+/// Decoder for XZ streams, as used by [`CompressionMethod::Xz`] entries.
 #[cfg(feature = "xz")]
-pub(crate) mod xz;
+pub mod xz;
 
 // Put the struct declaration in a private module to convince rustdoc to display ZipArchive nicely
 pub(crate) mod zip_archive {
-    use indexmap::IndexMap;
-    use std::sync::Arc;
+    use std::collections::HashMap;
+    use std::sync::{Arc, OnceLock};
 
     /// Extract immutable data from `ZipArchive` to make it cheap to clone
     #[derive(Debug)]
     pub(crate) struct Shared {
-        pub(crate) files: super::IndexMap<Box<str>, super::ZipFileData>,
+        /// Every entry, in central directory order, including any with duplicate names.
+        pub(crate) files: Vec<super::ZipFileData>,
+        /// Indices into `files` of the entries with each name, in central directory order.
+        pub(super) names: HashMap<Box<str>, Vec<usize>>,
+        /// Indices into `files`, sorted by name. Built on first use.
+        pub(super) sorted_indices: OnceLock<Box<[usize]>>,
         pub(super) offset: u64,
         pub(super) dir_start: u64,
-        // This isn't yet used anywhere, but it is here for use cases in the future.
-        #[allow(dead_code)]
         pub(super) config: super::Config,
     }
 
@@ -67,17 +87,35 @@ pub(crate) mod zip_archive {
 
     impl SharedBuilder {
         pub fn build(self) -> Shared {
-            let mut index_map = IndexMap::with_capacity(self.files.len());
-            self.files.into_iter().for_each(|file| {
-                index_map.insert(file.file_name.clone(), file);
-            });
+            Shared::new(self.files, self.offset, self.dir_start, self.config)
+        }
+    }
+
+    impl Shared {
+        pub(crate) fn new(
+            files: Vec<super::ZipFileData>,
+            offset: u64,
+            dir_start: u64,
+            config: super::Config,
+        ) -> Self {
+            let mut names: HashMap<Box<str>, Vec<usize>> = HashMap::with_capacity(files.len());
+            for (index, file) in files.iter().enumerate() {
+                names.entry(file.file_name.clone()).or_default().push(index);
+            }
             Shared {
-                files: index_map,
-                offset: self.offset,
-                dir_start: self.dir_start,
-                config: self.config,
+                files,
+                names,
+                sorted_indices: OnceLock::new(),
+                offset,
+                dir_start,
+                config,
             }
         }
+
+        /// The index of the last entry with the given name.
+        pub(crate) fn index_for_name(&self, name: &str) -> Option<usize> {
+            self.names.get(name)?.last().copied()
+        }
     }
 
     /// ZIP archive reader
@@ -106,6 +144,7 @@ pub(crate) mod zip_archive {
         pub(super) reader: R,
         pub(super) shared: Arc<Shared>,
         pub(super) comment: Arc<[u8]>,
+        pub(super) registry: Option<Arc<crate::CompressionRegistry>>,
     }
 }
 
@@ -113,7 +152,7 @@ pub(crate) mod zip_archive {
 use crate::aes::PWD_VERIFY_LENGTH;
 use crate::extra_fields::UnicodeExtraField;
 use crate::result::ZipError::{InvalidArchive, InvalidPassword};
-use crate::spec::is_dir;
+use crate::spec::is_macos_metadata;
 use crate::types::ffi::S_IFLNK;
 use crate::unstable::{path_to_string, LittleEndianReadExt};
 pub use zip_archive::ZipArchive;
@@ -195,7 +234,124 @@ fn invalid_state<T>() -> io::Result<T> {
 pub(crate) enum ZipFileReader<'a> {
     NoReader,
     Raw(io::Take<&'a mut dyn Read>),
-    Compressed(Box<Crc32Reader<Decompressor<io::BufReader<CryptoReader<'a>>>>>),
+    Compressed(Box<DecompressingReader<'a, CryptoReader<'a>>>),
+    #[cfg(feature = "_deflate-any")]
+    DataDescriptor(Box<DataDescriptorReader<'a>>),
+}
+
+/// Fails with [`ZipError::DecompressionLimitExceeded`] once more than the given number of bytes
+/// have been read, if there is a limit.
+pub(crate) struct ExpansionGuard<R> {
+    inner: R,
+    remaining: Option<u64>,
+}
+
+impl<R> ExpansionGuard<R> {
+    fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for ExpansionGuard<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining
+                .checked_sub(count as u64)
+                .ok_or(ZipError::DecompressionLimitExceeded)?;
+        }
+        Ok(count)
+    }
+}
+
+/// Reads a Deflated entry whose sizes and CRC-32 are only known from the data descriptor that
+/// follows it. The end of the compressed data is found by decompressing it, after which the
+/// descriptor is read and checked against the data.
+#[cfg(feature = "_deflate-any")]
+pub(crate) struct DataDescriptorReader<'a> {
+    decoder: flate2::bufread::DeflateDecoder<&'a mut dyn BufRead>,
+    hasher: crc32fast::Hasher,
+    uncompressed_size: u64,
+    large_file: bool,
+    entry: Box<str>,
+    done: bool,
+}
+
+#[cfg(feature = "_deflate-any")]
+impl<'a> DataDescriptorReader<'a> {
+    fn new(data: &ZipFileData, reader: &'a mut dyn BufRead) -> Self {
+        DataDescriptorReader {
+            decoder: flate2::bufread::DeflateDecoder::new(reader),
+            hasher: crc32fast::Hasher::new(),
+            uncompressed_size: 0,
+            large_file: data.large_file,
+            entry: data.file_name.clone(),
+            done: false,
+        }
+    }
+
+    /// Reads the descriptor, which may or may not start with a signature and may have 4-byte or
+    /// (for Zip64) 8-byte sizes. Each layout is checked against the data that was read, so that
+    /// exactly the descriptor's bytes are consumed and the next header is found.
+    fn read_data_descriptor(&mut self) -> ZipResult<()> {
+        let compressed_size = self.decoder.total_in();
+        let actual = self.hasher.clone().finalize();
+        let reader = self.decoder.get_mut();
+        let signature = spec::Magic::DATA_DESCRIPTOR_SIGNATURE;
+        let mut words = vec![reader.read_u32_le()?];
+        // A CRC-32 equal to the signature is only taken as a signature if it's repeated
+        let has_signature = spec::Magic::literal(words[0]) == signature
+            && (actual != words[0] || {
+                words.push(reader.read_u32_le()?);
+                spec::Magic::literal(words[1]) == signature
+            });
+        let crc_index = has_signature as usize;
+        while words.len() < crc_index + 3 {
+            words.push(reader.read_u32_le()?);
+        }
+        let crc32 = words[crc_index];
+        let (low, high) = (words[crc_index + 1], words[crc_index + 2]);
+        let zip32_matches =
+            u64::from(low) == compressed_size && u64::from(high) == self.uncompressed_size;
+        let sizes_match = if self.large_file || !zip32_matches {
+            let compressed = u64::from(low) | u64::from(high) << 32;
+            let uncompressed = reader.read_u64_le()?;
+            compressed == compressed_size && uncompressed == self.uncompressed_size
+        } else {
+            true
+        };
+        if !sizes_match {
+            return Err(ZipError::InvalidArchive(
+                "Data descriptor sizes don't match the entry data",
+            ));
+        }
+        if crc32 != actual {
+            return Err(ZipError::Crc32Mismatch {
+                expected: crc32,
+                actual,
+                entry: self.entry.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "_deflate-any")]
+impl<'a> Read for DataDescriptorReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let count = self.decoder.read(buf)?;
+        if count == 0 {
+            self.done = true;
+            self.read_data_descriptor()?;
+            return Ok(0);
+        }
+        self.hasher.update(&buf[..count]);
+        self.uncompressed_size += count as u64;
+        Ok(count)
+    }
 }
 
 impl<'a> Read for ZipFileReader<'a> {
@@ -204,6 +360,8 @@ impl<'a> Read for ZipFileReader<'a> {
             ZipFileReader::NoReader => invalid_state(),
             ZipFileReader::Raw(r) => r.read(buf),
             ZipFileReader::Compressed(r) => r.read(buf),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileReader::DataDescriptor(r) => r.read(buf),
         }
     }
 
@@ -212,6 +370,8 @@ impl<'a> Read for ZipFileReader<'a> {
             ZipFileReader::NoReader => invalid_state(),
             ZipFileReader::Raw(r) => r.read_exact(buf),
             ZipFileReader::Compressed(r) => r.read_exact(buf),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileReader::DataDescriptor(r) => r.read_exact(buf),
         }
     }
 
@@ -220,6 +380,8 @@ impl<'a> Read for ZipFileReader<'a> {
             ZipFileReader::NoReader => invalid_state(),
             ZipFileReader::Raw(r) => r.read_to_end(buf),
             ZipFileReader::Compressed(r) => r.read_to_end(buf),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileReader::DataDescriptor(r) => r.read_to_end(buf),
         }
     }
 
@@ -228,6 +390,8 @@ impl<'a> Read for ZipFileReader<'a> {
             ZipFileReader::NoReader => invalid_state(),
             ZipFileReader::Raw(r) => r.read_to_string(buf),
             ZipFileReader::Compressed(r) => r.read_to_string(buf),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileReader::DataDescriptor(r) => r.read_to_string(buf),
         }
     }
 }
@@ -237,9 +401,14 @@ impl<'a> ZipFileReader<'a> {
         match self {
             ZipFileReader::NoReader => invalid_state(),
             ZipFileReader::Raw(r) => Ok(r),
-            ZipFileReader::Compressed(r) => {
-                Ok(r.into_inner().into_inner().into_inner().into_inner())
-            }
+            ZipFileReader::Compressed(r) => Ok(r
+                .into_inner()
+                .into_inner()
+                .into_inner()?
+                .into_inner()
+                .into_inner()),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileReader::DataDescriptor(_) => invalid_state(),
         }
     }
 }
@@ -258,6 +427,52 @@ pub struct ZipFileSeek<'a, R> {
 
 enum ZipFileSeekReader<'a, R> {
     Raw(SeekableTake<'a, R>),
+    #[cfg(feature = "_deflate-any")]
+    Deflated(DeflatedSeekReader<'a, R>),
+}
+
+/// Seeks within a Deflate entry by decompressing and discarding data. Seeking backwards starts
+/// decompressing again from the beginning of the entry.
+#[cfg(feature = "_deflate-any")]
+struct DeflatedSeekReader<'a, R> {
+    decoder: flate2::bufread::DeflateDecoder<io::BufReader<SeekableTake<'a, R>>>,
+    uncompressed_size: u64,
+    position: u64,
+}
+
+#[cfg(feature = "_deflate-any")]
+impl<'a, R: Read> Read for DeflatedSeekReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.decoder.read(buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "_deflate-any")]
+impl<'a, R: Read + Seek> Seek for DeflatedSeekReader<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let offset = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.uncompressed_size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        let Some(offset) = offset else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+        let offset = std::cmp::min(self.uncompressed_size, offset);
+        if offset < self.position {
+            self.decoder.get_mut().seek(SeekFrom::Start(0))?;
+            self.decoder.reset_data();
+            self.position = 0;
+        }
+        let skip = offset - self.position;
+        io::copy(&mut self.by_ref().take(skip), &mut io::sink())?;
+        Ok(self.position)
+    }
 }
 
 struct SeekableTake<'a, R> {
@@ -297,7 +512,7 @@ impl<'a, R: Seek> Seek for SeekableTake<'a, R> {
                     .inner
                     .seek(SeekFrom::Start(self.inner_starting_offset + clamped_offset))?;
                 self.current_offset = new_inner_offset - self.inner_starting_offset;
-                Ok(new_inner_offset)
+                Ok(self.current_offset)
             }
         }
     }
@@ -344,6 +559,15 @@ fn find_data_start(
     data: &ZipFileData,
     reader: &mut (impl Read + Seek + Sized),
 ) -> Result<u64, ZipError> {
+    read_local_block(data, reader).map(|(_, data_start)| data_start)
+}
+
+/// Reads the fixed-size part of the local header of `data`, returning it along with the start of
+/// the file data.
+fn read_local_block(
+    data: &ZipFileData,
+    reader: &mut (impl Read + Seek + Sized),
+) -> ZipResult<(ZipLocalEntryBlock, u64)> {
     // Go to start of data.
     reader.seek(io::SeekFrom::Start(data.header_start))?;
 
@@ -366,7 +590,64 @@ fn find_data_start(
             debug_assert_eq!(*data.data_start.get().unwrap(), data_start);
         }
     }
-    Ok(data_start)
+    Ok((block, data_start))
+}
+
+/// Fails with [`ZipError::HeaderMismatch`] unless the local header of `data` has the name,
+/// compression method and sizes of its central directory record. Sizes are only compared if the
+/// local header has them, rather than a data descriptor.
+fn check_local_header(data: &ZipFileData, reader: &mut (impl Read + Seek)) -> ZipResult<()> {
+    let mismatch = |field| ZipError::HeaderMismatch {
+        field,
+        entry: data.file_name.to_string(),
+    };
+    reader.seek(io::SeekFrom::Start(data.header_start))?;
+    let block = ZipLocalEntryBlock::parse(reader)?;
+    let file_name_raw = read_variable_length_byte_field(reader, block.file_name_length as usize)?;
+    let extra_field = read_variable_length_byte_field(reader, block.extra_field_length as usize)?;
+
+    if file_name_raw != data.file_name_raw {
+        return Err(mismatch("name"));
+    }
+    // The central record's method was replaced by the one in the AES extra field
+    let compression_method = match data.aes_mode {
+        Some(_) => CompressionMethod::AES,
+        None => data.compression_method,
+    };
+    if block.compression_method != compression_method.serialize_to_u16() {
+        return Err(mismatch("compression method"));
+    }
+    if block.flags & (1 << 3) != 0 {
+        return Ok(());
+    }
+
+    let mut uncompressed_size = block.uncompressed_size as u64;
+    let mut compressed_size = block.compressed_size as u64;
+    let mut extra_field = &extra_field[..];
+    while extra_field.len() >= 4 {
+        let kind = extra_field.read_u16_le()?;
+        let len = extra_field.read_u16_le()? as usize;
+        let Some(field) = extra_field.get(..len) else {
+            break;
+        };
+        if kind == 0x0001 {
+            let mut field = field;
+            if uncompressed_size == spec::ZIP64_BYTES_THR {
+                uncompressed_size = field.read_u64_le()?;
+            }
+            if compressed_size == spec::ZIP64_BYTES_THR {
+                compressed_size = field.read_u64_le()?;
+            }
+        }
+        extra_field = &extra_field[len..];
+    }
+    if uncompressed_size != data.uncompressed_size {
+        return Err(mismatch("uncompressed size"));
+    }
+    if compressed_size != data.compressed_size {
+        return Err(mismatch("compressed size"));
+    }
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -375,11 +656,14 @@ pub(crate) fn make_crypto_reader<'a>(
     reader: io::Take<&'a mut dyn Read>,
     password: Option<&[u8]>,
     aes_info: Option<(AesMode, AesVendorVersion, CompressionMethod)>,
+    registry: Option<&CompressionRegistry>,
 ) -> ZipResult<CryptoReader<'a>> {
     #[allow(deprecated)]
     {
         if let CompressionMethod::Unsupported(_) = data.compression_method {
-            return unsupported_zip_error("Compression method not supported");
+            if !registry.is_some_and(|registry| registry.has_decoder(data.compression_method)) {
+                return unsupported_zip_error("Compression method not supported");
+            }
         }
     }
 
@@ -413,18 +697,64 @@ pub(crate) fn make_crypto_reader<'a>(
     Ok(reader)
 }
 
-pub(crate) fn make_reader(
-    compression_method: CompressionMethod,
-    crc32: u32,
-    reader: CryptoReader,
-) -> ZipResult<ZipFileReader> {
-    let ae2_encrypted = reader.is_ae2_encrypted();
+pub(crate) fn make_reader<'a>(
+    data: &ZipFileData,
+    verify_crc32: bool,
+    verify_size: bool,
+    max_uncompressed_size: Option<u64>,
+    reader: CryptoReader<'a>,
+    options: ReadOptions,
+    registry: Option<&CompressionRegistry>,
+) -> ZipResult<ZipFileReader<'a>> {
+    let verify_crc32 = verify_crc32 && !reader.is_ae2_encrypted();
+    Ok(ZipFileReader::Compressed(Box::new(
+        make_decompressing_reader(
+            data,
+            verify_crc32,
+            verify_size,
+            max_uncompressed_size,
+            reader,
+            options,
+            registry,
+        )?,
+    )))
+}
+
+/// Decompresses the file data read from `reader`, checking it against the CRC-32 and size of
+/// `data`.
+type DecompressingReader<'a, R> = Crc32Reader<ExpansionGuard<Decompressor<'a, io::BufReader<R>>>>;
 
-    Ok(ZipFileReader::Compressed(Box::new(Crc32Reader::new(
-        Decompressor::new(io::BufReader::new(reader), compression_method)?,
-        crc32,
-        ae2_encrypted,
-    ))))
+fn make_decompressing_reader<'a, R: Read + 'a>(
+    data: &ZipFileData,
+    verify_crc32: bool,
+    verify_size: bool,
+    max_uncompressed_size: Option<u64>,
+    reader: R,
+    options: ReadOptions,
+    registry: Option<&CompressionRegistry>,
+) -> ZipResult<DecompressingReader<'a, R>> {
+    let reader = match options.buffer_size {
+        Some(size) => io::BufReader::with_capacity(size, reader),
+        None => io::BufReader::new(reader),
+    };
+
+    Ok(Crc32Reader::new(
+        ExpansionGuard {
+            inner: Decompressor::new(
+                reader,
+                data.compression_method,
+                data.uncompressed_size,
+                data.general_purpose_flags,
+                options,
+                registry,
+            )?,
+            remaining: max_uncompressed_size,
+        },
+        data.crc32,
+        verify_crc32,
+        verify_size.then_some(data.uncompressed_size),
+        &data.file_name,
+    ))
 }
 
 #[derive(Debug)]
@@ -444,23 +774,26 @@ impl<R> ZipArchive<R> {
         comment: Box<[u8]>,
         reader: R,
         central_start: u64,
+        registry: Option<Arc<CompressionRegistry>>,
     ) -> ZipResult<Self> {
         let initial_offset = match files.first() {
             Some((_, file)) => file.header_start,
             None => central_start,
         };
-        let shared = Arc::new(zip_archive::Shared {
-            files,
-            offset: initial_offset,
-            dir_start: central_start,
-            config: Config {
+        let shared = Arc::new(zip_archive::Shared::new(
+            files.into_values().collect(),
+            initial_offset,
+            central_start,
+            Config {
                 archive_offset: ArchiveOffset::Known(initial_offset),
+                ..Default::default()
             },
-        });
+        ));
         Ok(Self {
             reader,
             shared,
             comment: comment.into(),
+            registry,
         })
     }
 
@@ -468,7 +801,7 @@ impl<R> ZipArchive<R> {
     /// metadata.
     pub fn decompressed_size(&self) -> Option<u128> {
         let mut total = 0u128;
-        for file in self.shared.files.values() {
+        for file in self.shared.files.iter() {
             if file.using_data_descriptor {
                 return None;
             }
@@ -476,15 +809,93 @@ impl<R> ZipArchive<R> {
         }
         Some(total)
     }
+
+    /// Total uncompressed size of the entries, as recorded in the central directory. Nothing is
+    /// decompressed, so this is cheap enough to check for disk space before extracting.
+    ///
+    /// Entries with a data descriptor count too, since the central directory holds their real
+    /// sizes.
+    pub fn uncompressed_size(&self) -> u64 {
+        self.shared.files.iter().fold(0, |total, file| {
+            total.saturating_add(file.uncompressed_size)
+        })
+    }
+
+    /// Total compressed size of the entries, as recorded in the central directory, like
+    /// [`ZipArchive::uncompressed_size`].
+    pub fn compressed_size(&self) -> u64 {
+        self.shared
+            .files
+            .iter()
+            .fold(0, |total, file| total.saturating_add(file.compressed_size))
+    }
+
+    /// Counts the entries compressed with each method. AES encrypted entries are counted under
+    /// the method used under the encryption.
+    pub fn method_histogram(&self) -> HashMap<CompressionMethod, usize> {
+        let mut histogram = HashMap::new();
+        for file in self.shared.files.iter() {
+            *histogram.entry(file.compression_method).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// The [`ZipArchive::compressed_size`] of the archive divided by its
+    /// [`ZipArchive::uncompressed_size`], so smaller is better. Returns 1 if there's no data.
+    pub fn compression_ratio(&self) -> f64 {
+        match self.uncompressed_size() {
+            0 => 1.0,
+            uncompressed_size => self.compressed_size() as f64 / uncompressed_size as f64,
+        }
+    }
+}
+
+impl<T: AsRef<[u8]>> ZipArchive<io::Cursor<T>> {
+    /// Borrows the contents of a Stored, unencrypted entry directly from the in-memory archive,
+    /// without copying them.
+    ///
+    /// Returns `None` if there's no such entry, if it's compressed or encrypted, or if its data
+    /// lies outside the buffer. The CRC-32 isn't checked.
+    pub fn by_index_stored_slice(&self, index: usize) -> Option<&[u8]> {
+        let data = self.shared.files.get(index)?;
+        if data.encrypted || data.compression_method != CompressionMethod::Stored {
+            return None;
+        }
+        let start = usize::try_from(*data.data_start.get()?).ok()?;
+        let end = start.checked_add(usize::try_from(data.compressed_size).ok()?)?;
+        self.reader.get_ref().as_ref().get(start..end)
+    }
+}
+
+impl<R: Read + Seek> ZipArchive<MultiVolumeReader<R>> {
+    /// Read a ZIP archive split into volumes, collecting the files it contains.
+    ///
+    /// The disk numbers recorded in the central directory are used to locate it and the local
+    /// headers, and entries may span several volumes. A default [`Config`] is used.
+    pub fn new_multi_volume(reader: MultiVolumeReader<R>) -> ZipResult<Self> {
+        Self::multi_volume_with_config(Default::default(), reader)
+    }
+
+    /// Read a ZIP archive split into volumes, providing a read configuration. See
+    /// [`ZipArchive::new_multi_volume`].
+    ///
+    /// [`Config::archive_offset`] is ignored, since offsets are relative to each volume.
+    pub fn multi_volume_with_config(
+        config: Config,
+        reader: MultiVolumeReader<R>,
+    ) -> ZipResult<Self> {
+        let volumes = reader.volume_starts().to_vec();
+        Self::with_config_and_volumes(config, reader, &volumes)
+    }
 }
 
 impl<R: Read + Seek> ZipArchive<R> {
     pub(crate) fn merge_contents<W: Write + io::Seek>(
         &mut self,
         mut w: W,
-    ) -> ZipResult<IndexMap<Box<str>, ZipFileData>> {
+    ) -> ZipResult<Vec<ZipFileData>> {
         if self.shared.files.is_empty() {
-            return Ok(IndexMap::new());
+            return Ok(Vec::new());
         }
         let mut new_files = self.shared.files.clone();
         /* The first file header will probably start at the beginning of the file, but zip doesn't
@@ -496,7 +907,7 @@ impl<R: Read + Seek> ZipArchive<R> {
 
         let new_initial_header_start = w.stream_position()?;
         /* Push back file header starts for all entries in the covered files. */
-        new_files.values_mut().try_for_each(|f| {
+        new_files.iter_mut().try_for_each(|f| {
             /* This is probably the only really important thing to change. */
             f.header_start = f.header_start.checked_add(new_initial_header_start).ok_or(
                 ZipError::InvalidArchive("new header start from merge would have been too large"),
@@ -545,7 +956,68 @@ impl<R: Read + Seek> ZipArchive<R> {
         reader: &mut R,
         footer: &spec::Zip32CentralDirectoryEnd,
         cde_start_pos: u64,
+        volumes: &[u64],
+    ) -> ZipResult<CentralDirectoryInfo> {
+        let result =
+            Self::get_directory_info_zip32_values(config, reader, footer, cde_start_pos, volumes);
+        if !footer.defers_to_zip64() {
+            return result;
+        }
+        // There's no Zip64 locator, so a maximum value may be the real one: older writers, such
+        // as this crate up to 2.1.5, only used Zip64 for more than 65,535 entries. Accept the
+        // record if it points at a central directory that ends before it.
+        match result {
+            Ok(info)
+                if info
+                    .directory_start
+                    .checked_add(footer.central_directory_size as u64)
+                    .is_some_and(|directory_end| directory_end <= cde_start_pos)
+                    && Self::is_central_directory_header(reader, info.directory_start) =>
+            {
+                Ok(info)
+            }
+            _ => Err(InvalidArchive(MISSING_ZIP64_RECORD)),
+        }
+    }
+
+    fn is_central_directory_header(reader: &mut R, position: u64) -> bool {
+        let mut buf = [0; 4];
+        reader.seek(io::SeekFrom::Start(position)).is_ok()
+            && reader.read_exact(&mut buf).is_ok()
+            && spec::Magic::from_le_bytes(buf) == spec::Magic::CENTRAL_DIRECTORY_HEADER_SIGNATURE
+    }
+
+    fn has_zip64_locator(reader: &mut R, cde_start_pos: u64) -> bool {
+        let Some(locator_start) =
+            cde_start_pos.checked_sub(mem::size_of::<spec::Zip64CDELocatorBlock>() as u64)
+        else {
+            return false;
+        };
+        reader.seek(io::SeekFrom::Start(locator_start)).is_ok()
+            && spec::Zip64CentralDirectoryEndLocator::parse(reader).is_ok()
+    }
+
+    fn get_directory_info_zip32_values(
+        config: &Config,
+        reader: &mut R,
+        footer: &spec::Zip32CentralDirectoryEnd,
+        cde_start_pos: u64,
+        volumes: &[u64],
     ) -> ZipResult<CentralDirectoryInfo> {
+        if !volumes.is_empty() {
+            // Offsets are relative to the volume they point into
+            let directory_start = volume_start(volumes, footer.disk_with_central_directory as u32)?
+                + footer.central_directory_offset as u64;
+            return Ok(CentralDirectoryInfo {
+                archive_offset: 0,
+                directory_start,
+                number_of_files: footer.number_of_files as usize,
+                disk_number: footer.disk_number as u32,
+                disk_with_central_directory: footer.disk_with_central_directory as u32,
+                cde_position: cde_start_pos,
+                is_zip64: false,
+            });
+        }
         let archive_offset = match config.archive_offset {
             ArchiveOffset::Known(n) => n,
             ArchiveOffset::FromCentralDirectory | ArchiveOffset::Detect => {
@@ -611,6 +1083,7 @@ impl<R: Read + Seek> ZipArchive<R> {
         reader: &mut R,
         footer: &spec::Zip32CentralDirectoryEnd,
         cde_start_pos: u64,
+        volumes: &[u64],
     ) -> ZipResult<Vec<ZipResult<CentralDirectoryInfo>>> {
         // See if there's a ZIP64 footer. The ZIP64 locator if present will
         // have its signature 20 bytes in front of the standard footer. The
@@ -637,15 +1110,19 @@ impl<R: Read + Seek> ZipArchive<R> {
                 "File cannot contain ZIP64 central directory end",
             ))?;
 
-        let (lower, upper) = Self::order_lower_upper_bounds(
-            locator64.end_of_central_directory_offset,
-            search_upper_bound,
-        );
+        let locator_offset = if volumes.is_empty() {
+            locator64.end_of_central_directory_offset
+        } else {
+            volume_start(volumes, locator64.disk_with_central_directory)?
+                .saturating_add(locator64.end_of_central_directory_offset)
+        };
+        let (lower, upper) = Self::order_lower_upper_bounds(locator_offset, search_upper_bound);
 
         let search_results = spec::Zip64CentralDirectoryEnd::find_and_parse(reader, lower, upper)?;
         let results: Vec<ZipResult<CentralDirectoryInfo>> =
             search_results.into_iter().map(|(footer64, archive_offset)| {
                 let archive_offset = match config.archive_offset {
+                    _ if !volumes.is_empty() => 0,
                     ArchiveOffset::Known(n) => n,
                     ArchiveOffset::FromCentralDirectory => archive_offset,
                     ArchiveOffset::Detect => {
@@ -669,10 +1146,22 @@ impl<R: Read + Seek> ZipArchive<R> {
                 let directory_start = footer64
                     .central_directory_offset
                     .checked_add(archive_offset)
+                    .and_then(|start| {
+                        if volumes.is_empty() {
+                            Some(start)
+                        } else {
+                            volume_start(volumes, footer64.disk_with_central_directory)
+                                .ok()?
+                                .checked_add(start)
+                        }
+                    })
                     .ok_or(ZipError::InvalidArchive(
                         "Invalid central directory size or offset",
                     ))?;
-                if directory_start > search_upper_bound {
+                if directory_start
+                    .checked_add(footer64.central_directory_size)
+                    .map_or(true, |directory_end| directory_end > search_upper_bound)
+                {
                     Err(ZipError::InvalidArchive(
                         "Invalid central directory size or offset",
                     ))
@@ -689,7 +1178,8 @@ impl<R: Read + Seek> ZipArchive<R> {
                     Ok(CentralDirectoryInfo {
                         archive_offset,
                         directory_start,
-                        number_of_files: footer64.number_of_files as usize,
+                        number_of_files: usize::try_from(footer64.number_of_files)
+                            .unwrap_or(usize::MAX),
                         disk_number: footer64.disk_number,
                         disk_with_central_directory: footer64.disk_with_central_directory,
                         cde_position: cde_start_pos,
@@ -702,9 +1192,13 @@ impl<R: Read + Seek> ZipArchive<R> {
 
     /// Get the directory start offset and number of files. This is done in a
     /// separate function to ease the control flow design.
+    ///
+    /// `volumes` holds the start of each volume of a split archive, indexed by disk number, or is
+    /// empty if the archive isn't split.
     pub(crate) fn get_metadata(
         config: Config,
         reader: &mut R,
+        volumes: &[u64],
     ) -> ZipResult<(Zip32CentralDirectoryEnd, Shared)> {
         let mut invalid_errors_32 = Vec::new();
         let mut unsupported_errors_32 = Vec::new();
@@ -712,23 +1206,37 @@ impl<R: Read + Seek> ZipArchive<R> {
         let mut unsupported_errors_64 = Vec::new();
         let mut ok_results = Vec::new();
         let cde_locations = spec::Zip32CentralDirectoryEnd::find_and_parse(reader)?;
+        let file_length = reader.seek(io::SeekFrom::End(0))?;
         cde_locations
             .into_vec()
             .into_iter()
             .for_each(|(footer, cde_start_pos)| {
-                let zip32_result =
-                    Self::get_directory_info_zip32(&config, reader, &footer, cde_start_pos);
-                Self::sort_result(
-                    zip32_result,
-                    &mut invalid_errors_32,
-                    &mut unsupported_errors_32,
-                    &mut ok_results,
-                    &footer,
-                );
+                // The real values are only in the Zip64 record, which is tried separately
+                if !(footer.defers_to_zip64() && Self::has_zip64_locator(reader, cde_start_pos)) {
+                    let zip32_result = Self::get_directory_info_zip32(
+                        &config,
+                        reader,
+                        &footer,
+                        cde_start_pos,
+                        volumes,
+                    );
+                    Self::sort_result(
+                        zip32_result,
+                        &mut invalid_errors_32,
+                        &mut unsupported_errors_32,
+                        &mut ok_results,
+                        &footer,
+                    );
+                }
                 let mut inner_results = Vec::with_capacity(1);
                 // Check if file has a zip64 footer
-                let zip64_vec_result =
-                    Self::get_directory_info_zip64(&config, reader, &footer, cde_start_pos);
+                let zip64_vec_result = Self::get_directory_info_zip64(
+                    &config,
+                    reader,
+                    &footer,
+                    cde_start_pos,
+                    volumes,
+                );
                 Self::sort_result(
                     zip64_vec_result,
                     &mut invalid_errors_64,
@@ -748,10 +1256,22 @@ impl<R: Read + Seek> ZipArchive<R> {
                     });
                 });
             });
-        ok_results.sort_by_key(|(_, result)| {
+        ok_results.sort_by_key(|(footer, result)| {
+            // A record whose comment ends at the end of the file is most likely the real one, and
+            // the earliest of those is, since the others must be inside its comment. Otherwise
+            // there's data after the comment, so try the last record first.
+            let ends_file = result.cde_position
+                + mem::size_of::<spec::Zip32CDEBlock>() as u64
+                + footer.zip_file_comment.len() as u64
+                == file_length;
             (
-                u64::MAX - result.cde_position, // try the last one first
-                !result.is_zip64,               // try ZIP64 first
+                !ends_file,
+                if ends_file {
+                    result.cde_position
+                } else {
+                    u64::MAX - result.cde_position
+                },
+                !result.is_zip64, // try ZIP64 first
             )
         });
         let mut best_result = None;
@@ -759,7 +1279,7 @@ impl<R: Read + Seek> ZipArchive<R> {
             let mut inner_result = Vec::with_capacity(1);
             let is_zip64 = result.is_zip64;
             Self::sort_result(
-                Self::read_central_header(result, config, reader),
+                Self::read_central_header(result, config, reader, volumes),
                 if is_zip64 {
                     &mut invalid_errors_64
                 } else {
@@ -806,21 +1326,37 @@ impl<R: Read + Seek> ZipArchive<R> {
         dir_info: CentralDirectoryInfo,
         config: Config,
         reader: &mut R,
+        volumes: &[u64],
     ) -> Result<SharedBuilder, ZipError> {
         // If the parsed number of files is greater than the offset then
         // something fishy is going on and we shouldn't trust number_of_files.
-        let file_capacity = if dir_info.number_of_files > dir_info.directory_start as usize {
+        let file_capacity = if dir_info.number_of_files as u64 > dir_info.directory_start {
             0
         } else {
             dir_info.number_of_files
         };
-        if dir_info.disk_number != dir_info.disk_with_central_directory {
+        if volumes.is_empty() && dir_info.disk_number != dir_info.disk_with_central_directory {
             return unsupported_zip_error("Support for multi-disk files is not implemented");
         }
+        let limits = config.limits;
+        if dir_info.number_of_files > limits.max_entries {
+            return Err(ZipError::LimitExceeded("Too many entries"));
+        }
         let mut files = Vec::with_capacity(file_capacity);
         reader.seek(io::SeekFrom::Start(dir_info.directory_start))?;
         for _ in 0..dir_info.number_of_files {
-            let file = central_header_to_zip_file(reader, dir_info.archive_offset)?;
+            let file = central_header_to_zip_file(
+                reader,
+                dir_info.archive_offset,
+                config.name_encoding,
+                volumes,
+            )?;
+            if file.file_name_raw.len() > limits.max_name_len {
+                return Err(ZipError::LimitExceeded("File name too long"));
+            }
+            if file.extra_field.as_ref().map_or(0, |extra| extra.len()) > limits.max_extra_len {
+                return Err(ZipError::LimitExceeded("Extra field too long"));
+            }
             files.push(file);
         }
         Ok(SharedBuilder {
@@ -839,9 +1375,12 @@ impl<R: Read + Seek> ZipArchive<R> {
         footer: &U,
     ) {
         match result {
-            Err(ZipError::UnsupportedArchive(e)) => {
-                unsupported_errors.push(ZipError::UnsupportedArchive(e))
-            }
+            // Reported in preference to invalid archive errors, like unsupported ones
+            Err(
+                e @ (ZipError::UnsupportedArchive(_)
+                | ZipError::LimitExceeded(_)
+                | ZipError::StrongEncryptionUnsupported(_)),
+            ) => unsupported_errors.push(e),
             Err(e) => invalid_errors.push(e),
             Ok(o) => ok_results.push((footer.clone(), o)),
         }
@@ -859,10 +1398,10 @@ impl<R: Read + Seek> ZipArchive<R> {
         &mut self,
         file_number: usize,
     ) -> ZipResult<Option<AesInfo>> {
-        let (_, data) = self
+        let data = self
             .shared
             .files
-            .get_index(file_number)
+            .get(file_number)
             .ok_or(ZipError::FileNotFound)?;
 
         let limit_reader = find_content(data, &mut self.reader)?;
@@ -886,6 +1425,11 @@ impl<R: Read + Seek> ZipArchive<R> {
     ///
     /// This uses the central directory record of the ZIP file, and ignores local file headers.
     ///
+    /// Data prepended to the archive, such as the executable stub of a self-extracting archive,
+    /// is detected by comparing where the central directory is with the offset recorded for it.
+    /// The difference, returned by [`ZipArchive::offset`], is then added to every offset in the
+    /// archive. [`Config::archive_offset`] can override this.
+    ///
     /// A default [`Config`] is used.
     pub fn new(reader: R) -> ZipResult<ZipArchive<R>> {
         Self::with_config(Default::default(), reader)
@@ -894,20 +1438,85 @@ impl<R: Read + Seek> ZipArchive<R> {
     /// Read a ZIP archive providing a read configuration, collecting the files it contains.
     ///
     /// This uses the central directory record of the ZIP file, and ignores local file headers.
-    pub fn with_config(config: Config, mut reader: R) -> ZipResult<ZipArchive<R>> {
+    pub fn with_config(config: Config, reader: R) -> ZipResult<ZipArchive<R>> {
+        Self::with_config_and_volumes(config, reader, &[])
+    }
+
+    fn with_config_and_volumes(
+        config: Config,
+        mut reader: R,
+        volumes: &[u64],
+    ) -> ZipResult<ZipArchive<R>> {
         reader.seek(SeekFrom::Start(0))?;
-        if let Ok((footer, shared)) = Self::get_metadata(config, &mut reader) {
-            return Ok(ZipArchive {
+        match Self::get_metadata(config, &mut reader, volumes) {
+            Ok((footer, shared)) => Ok(ZipArchive {
                 reader,
                 shared: shared.into(),
                 comment: footer.zip_file_comment.into(),
-            });
+                registry: None,
+            }),
+            Err(
+                e @ (ZipError::LimitExceeded(_)
+                | ZipError::StrongEncryptionUnsupported(_)
+                | ZipError::InvalidArchive(MISSING_ZIP64_RECORD)),
+            ) => Err(e),
+            Err(_) => Err(InvalidArchive("No valid central directory found")),
         }
-        Err(InvalidArchive("No valid central directory found"))
+    }
+
+    /// Read a ZIP archive, decoding the names and comments of entries without the UTF-8 flag
+    /// with the given encoding.
+    ///
+    /// This is useful for archives created with a legacy code page, such as Shift-JIS or GBK.
+    /// A default [`Config`] is used otherwise.
+    pub fn new_with_encoding(reader: R, name_encoding: NameEncoding) -> ZipResult<ZipArchive<R>> {
+        Self::with_config(
+            Config {
+                name_encoding,
+                ..Default::default()
+            },
+            reader,
+        )
+    }
+
+    /// Read a ZIP archive, rejecting it with [`ZipError::LimitExceeded`] if its central directory
+    /// exceeds any of the given limits.
+    ///
+    /// This is meant for archives from untrusted sources. A default [`Config`] is used otherwise.
+    pub fn with_limits(reader: R, limits: ArchiveLimits) -> ZipResult<ZipArchive<R>> {
+        Self::with_config(
+            Config {
+                limits,
+                ..Default::default()
+            },
+            reader,
+        )
+    }
+
+    /// Read a ZIP archive, checking the local header of each entry against its central directory
+    /// record whenever the entry is opened.
+    ///
+    /// This is meant for archives from untrusted sources, which may give an entry different
+    /// names or sizes in the two headers to confuse extractors that read different ones. See
+    /// [`Config::verify_local_headers`]. A default [`Config`] is used otherwise.
+    pub fn new_strict(reader: R) -> ZipResult<ZipArchive<R>> {
+        Self::with_config(
+            Config {
+                verify_local_headers: true,
+                ..Default::default()
+            },
+            reader,
+        )
     }
 
     /// Extract a Zip archive into a directory, overwriting files if they
-    /// already exist. Paths are sanitized with [`ZipFile::enclosed_name`].
+    /// already exist. Paths are sanitized with [`ZipFile::enclosed_name`], after treating
+    /// backslashes as separators and removing drive letters, on every platform. Entries with UNC
+    /// or device paths such as `\\server\share\file` or `\\?\C:\file` are rejected.
+    ///
+    /// Entries whose names contain control characters or are longer than the platform's path
+    /// limit fail with [`ZipError::InvalidEntryName`], unless [`Config::sanitize_entry_names`]
+    /// is set.
     ///
     /// Extraction is not atomic. If an error is encountered, some of the files
     /// may be left on disk. However, on Unix targets, no newly-created directories with part but
@@ -916,39 +1525,243 @@ impl<R: Read + Seek> ZipArchive<R> {
     ///
     /// On Unix and Windows, symbolic links are extracted correctly. On other platforms such as
     /// WebAssembly, symbolic links aren't supported, so they're extracted as normal files
-    /// containing the target path in UTF-8.
+    /// containing the target path in UTF-8. The same happens on Windows if the process isn't
+    /// allowed to create symbolic links. A link whose target is absolute or leads out of
+    /// `directory` is rejected with [`ZipError::InvalidArchive`], and so is an entry that would
+    /// be written through a link extracted earlier. Since any component of a target may be
+    /// another link, `..` is only accepted at the start of a target, such as in `../../file`,
+    /// and not after another component, as in `dir/../file`.
+    ///
+    /// On Unix, the permissions stored for files and directories are applied once everything has
+    /// been written, unless [`Config::extract_unix_permissions`] is turned off.
+    ///
+    /// If [`Config::decompression_limits`] are set, extraction stops with
+    /// [`ZipError::DecompressionLimitExceeded`] as soon as one of them is exceeded.
     pub fn extract<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<()> {
+        self.extract_internal(directory.as_ref(), |_| true, |_, _, _| {})
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], skipping the
+    /// entries that [`ExtractOptions`] leaves out.
+    pub fn extract_with_options<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        options: ExtractOptions,
+    ) -> ZipResult<()> {
+        self.extract_internal(
+            directory.as_ref(),
+            |name| !(options.skip_macos_metadata && is_macos_metadata(name)),
+            |_, _, _| {},
+        )
+    }
+
+    /// Extract the entries whose names match the shell-style glob `pattern` into a directory,
+    /// like [`ZipArchive::extract`].
+    ///
+    /// `?` and `*` match any character or any sequence of characters within a path segment,
+    /// while `**` also matches across `/`, so `*.txt` only matches text files at the root of the
+    /// archive and `src/**` matches everything under `src/`. Character classes such as `[a-z]`
+    /// and `[!0-9]` are supported too.
+    ///
+    /// Entries that don't match are skipped without being decompressed. The paths of those that
+    /// do are sanitized with [`ZipFile::enclosed_name`].
+    pub fn extract_matching<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        pattern: &str,
+    ) -> ZipResult<()> {
+        self.extract_internal(
+            directory.as_ref(),
+            |name| glob::glob_matches(pattern, name),
+            |_, _, _| {},
+        )
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], reporting progress
+    /// through `progress`.
+    ///
+    /// The callback receives the entry being extracted, the number of bytes written for it so far
+    /// and its total uncompressed size. It's called once before each entry's contents are
+    /// written, then again after every chunk, so it can be used to draw a progress bar. It's
+    /// never called for directories. The total is the size recorded in the central directory,
+    /// or 0 if that is unknown.
+    pub fn extract_with_progress<P, F>(&mut self, directory: P, progress: F) -> ZipResult<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&ZipFile<'_>, u64, u64),
+    {
+        self.extract_internal(directory.as_ref(), |_| true, progress)
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], decompressing
+    /// entries on `threads` threads at once.
+    ///
+    /// Each thread reads through its own clone of the archive, so the reader must be cheap to
+    /// clone and each clone must have its own position, as with a [`std::io::Cursor`] over
+    /// shared data. To extract a file on disk, open it once for each thread and use
+    /// [`ZipArchive::extract_parallel_with`] instead.
+    ///
+    /// Unix permissions are applied once all the threads are done, so directories stay
+    /// writable until then.
+    pub fn extract_parallel<P: AsRef<Path>>(&self, directory: P, threads: usize) -> ZipResult<()>
+    where
+        R: Clone + Send,
+    {
+        self.extract_parallel_with(directory, threads, || Ok(self.reader.clone()))
+    }
+
+    /// Like [`ZipArchive::extract_parallel`], but calls `open` once for each thread to get
+    /// another reader over the same archive, such as by opening its file again.
+    pub fn extract_parallel_with<P, O>(
+        &self,
+        directory: P,
+        threads: usize,
+        mut open: O,
+    ) -> ZipResult<()>
+    where
+        P: AsRef<Path>,
+        R: Send,
+        O: FnMut() -> io::Result<R>,
+    {
+        let directory = directory.as_ref();
+        let next_index = &AtomicUsize::new(0);
+        let total_written = &Mutex::new(0);
+        let mut archives = Vec::with_capacity(threads.max(1));
+        for _ in 0..threads.max(1) {
+            archives.push(ZipArchive {
+                reader: open()?,
+                shared: self.shared.clone(),
+                comment: self.comment.clone(),
+                registry: self.registry.clone(),
+            });
+        }
+        let results: Vec<ZipResult<Vec<(PathBuf, u32)>>> = std::thread::scope(|scope| {
+            // Directories are created as needed by each thread; `create_dir_all` tolerates another
+            // thread creating the same directory at the same time.
+            let workers: Vec<_> = archives
+                .into_iter()
+                .map(|mut archive| {
+                    scope.spawn(move || {
+                        archive.extract_entries(
+                            directory,
+                            |archive| {
+                                let i = next_index.fetch_add(1, Ordering::Relaxed);
+                                (i < archive.len()).then_some(i)
+                            },
+                            total_written,
+                            |_, _, _| {},
+                        )
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        let mut files_by_unix_mode = Vec::new();
+        for result in results {
+            files_by_unix_mode.extend(result?);
+        }
+        Self::set_extracted_permissions(files_by_unix_mode)
+    }
+
+    fn extract_internal<M, F>(
+        &mut self,
+        directory: &Path,
+        mut matches: M,
+        progress: F,
+    ) -> ZipResult<()>
+    where
+        M: FnMut(&str) -> bool,
+        F: FnMut(&ZipFile<'_>, u64, u64),
+    {
+        let mut indices = 0..self.len();
+        let total_written = Mutex::new(0);
+        let files_by_unix_mode = self.extract_entries(
+            directory,
+            |archive| indices.find(|&i| matches(archive.name_for_index(i).unwrap_or_default())),
+            &total_written,
+            progress,
+        )?;
+        Self::set_extracted_permissions(files_by_unix_mode)
+    }
+
+    /// Extracts the entries returned by `next_index` until it returns `None`, and returns the
+    /// Unix permissions to apply once all the entries of the archive have been extracted.
+    ///
+    /// `total_written` counts the bytes extracted towards
+    /// [`DecompressionLimits::max_total_bytes`], and may be shared with other threads.
+    fn extract_entries<N, F>(
+        &mut self,
+        directory: &Path,
+        mut next_index: N,
+        total_written: &Mutex<u64>,
+        mut progress: F,
+    ) -> ZipResult<Vec<(PathBuf, u32)>>
+    where
+        N: FnMut(&Self) -> Option<usize>,
+        F: FnMut(&ZipFile<'_>, u64, u64),
+    {
         use std::fs;
-        #[cfg(unix)]
+        #[allow(unused_mut)]
         let mut files_by_unix_mode = Vec::new();
-        for i in 0..self.len() {
+        #[cfg(unix)]
+        let extract_unix_permissions = self.shared.config.extract_unix_permissions;
+        let max_total_bytes = self.shared.config.decompression_limits.max_total_bytes;
+        let sanitize_entry_names = self.shared.config.sanitize_entry_names;
+        let count_written = |len: usize| {
+            let mut total = total_written.lock().unwrap_or_else(|e| e.into_inner());
+            *total += len as u64;
+            match max_total_bytes {
+                Some(max) if *total > max => Err(ZipError::DecompressionLimitExceeded),
+                _ => Ok(()),
+            }
+        };
+        while let Some(i) = next_index(self) {
             let mut file = self.by_index(i)?;
-            let filepath = file
-                .enclosed_name()
-                .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
+            let filepath = file.get_metadata().extraction_path(sanitize_entry_names)?;
 
-            let outpath = directory.as_ref().join(filepath);
+            let outpath = directory.join(&filepath);
 
             if file.is_dir() {
-                Self::make_writable_dir_all(&outpath)?;
+                Self::make_writable_dir_within(directory, &filepath)?;
+                #[cfg(unix)]
+                if let Some(mode) = file.unix_mode().filter(|_| extract_unix_permissions) {
+                    files_by_unix_mode.push((outpath, mode));
+                }
                 continue;
             }
             let symlink_target = if file.is_symlink() && (cfg!(unix) || cfg!(windows)) {
-                let mut target = Vec::with_capacity(file.size() as usize);
-                file.read_to_end(&mut target)?;
+                let mut target = Vec::new();
+                file.read_to_end(&mut target)
+                    .map_err(ZipError::from_read_error)?;
+                count_written(target.len())?;
+                progress(&file, target.len() as u64, file.size());
                 Some(target)
             } else {
                 None
             };
             drop(file);
-            if let Some(p) = outpath.parent() {
-                Self::make_writable_dir_all(p)?;
+            Self::make_writable_dir_within(directory, filepath.parent().unwrap_or(Path::new("")))?;
+            if fs::symlink_metadata(&outpath)
+                .is_ok_and(|metadata| metadata.file_type().is_symlink())
+            {
+                return Err(InvalidArchive(PATH_THROUGH_SYMLINK));
             }
             if let Some(target) = symlink_target {
                 #[cfg(unix)]
                 {
+                    use std::ffi::OsString;
                     use std::os::unix::ffi::OsStringExt;
                     let target = OsString::from_vec(target);
+                    if !symlink_is_enclosed(&filepath, target.as_ref()) {
+                        return Err(InvalidArchive(SYMLINK_ESCAPES));
+                    }
                     std::os::unix::fs::symlink(&target, outpath.as_path())?;
                 }
                 #[cfg(windows)]
@@ -956,10 +1769,27 @@ impl<R: Read + Seek> ZipArchive<R> {
                     let Ok(target) = String::from_utf8(target) else {
                         return Err(ZipError::InvalidArchive("Invalid UTF-8 as symlink target"));
                     };
-                    let target = target.into_boxed_str();
-                    let target_is_dir_from_archive =
-                        self.shared.files.contains_key(&target) && is_dir(&target);
-                    let target_path = directory.as_ref().join(OsString::from(target.to_string()));
+                    if !symlink_is_enclosed(&filepath, target.as_ref()) {
+                        return Err(InvalidArchive(SYMLINK_ESCAPES));
+                    }
+                    // Like on Unix, the target is relative to the link's directory
+                    let mut target_name = PathBuf::new();
+                    let link_dir = filepath.parent().unwrap_or(Path::new(""));
+                    for component in link_dir.components().chain(Path::new(&target).components()) {
+                        match component {
+                            std::path::Component::ParentDir => {
+                                target_name.pop();
+                            }
+                            std::path::Component::Normal(name) => target_name.push(name),
+                            _ => {}
+                        }
+                    }
+                    let target_name = target_name.to_string_lossy().replace('\\', "/");
+                    let target_is_dir_from_archive = self
+                        .shared
+                        .names
+                        .contains_key(format!("{target_name}/").as_str());
+                    let target_path = directory.join(&target_name);
                     let target_is_dir = if target_is_dir_from_archive {
                         true
                     } else if let Ok(meta) = std::fs::metadata(&target_path) {
@@ -967,25 +1797,50 @@ impl<R: Read + Seek> ZipArchive<R> {
                     } else {
                         false
                     };
-                    if target_is_dir {
-                        std::os::windows::fs::symlink_dir(target_path, outpath.as_path())?;
+                    let result = if target_is_dir {
+                        std::os::windows::fs::symlink_dir(target_path, outpath.as_path())
                     } else {
-                        std::os::windows::fs::symlink_file(target_path, outpath.as_path())?;
+                        std::os::windows::fs::symlink_file(target_path, outpath.as_path())
+                    };
+                    if result.is_err() {
+                        // Creating symlinks requires extra privileges on Windows, so fall back to
+                        // a regular file containing the target
+                        fs::write(&outpath, target.as_bytes())?;
                     }
                 }
                 continue;
             }
             let mut file = self.by_index(i)?;
             let mut outfile = fs::File::create(&outpath)?;
-            io::copy(&mut file, &mut outfile)?;
+            let total = file.size();
+            let mut written = 0;
+            let mut buf = [0u8; 8 * 1024];
+            progress(&file, written, total);
+            loop {
+                let len = match file.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(len) => len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(ZipError::from_read_error(e)),
+                };
+                count_written(len)?;
+                outfile.write_all(&buf[..len])?;
+                written += len as u64;
+                progress(&file, written, total);
+            }
             #[cfg(unix)]
             {
                 // Check for real permissions, which we'll set in a second pass
-                if let Some(mode) = file.unix_mode() {
+                if let Some(mode) = file.unix_mode().filter(|_| extract_unix_permissions) {
                     files_by_unix_mode.push((outpath.clone(), mode));
                 }
             }
         }
+        Ok(files_by_unix_mode)
+    }
+
+    #[allow(unused_mut, unused_variables)]
+    fn set_extracted_permissions(mut files_by_unix_mode: Vec<(PathBuf, u32)>) -> ZipResult<()> {
         #[cfg(unix)]
         {
             use std::cmp::Reverse;
@@ -996,15 +1851,43 @@ impl<R: Read + Seek> ZipArchive<R> {
                 files_by_unix_mode.sort_by_key(|(path, _)| Reverse(path.clone()));
             }
             for (path, mode) in files_by_unix_mode.into_iter() {
-                fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
             }
         }
         Ok(())
     }
 
-    fn make_writable_dir_all<T: AsRef<Path>>(outpath: T) -> Result<(), ZipError> {
-        create_dir_all(outpath.as_ref())?;
-        #[cfg(unix)]
+    /// Creates `relative` inside `directory` like [`Self::make_writable_dir_all`], but fails
+    /// instead of following a symlink along the way. An earlier entry may have created it, and
+    /// symlinks to other symlinks can lead out of `directory` even though each target looks
+    /// enclosed on its own.
+    fn make_writable_dir_within(directory: &Path, relative: &Path) -> Result<(), ZipError> {
+        use std::fs;
+
+        create_dir_all(directory)?;
+        let mut path = directory.to_path_buf();
+        for component in relative.components() {
+            path.push(component);
+            let metadata = match fs::symlink_metadata(&path) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    // Another thread may create it first when extracting in parallel
+                    match fs::create_dir(&path) {
+                        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e.into()),
+                        _ => fs::symlink_metadata(&path)?,
+                    }
+                }
+                result => result?,
+            };
+            if metadata.file_type().is_symlink() {
+                return Err(InvalidArchive(PATH_THROUGH_SYMLINK));
+            }
+        }
+        Self::make_writable_dir_all(path)
+    }
+
+    fn make_writable_dir_all<T: AsRef<Path>>(outpath: T) -> Result<(), ZipError> {
+        create_dir_all(outpath.as_ref())?;
+        #[cfg(unix)]
         {
             // Dirs must be writable until all normal files are extracted
             use std::os::unix::fs::PermissionsExt;
@@ -1019,10 +1902,20 @@ impl<R: Read + Seek> ZipArchive<R> {
     }
 
     /// Number of files contained in this zip.
+    ///
+    /// This counts every record read from the central directory, so it isn't capped at 65535
+    /// like the count in the classic end of central directory record: the count in the Zip64
+    /// record is used when there is one.
     pub fn len(&self) -> usize {
         self.shared.files.len()
     }
 
+    /// Number of files contained in this zip, as a `u64` like the count in the Zip64 end of
+    /// central directory record. See [`ZipArchive::len`].
+    pub fn total_entries(&self) -> u64 {
+        self.shared.files.len() as u64
+    }
+
     /// Whether this zip archive contains no files
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -1036,14 +1929,67 @@ impl<R: Read + Seek> ZipArchive<R> {
         self.shared.offset
     }
 
+    /// Get the number of bytes before the first local header, or before the central directory
+    /// if the archive is empty, such as the executable stub of a self-extracting archive.
+    ///
+    /// This is usually the same as [`ZipArchive::offset`], but also covers stubs that the
+    /// archive's offsets already account for, as produced by `zip -A`.
+    pub fn prefix_len(&self) -> u64 {
+        self.shared
+            .files
+            .iter()
+            .map(|file| file.header_start)
+            .min()
+            .unwrap_or(self.shared.dir_start)
+    }
+
     /// Get the comment of the zip archive.
     pub fn comment(&self) -> &[u8] {
         &self.comment
     }
 
+    /// Get the comment of the zip archive as a string.
+    ///
+    /// The archive comment has no encoding flag of its own, so it's decoded as UTF-8 if it's
+    /// valid UTF-8, and as CP437 otherwise.
+    pub fn comment_str(&self) -> Cow<'_, str> {
+        match std::str::from_utf8(&self.comment) {
+            Ok(comment) => Cow::Borrowed(comment),
+            Err(_) => self.comment.as_ref().from_cp437(),
+        }
+    }
+
     /// Returns an iterator over all the file and directory names in this archive.
+    ///
+    /// The names are returned in the order their entries appear in the central directory, which
+    /// is also the order used by [`ZipArchive::by_index`].
     pub fn file_names(&self) -> impl Iterator<Item = &str> {
-        self.shared.files.keys().map(|s| s.as_ref())
+        self.shared.files.iter().map(|file| &*file.file_name)
+    }
+
+    /// Returns an iterator over all the file and directory names in this archive, in lexical
+    /// order.
+    ///
+    /// Names are compared byte by byte, so the order doesn't depend on the locale. The sorted
+    /// order is computed the first time this is called and then reused.
+    pub fn file_names_sorted(&self) -> impl Iterator<Item = &str> {
+        let files = &self.shared.files;
+        let sorted_indices = self.shared.sorted_indices.get_or_init(|| {
+            let mut indices: Box<[usize]> = (0..files.len()).collect();
+            indices.sort_unstable_by_key(|&i| files[i].file_name.as_bytes());
+            indices
+        });
+        sorted_indices.iter().map(|&i| &*files[i].file_name)
+    }
+
+    /// Returns an iterator over the metadata of all the entries in this archive, in the same
+    /// order as [`ZipArchive::file_names`].
+    ///
+    /// The metadata comes from the central directory that was parsed when the archive was
+    /// opened, so this doesn't touch the underlying reader. Use it to list the contents or
+    /// decide what to extract before opening any entry.
+    pub fn entries(&self) -> impl Iterator<Item = &ZipFileData> {
+        self.shared.files.iter()
     }
 
     /// Search for a file entry by name, decrypt with given password
@@ -1059,19 +2005,44 @@ impl<R: Read + Seek> ZipArchive<R> {
     /// There are many passwords out there that will also pass the validity checks
     /// we are able to perform. This is a weakness of the ZipCrypto algorithm,
     /// due to its fairly primitive approach to cryptography.
-    pub fn by_name_decrypt(&mut self, name: &str, password: &[u8]) -> ZipResult<ZipFile> {
+    pub fn by_name_decrypt(&mut self, name: &str, password: &[u8]) -> ZipResult<ZipFile<'_>> {
         self.by_name_with_optional_password(name, Some(password))
     }
 
     /// Search for a file entry by name
-    pub fn by_name(&mut self, name: &str) -> ZipResult<ZipFile> {
+    ///
+    /// If several entries have this name, the last one in the central directory is returned, as
+    /// it's the one that overwrites the others when the archive is extracted. Use
+    /// [`ZipArchive::all_indices_for_name`] to find the others.
+    pub fn by_name(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
         self.by_name_with_optional_password(name, None)
     }
 
+    /// Search for a file entry by name, decompressing it with the given [`ReadOptions`], like
+    /// [`ZipArchive::by_index_with_options`].
+    pub fn by_name_with_options(
+        &mut self,
+        name: &str,
+        options: ReadOptions,
+    ) -> ZipResult<ZipFile<'_>> {
+        let index = self.index_for_name(name).ok_or(ZipError::FileNotFound)?;
+        self.by_index_with_options(index, options)
+    }
+
     /// Get the index of a file entry by name, if it's present.
+    ///
+    /// If several entries have this name, the index of the last one is returned.
     #[inline(always)]
     pub fn index_for_name(&self, name: &str) -> Option<usize> {
-        self.shared.files.get_index_of(name)
+        self.shared.index_for_name(name)
+    }
+
+    /// Get the indices of every file entry with the given name, in central directory order.
+    ///
+    /// A zip file may contain several entries with the same name, e.g. to shadow an earlier
+    /// version of a file, and [`ZipArchive::by_name`] only returns the last one.
+    pub fn all_indices_for_name(&self, name: &str) -> Vec<usize> {
+        self.shared.names.get(name).cloned().unwrap_or_default()
     }
 
     /// Get the index of a file entry by path, if it's present.
@@ -1083,29 +2054,50 @@ impl<R: Read + Seek> ZipArchive<R> {
     /// Get the name of a file entry, if it's present.
     #[inline(always)]
     pub fn name_for_index(&self, index: usize) -> Option<&str> {
-        self.shared
-            .files
-            .get_index(index)
-            .map(|(name, _)| name.as_ref())
+        self.shared.files.get(index).map(|file| &*file.file_name)
     }
 
     /// Search for a file entry by name and return a seekable object.
-    pub fn by_name_seek(&mut self, name: &str) -> ZipResult<ZipFileSeek<R>> {
+    pub fn by_name_seek(&mut self, name: &str) -> ZipResult<ZipFileSeek<'_, R>> {
         self.by_index_seek(self.index_for_name(name).ok_or(ZipError::FileNotFound)?)
     }
 
     /// Search for a file entry by index and return a seekable object.
-    pub fn by_index_seek(&mut self, index: usize) -> ZipResult<ZipFileSeek<R>> {
+    ///
+    /// Stored entries are read directly from the archive, so seeking is cheap. For Deflate
+    /// entries, seeking forwards decompresses and discards the data in between, and seeking
+    /// backwards decompresses again from the start of the entry. Other compression methods and
+    /// encrypted entries aren't supported.
+    pub fn by_index_seek(&mut self, index: usize) -> ZipResult<ZipFileSeek<'_, R>> {
         let reader = &mut self.reader;
+        let verify_local_header = self.shared.config.verify_local_headers;
         self.shared
             .files
-            .get_index(index)
+            .get(index)
             .ok_or(ZipError::FileNotFound)
-            .and_then(move |(_, data)| {
+            .and_then(move |data| {
+                if verify_local_header {
+                    check_local_header(data, reader)?;
+                }
+                if data.encrypted {
+                    return Err(ZipError::UnsupportedArchive(
+                        "Seekable encrypted files are not supported",
+                    ));
+                }
                 let seek_reader = match data.compression_method {
                     CompressionMethod::Stored => {
                         ZipFileSeekReader::Raw(find_content_seek(data, reader)?)
                     }
+                    #[cfg(feature = "_deflate-any")]
+                    CompressionMethod::Deflated => {
+                        ZipFileSeekReader::Deflated(DeflatedSeekReader {
+                            decoder: flate2::bufread::DeflateDecoder::new(io::BufReader::new(
+                                find_content_seek(data, reader)?,
+                            )),
+                            uncompressed_size: data.uncompressed_size,
+                            position: 0,
+                        })
+                    }
                     _ => {
                         return Err(ZipError::UnsupportedArchive(
                             "Seekable compressed files are not yet supported",
@@ -1124,10 +2116,10 @@ impl<R: Read + Seek> ZipArchive<R> {
         name: &str,
         password: Option<&[u8]>,
     ) -> ZipResult<ZipFile<'a>> {
-        let Some(index) = self.shared.files.get_index_of(name) else {
+        let Some(index) = self.shared.index_for_name(name) else {
             return Err(ZipError::FileNotFound);
         };
-        self.by_index_with_optional_password(index, password)
+        self.by_index_with_optional_password(index, password, true, ReadOptions::default())
     }
 
     /// Get a contained file by index, decrypt with given password
@@ -1148,22 +2140,139 @@ impl<R: Read + Seek> ZipArchive<R> {
         file_number: usize,
         password: &[u8],
     ) -> ZipResult<ZipFile<'_>> {
-        self.by_index_with_optional_password(file_number, Some(password))
+        self.by_index_with_optional_password(
+            file_number,
+            Some(password),
+            true,
+            ReadOptions::default(),
+        )
     }
 
     /// Get a contained file by index
     pub fn by_index(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
-        self.by_index_with_optional_password(file_number, None)
+        self.by_index_with_optional_password(file_number, None, true, ReadOptions::default())
+    }
+
+    /// Get a contained file by index, decompressing it with the given [`ReadOptions`], such as
+    /// a smaller buffer for memory-constrained environments.
+    pub fn by_index_with_options(
+        &mut self,
+        file_number: usize,
+        options: ReadOptions,
+    ) -> ZipResult<ZipFile<'_>> {
+        self.by_index_with_optional_password(file_number, None, true, options)
+    }
+
+    /// Get a contained file by the offset of its central directory header or of its local header,
+    /// as listed by [`ZipArchive::entry_layout`], for indexes that store offsets instead of
+    /// names.
+    ///
+    /// Fails with [`ZipError::FileNotFound`] if no file's headers start at `offset`.
+    pub fn by_central_offset(&mut self, offset: u64) -> ZipResult<ZipFile<'_>> {
+        let files = &self.shared.files;
+        // The central directory is read in order, so its headers are sorted, but local headers
+        // may be anywhere
+        let index = files
+            .binary_search_by_key(&offset, |file| file.central_header_start)
+            .ok()
+            .or_else(|| files.iter().position(|file| file.header_start == offset))
+            .ok_or(ZipError::FileNotFound)?;
+        self.by_index(index)
+    }
+
+    /// Get a contained file by index, without verifying its CRC-32 once it's been read.
+    ///
+    /// This saves the cost of hashing the data when the archive comes from a trusted source.
+    /// Corrupt data won't be detected, unless the compression format has its own checks.
+    pub fn by_index_no_crc_check(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
+        self.by_index_with_optional_password(file_number, None, false, ReadOptions::default())
+    }
+
+    /// Search for a file entry by name without decompressing it
+    ///
+    /// The returned [`ZipFile`] reads the stored bytes as they are, without decryption,
+    /// decompression or CRC checking, and can be passed to [`crate::ZipWriter::raw_copy_file`].
+    pub fn by_name_raw(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
+        self.by_index_raw(self.index_for_name(name).ok_or(ZipError::FileNotFound)?)
+    }
+
+    /// Get the absolute byte range of a file's data within the archive, for callers who read it
+    /// themselves, e.g. through a memory map.
+    ///
+    /// The range starts after the local header, whose extra field length is read from the
+    /// archive, since it often differs from the central directory's. The data is as stored: the
+    /// [`ZipFile::compression`], [`ZipFile::encrypted`] and [`ZipFile::crc32`] of
+    /// [`ZipArchive::by_name_raw`] tell how to decode and check it.
+    pub fn entry_data_range(&mut self, name: &str) -> ZipResult<Range<u64>> {
+        let index = self.index_for_name(name).ok_or(ZipError::FileNotFound)?;
+        let data = &self.shared.files[index];
+        let data_start = match data.data_start.get() {
+            Some(data_start) => *data_start,
+            None => find_data_start(data, &mut self.reader)?,
+        };
+        let data_end = data_start
+            .checked_add(data.compressed_size)
+            .ok_or(InvalidArchive("File data is too large"))?;
+        Ok(data_start..data_end)
+    }
+
+    /// Lists where the records of each file are in the archive, in central directory order, for
+    /// tools that inspect its structure.
+    ///
+    /// The local header of every file is read, to find where its data starts and get its flags.
+    pub fn entry_layout(&mut self) -> ZipResult<Vec<EntryLayout>> {
+        let reader = &mut self.reader;
+        self.shared
+            .files
+            .iter()
+            .map(|data| {
+                let (block, data_start) = read_local_block(data, reader)?;
+                Ok(EntryLayout {
+                    name: data.file_name.clone(),
+                    header_start: data.header_start,
+                    data_start,
+                    compressed_size: data.compressed_size,
+                    central_header_start: data.central_header_start,
+                    compression_method: data.compression_method,
+                    flags: block.flags,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads every file in the archive to check it, like `unzip -t`, and returns the outcome for
+    /// each one in central directory order.
+    ///
+    /// The data is decompressed and discarded as it's read, so that the CRC-32 and any checks of
+    /// the compression format, such as those of XZ, are verified. A failure is recorded and the
+    /// next file is checked. Encrypted files fail, since no password is given.
+    pub fn verify(&mut self) -> ZipResult<Vec<VerifyResult>> {
+        let mut results = Vec::with_capacity(self.len());
+        for index in 0..self.len() {
+            let name = self.shared.files[index].file_name.clone();
+            let result = self.by_index(index).and_then(|mut file| {
+                io::copy(&mut file, &mut io::sink())?;
+                Ok(())
+            });
+            results.push(VerifyResult { name, result });
+        }
+        Ok(results)
     }
 
     /// Get a contained file by index without decompressing it
+    ///
+    /// The returned [`ZipFile`] reads the stored bytes as they are, without decryption,
+    /// decompression or CRC checking, and can be passed to [`crate::ZipWriter::raw_copy_file`].
     pub fn by_index_raw(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
         let reader = &mut self.reader;
-        let (_, data) = self
+        let data = self
             .shared
             .files
-            .get_index(file_number)
+            .get(file_number)
             .ok_or(ZipError::FileNotFound)?;
+        if self.shared.config.verify_local_headers {
+            check_local_header(data, reader)?;
+        }
         Ok(ZipFile {
             reader: ZipFileReader::Raw(find_content(data, reader)?),
             data: Cow::Borrowed(data),
@@ -1174,11 +2283,13 @@ impl<R: Read + Seek> ZipArchive<R> {
         &mut self,
         file_number: usize,
         mut password: Option<&[u8]>,
+        verify_crc32: bool,
+        options: ReadOptions,
     ) -> ZipResult<ZipFile<'_>> {
-        let (_, data) = self
+        let data = self
             .shared
             .files
-            .get_index(file_number)
+            .get(file_number)
             .ok_or(ZipError::FileNotFound)?;
 
         match (password, data.encrypted) {
@@ -1186,22 +2297,104 @@ impl<R: Read + Seek> ZipArchive<R> {
             (Some(_), false) => password = None, //Password supplied, but none needed! Discard.
             _ => {}
         }
+        if self.shared.config.verify_local_headers {
+            check_local_header(data, &mut self.reader)?;
+        }
         let limit_reader = find_content(data, &mut self.reader)?;
 
-        let crypto_reader = make_crypto_reader(data, limit_reader, password, data.aes_mode)?;
+        let crypto_reader = make_crypto_reader(
+            data,
+            limit_reader,
+            password,
+            data.aes_mode,
+            self.registry.as_deref(),
+        )?;
 
         Ok(ZipFile {
             data: Cow::Borrowed(data),
-            reader: make_reader(data.compression_method, data.crc32, crypto_reader)?,
+            reader: make_reader(
+                data,
+                verify_crc32,
+                self.shared.config.verify_sizes,
+                self.shared
+                    .config
+                    .decompression_limits
+                    .max_expansion_ratio
+                    .map(|ratio| data.compressed_size.saturating_mul(ratio)),
+                crypto_reader,
+                options,
+                self.registry.as_deref(),
+            )?,
         })
     }
 
+    /// Decompress entries with the decoders in `registry`, falling back to the built-in methods
+    /// for the others.
+    pub fn set_compression_registry(&mut self, registry: CompressionRegistry) {
+        self.registry = Some(Arc::new(registry));
+    }
+
     /// Unwrap and return the inner reader object
     ///
     /// The position of the reader is undefined.
     pub fn into_inner(self) -> R {
         self.reader
     }
+
+    /// Turns the archive into a single reader of the decompressed contents of all its files, one
+    /// after the other in central directory order, such as to feed the logs of an archive to a
+    /// line-based tool.
+    ///
+    /// `header` is called before each file and what it returns is read before the file's
+    /// contents, so it can return a separator, a header naming the file, or nothing.
+    /// Directories are skipped. Each file is decompressed as it's read, so files of any size can
+    /// be streamed through.
+    ///
+    /// ```
+    /// # fn doit() -> zip::result::ZipResult<()>
+    /// # {
+    /// use std::io::{BufRead, BufReader};
+    ///
+    /// # let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// # writer.start_file("a.log", zip::write::SimpleFileOptions::default())?;
+    /// # std::io::Write::write_all(&mut writer, b"started\n")?;
+    /// # let archive = writer.finish_into_readable()?;
+    /// let reader =
+    ///     archive.into_concatenated_reader(|file| format!("==> {} <==\n", file.name()).into());
+    /// for line in BufReader::new(reader).lines() {
+    ///     println!("{}", line?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # doit().unwrap();
+    /// ```
+    pub fn into_concatenated_reader<'a, F>(self, header: F) -> ConcatenatedReader<'a, R, F>
+    where
+        F: FnMut(&ZipFile<'_>) -> Vec<u8>,
+    {
+        ConcatenatedReader {
+            reader: Rc::new(RefCell::new(self.reader)),
+            shared: self.shared,
+            comment: self.comment,
+            registry: self.registry,
+            header,
+            next_index: 0,
+            buffer: Vec::new(),
+            position: 0,
+            file: None,
+        }
+    }
+
+    /// Unwrap and return the inner reader object, positioned at the end of the data prepended
+    /// to the archive, as given by [`ZipArchive::prefix_len`].
+    ///
+    /// Reading [`ZipArchive::prefix_len`] bytes from the start of the reader gets the prefix,
+    /// and reading from here gets the archive proper.
+    pub fn into_inner_at_zip_start(mut self) -> ZipResult<R> {
+        let prefix_len = self.prefix_len();
+        self.reader.seek(io::SeekFrom::Start(prefix_len))?;
+        Ok(self.reader)
+    }
 }
 
 /// Holds the AES information of a file in the zip archive
@@ -1216,21 +2409,254 @@ pub struct AesInfo {
     pub salt: Vec<u8>,
 }
 
+/// Where the records of a file are in the archive, as listed by [`ZipArchive::entry_layout`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryLayout {
+    /// The name of the file
+    pub name: Box<str>,
+    /// The offset of the local header
+    pub header_start: u64,
+    /// The offset of the file data, after the local header
+    pub data_start: u64,
+    /// The size of the file data, as stored
+    pub compressed_size: u64,
+    /// The offset of the central directory header
+    pub central_header_start: u64,
+    /// The compression method, which for AES encrypted files is the one used under the encryption
+    pub compression_method: CompressionMethod,
+    /// The general purpose bit flags of the local header
+    pub flags: u16,
+}
+
+/// The outcome of checking a file with [`ZipArchive::verify`]
+#[derive(Debug)]
+pub struct VerifyResult {
+    /// The name of the file
+    pub name: Box<str>,
+    /// Why the file failed, or `Ok` if all of it could be read and it passed its checks
+    pub result: ZipResult<()>,
+}
+
+impl VerifyResult {
+    /// Returns true if the file passed.
+    pub const fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Reads the contents of all the files of an archive one after the other, as returned by
+/// [`ZipArchive::into_concatenated_reader`]
+///
+/// The current file is read through the archive's reader, which custom decoders registered with
+/// [`ZipArchive::set_compression_registry`] may borrow for `'a`.
+pub struct ConcatenatedReader<'a, R: Read, F> {
+    /// Shared with the reader of the current file's contents
+    reader: Rc<RefCell<R>>,
+    shared: Arc<Shared>,
+    comment: Arc<[u8]>,
+    registry: Option<Arc<CompressionRegistry>>,
+    header: F,
+    next_index: usize,
+    /// The header of the current file
+    buffer: Vec<u8>,
+    position: usize,
+    /// Reads the contents of the current file once its header has been read
+    file: Option<Box<DecompressingReader<'a, io::Take<SharedReader<R>>>>>,
+}
+
+impl<'a, R: Read, F> ConcatenatedReader<'a, R, F> {
+    /// Returns the archive, which can be read again from its first file
+    ///
+    /// Fails if a custom decoder kept the archive's reader.
+    pub fn into_inner(self) -> ZipResult<ZipArchive<R>> {
+        drop(self.file);
+        let reader = Rc::try_unwrap(self.reader).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "Custom decoder kept its reader after being dropped",
+            )
+        })?;
+        Ok(ZipArchive {
+            reader: reader.into_inner(),
+            shared: self.shared,
+            comment: self.comment,
+            registry: self.registry,
+        })
+    }
+}
+
+impl<'a, R: Read + Seek + 'a, F> ConcatenatedReader<'a, R, F> {
+    /// Starts reading the contents of `data` after its header.
+    fn open(&mut self, data: &ZipFileData) -> ZipResult<()> {
+        if data.encrypted {
+            return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED));
+        }
+        let config = &self.shared.config;
+        {
+            let mut reader = self.reader.borrow_mut();
+            if config.verify_local_headers {
+                check_local_header(data, &mut *reader)?;
+            }
+            let data_start = match data.data_start.get() {
+                Some(data_start) => *data_start,
+                None => find_data_start(data, &mut *reader)?,
+            };
+            reader.seek(SeekFrom::Start(data_start))?;
+        }
+        let content = SharedReader(self.reader.clone()).take(data.compressed_size);
+        self.file = Some(Box::new(make_decompressing_reader(
+            data,
+            true,
+            config.verify_sizes,
+            config
+                .decompression_limits
+                .max_expansion_ratio
+                .map(|ratio| data.compressed_size.saturating_mul(ratio)),
+            content,
+            ReadOptions::default(),
+            self.registry.as_deref(),
+        )?));
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + std::fmt::Debug, F> std::fmt::Debug for ConcatenatedReader<'a, R, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConcatenatedReader")
+            .field("reader", &self.reader)
+            .field("next_index", &self.next_index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, R, F> Read for ConcatenatedReader<'a, R, F>
+where
+    R: Read + Seek + 'a,
+    F: FnMut(&ZipFile<'_>) -> Vec<u8>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.position < self.buffer.len() {
+                let count = buf.len().min(self.buffer.len() - self.position);
+                buf[..count].copy_from_slice(&self.buffer[self.position..][..count]);
+                self.position += count;
+                return Ok(count);
+            }
+            if let Some(file) = &mut self.file {
+                match file.read(buf)? {
+                    0 if !buf.is_empty() => self.file = None,
+                    count => return Ok(count),
+                }
+            }
+            let shared = self.shared.clone();
+            let Some(data) = shared.files.get(self.next_index) else {
+                return Ok(0);
+            };
+            self.next_index += 1;
+            if data.is_dir() {
+                continue;
+            }
+            self.buffer = (self.header)(&ZipFile {
+                data: Cow::Borrowed(data),
+                reader: ZipFileReader::NoReader,
+            });
+            self.position = 0;
+            self.open(data)?;
+        }
+    }
+}
+
 const fn unsupported_zip_error<T>(detail: &'static str) -> ZipResult<T> {
     Err(ZipError::UnsupportedArchive(detail))
 }
 
+#[cfg(any(unix, windows))]
+const SYMLINK_ESCAPES: &str = "Symlink target is outside the extraction directory";
+const PATH_THROUGH_SYMLINK: &str = "Entry would be extracted through a symlink";
+const MISSING_ZIP64_RECORD: &str =
+    "End of central directory defers to a ZIP64 record that is missing or invalid";
+
+/// Checks that a symlink extracted to `link`, relative to the extraction directory, and pointing
+/// to `target` doesn't lead out of that directory.
+///
+/// Only the paths are compared, so absolute targets are always rejected. So are targets with a
+/// `..` after another component: that component may be a symlink, extracted before or after this
+/// one, and `..` would then go up from wherever it leads rather than back to where it started.
+#[cfg(any(unix, windows))]
+fn symlink_is_enclosed(link: &Path, target: &Path) -> bool {
+    use std::path::Component;
+
+    // The link's parent directories were created without following any symlinks, so `..` can
+    // only be a problem in the target
+    let parent = link.parent().unwrap_or(Path::new(""));
+    let components = parent.components().map(|component| (component, false));
+    let target_components = target.components().map(|component| (component, true));
+    let mut depth = 0usize;
+    let mut descended_in_target = false;
+    for (component, in_target) in components.chain(target_components) {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return false,
+            Component::CurDir => {}
+            Component::ParentDir if descended_in_target => return false,
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(parent_depth) => depth = parent_depth,
+                None => return false,
+            },
+            Component::Normal(_) => {
+                depth += 1;
+                descended_in_target |= in_target;
+            }
+        }
+    }
+    true
+}
+
+/// Where the volume with the given disk number starts in a split archive.
+fn volume_start(volumes: &[u64], disk: u32) -> ZipResult<u64> {
+    volumes
+        .get(disk as usize)
+        .copied()
+        .ok_or(InvalidArchive("Archive refers to a missing volume"))
+}
+
 /// Parse a central directory entry to collect the information for the file.
 pub(crate) fn central_header_to_zip_file<R: Read + Seek>(
     reader: &mut R,
     archive_offset: u64,
+    name_encoding: NameEncoding,
+    volumes: &[u64],
 ) -> ZipResult<ZipFileData> {
     let central_header_start = reader.stream_position()?;
 
     // Parse central header
-    let block = ZipCentralEntryBlock::parse(reader)?;
-    let file =
-        central_header_to_zip_file_inner(reader, archive_offset, central_header_start, block)?;
+    let block = match ZipCentralEntryBlock::parse(reader) {
+        Ok(block) => block,
+        Err(e) => {
+            // An encrypted central directory starts with an archive extra data record instead
+            let mut magic = [0u8; 4];
+            reader.seek(io::SeekFrom::Start(central_header_start))?;
+            reader.read_exact(&mut magic)?;
+            if spec::Magic::from_le_bytes(magic) == spec::Magic::ARCHIVE_EXTRA_DATA_SIGNATURE {
+                return Err(ZipError::StrongEncryptionUnsupported(
+                    "the central directory is encrypted",
+                ));
+            }
+            return Err(e);
+        }
+    };
+    let archive_offset = if volumes.is_empty() {
+        archive_offset
+    } else {
+        // The local header is on the disk this entry names
+        volume_start(volumes, block.disk_number as u32)? + archive_offset
+    };
+    let file = central_header_to_zip_file_inner(
+        reader,
+        archive_offset,
+        central_header_start,
+        name_encoding,
+        block,
+    )?;
     let central_header_end = reader.stream_position()?;
     let data_start = find_data_start(&file, reader)?;
     if data_start > central_header_start {
@@ -1250,11 +2676,28 @@ fn read_variable_length_byte_field<R: Read>(reader: &mut R, len: usize) -> io::R
     Ok(data)
 }
 
+/// Rejects entries using PKWARE's Strong Encryption, signalled by general purpose flag bits 6
+/// and 13, since their data and, with bit 13, their headers can't be decrypted.
+fn check_strong_encryption(flags: u16) -> ZipResult<()> {
+    if flags & (1 << 13) != 0 {
+        return Err(ZipError::StrongEncryptionUnsupported(
+            "the central directory is encrypted",
+        ));
+    }
+    if flags & (1 << 6) != 0 {
+        return Err(ZipError::StrongEncryptionUnsupported(
+            "the file data is encrypted",
+        ));
+    }
+    Ok(())
+}
+
 /// Parse a central directory entry to collect the information for the file.
 fn central_header_to_zip_file_inner<R: Read>(
     reader: &mut R,
     archive_offset: u64,
     central_header_start: u64,
+    name_encoding: NameEncoding,
     block: ZipCentralEntryBlock,
 ) -> ZipResult<ZipFileData> {
     let ZipCentralEntryBlock {
@@ -1278,6 +2721,7 @@ fn central_header_to_zip_file_inner<R: Read>(
         ..
     } = block;
 
+    check_strong_encryption(flags)?;
     let encrypted = flags & 1 == 1;
     let is_utf8 = flags & (1 << 11) != 0;
     let using_data_descriptor = flags & (1 << 3) != 0;
@@ -1287,11 +2731,11 @@ fn central_header_to_zip_file_inner<R: Read>(
     let file_comment_raw = read_variable_length_byte_field(reader, file_comment_length as usize)?;
     let file_name: Box<str> = match is_utf8 {
         true => String::from_utf8_lossy(&file_name_raw).into(),
-        false => file_name_raw.clone().from_cp437(),
+        false => name_encoding.decode(&file_name_raw),
     };
     let file_comment: Box<str> = match is_utf8 {
         true => String::from_utf8_lossy(&file_comment_raw).into(),
-        false => file_comment_raw.from_cp437(),
+        false => name_encoding.decode(&file_comment_raw),
     };
 
     // Construct the result
@@ -1304,6 +2748,12 @@ fn central_header_to_zip_file_inner<R: Read>(
         is_utf8,
         compression_method: CompressionMethod::parse_from_u16(compression_method),
         compression_level: None,
+        deflate_hint: DeflateHint::from_flags(
+            CompressionMethod::parse_from_u16(compression_method),
+            flags,
+        ),
+        general_purpose_flags: flags,
+        explicit_version_needed: None,
         last_modified_time: DateTime::try_from_msdos(last_mod_date, last_mod_time).ok(),
         crc32,
         compressed_size: compressed_size.into(),
@@ -1444,6 +2894,13 @@ pub(crate) fn parse_single_extra_field<R: Read>(
             file.compression_method = compression_method;
             file.aes_extra_data_start = bytes_already_read;
         }
+        0x000a => {
+            // NTFS extra field
+            // APPNOTE 4.5.5
+            if let Some(times) = NtfsTimes::try_from_reader(reader, len)? {
+                file.extra_fields.push(ExtraField::NtfsTimes(times));
+            }
+        }
         0x5455 => {
             // extended timestamp
             // https://libzip.org/specifications/extrafld.txt
@@ -1452,24 +2909,49 @@ pub(crate) fn parse_single_extra_field<R: Read>(
                 ExtendedTimestamp::try_from_reader(reader, len)?,
             ));
         }
+        0x7875 => {
+            // Info-ZIP Unix Extra Field (UID/GID)
+            // https://libzip.org/specifications/extrafld.txt
+            if let Some(ids) = InfoZipUnix::try_from_reader(reader, len)? {
+                file.extra_fields.push(ExtraField::InfoZipUnix(ids));
+            }
+        }
+        0x7855 => {
+            // Info-ZIP Unix2 Extra Field (16-bit UID/GID, local header only)
+            // https://libzip.org/specifications/extrafld.txt
+            if let Some(ids) = InfoZipUnix2::try_from_reader(reader, len)? {
+                file.extra_fields.push(ExtraField::InfoZipUnix2(ids));
+            }
+        }
         0x6375 => {
             // Info-ZIP Unicode Comment Extra Field
             // APPNOTE 4.6.8 and https://libzip.org/specifications/extrafld.txt
-            file.file_comment = String::from_utf8(
-                UnicodeExtraField::try_from_reader(reader, len)?
-                    .unwrap_valid(file.file_comment.as_bytes())?
-                    .into_vec(),
-            )?
-            .into();
+            // A stale field is ignored in favor of the header, as the specification says
+            let field = UnicodeExtraField::try_from_reader(reader, len)?;
+            if disallow_zip64 && !field.is_supported_version() {
+                return Err(InvalidArchive(
+                    "Unicode extra field has an unsupported version",
+                ));
+            }
+            if let Some(file_comment) = field.valid_utf8(file.file_comment.as_bytes()) {
+                file.file_comment = file_comment.into();
+            }
         }
         0x7075 => {
             // Info-ZIP Unicode Path Extra Field
             // APPNOTE 4.6.9 and https://libzip.org/specifications/extrafld.txt
-            file.file_name_raw = UnicodeExtraField::try_from_reader(reader, len)?
-                .unwrap_valid(&file.file_name_raw)?;
-            file.file_name =
-                String::from_utf8(file.file_name_raw.clone().into_vec())?.into_boxed_str();
-            file.is_utf8 = true;
+            // Only the decoded name is replaced; the raw name keeps the bytes stored in the header.
+            // A stale field is ignored in favor of the header, as the specification says
+            let field = UnicodeExtraField::try_from_reader(reader, len)?;
+            if disallow_zip64 && !field.is_supported_version() {
+                return Err(InvalidArchive(
+                    "Unicode extra field has an unsupported version",
+                ));
+            }
+            if let Some(file_name) = field.valid_utf8(&file.file_name_raw) {
+                file.file_name = file_name.into_boxed_str();
+                file.is_utf8 = true;
+            }
         }
         _ => {
             reader.read_exact(&mut vec![0u8; len as usize])?;
@@ -1491,6 +2973,23 @@ impl<'a> ZipFile<'a> {
         std::mem::replace(&mut self.reader, ZipFileReader::NoReader).into_inner()
     }
 
+    /// Feed the decompressed contents of the file to `f` one chunk at a time, rather than
+    /// reading them into a buffer in a loop.
+    ///
+    /// Each chunk is what a single read from the decompressor produced, so chunks vary in size,
+    /// up to 32 KiB. Reading stops at the first error, including one returned by `f`.
+    pub fn read_chunks<F: FnMut(&[u8]) -> io::Result<()>>(&mut self, mut f: F) -> io::Result<()> {
+        let mut buf = vec![0; 32 * 1024];
+        loop {
+            match self.reader.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(count) => f(&buf[..count])?,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
     /// Get the version of the file
     pub fn version_made_by(&self) -> (u8, u8) {
         (
@@ -1517,7 +3016,10 @@ impl<'a> ZipFile<'a> {
 
     /// Get the name of the file, in the raw (internal) byte representation.
     ///
-    /// The encoding of this data is currently undefined.
+    /// These are the bytes stored in the header, untouched. They're UTF-8 if the entry's UTF-8
+    /// flag is set; otherwise their encoding is undefined, though it's usually CP437 or the
+    /// system code page of the machine that created the archive (such as Shift-JIS). A name
+    /// taken from an Info-ZIP Unicode Path extra field only affects [`ZipFile::name`].
     pub fn name_raw(&self) -> &[u8] {
         &self.get_metadata().file_name_raw
     }
@@ -1568,11 +3070,26 @@ impl<'a> ZipFile<'a> {
         &self.get_metadata().file_comment
     }
 
+    /// Get the comment of the file as a string, decoded as UTF-8 if the entry's UTF-8 flag
+    /// (general purpose bit 11) is set and as CP437 otherwise.
+    ///
+    /// The comment is decoded when the central directory is read, so this is the same as
+    /// [`ZipFile::comment`].
+    pub fn comment_str(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.comment())
+    }
+
     /// Get the compression method used to store the file
     pub fn compression(&self) -> CompressionMethod {
         self.get_metadata().compression_method
     }
 
+    /// Get the compression hint recorded for a Deflate or Deflate64 entry, or `None` for any
+    /// other compression method
+    pub fn deflate_hint(&self) -> Option<DeflateHint> {
+        self.get_metadata().deflate_hint
+    }
+
     /// Get if the files is encrypted or not
     pub fn encrypted(&self) -> bool {
         self.data.encrypted
@@ -1592,9 +3109,17 @@ impl<'a> ZipFile<'a> {
     pub fn last_modified(&self) -> Option<DateTime> {
         self.data.last_modified_time
     }
-    /// Returns whether the file is actually a directory
+    /// Returns whether the file is actually a directory: its name ends with `/`, or its external
+    /// attributes have the Unix or MS-DOS directory bit set. Extraction creates a directory for
+    /// such an entry instead of writing a file.
     pub fn is_dir(&self) -> bool {
-        is_dir(self.name())
+        self.get_metadata().is_dir()
+    }
+
+    /// Returns whether the file only holds metadata added by macOS, i.e. it's under `__MACOSX/`
+    /// or is an AppleDouble file whose name starts with `._`
+    pub fn is_macos_metadata(&self) -> bool {
+        is_macos_metadata(self.name())
     }
 
     /// Returns whether the file is actually a symbolic link
@@ -1613,11 +3138,55 @@ impl<'a> ZipFile<'a> {
         self.get_metadata().unix_mode()
     }
 
-    /// Get the CRC32 hash of the original file
+    /// Get the CRC32 hash of the original file, as recorded in the archive
     pub fn crc32(&self) -> u32 {
         self.get_metadata().crc32
     }
 
+    /// Get the CRC32 hash of the data that was read, once it has been read to the end
+    ///
+    /// This is the value that's checked against [`ZipFile::crc32`], or against the data
+    /// descriptor of a streamed entry. It's `None` before the end of the data is reached, for a
+    /// raw reader, and for AE-2 encrypted files, which don't record a CRC32.
+    pub fn computed_crc32(&self) -> Option<u32> {
+        match &self.reader {
+            ZipFileReader::Compressed(reader) => reader.computed_crc32(),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileReader::DataDescriptor(reader) => {
+                reader.done.then(|| reader.hasher.clone().finalize())
+            }
+            _ => None,
+        }
+    }
+
+    /// Read up to the first `len` bytes of the decompressed data, e.g. to detect the type of the
+    /// file from its magic number. Fewer bytes are returned only if the file is shorter, and the
+    /// rest of the file can still be read afterwards.
+    ///
+    /// ```
+    /// # fn main() -> zip::result::ZipResult<()> {
+    /// use std::io::{Cursor, Read, Write};
+    /// use zip::{write::SimpleFileOptions, ZipWriter};
+    ///
+    /// let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    /// writer.start_file("image.png", SimpleFileOptions::default())?;
+    /// writer.write_all(b"\x89PNG\r\n\x1a\nrest of the image")?;
+    /// let mut archive = writer.finish_into_readable()?;
+    ///
+    /// let mut file = archive.by_name("image.png")?;
+    /// assert_eq!(file.read_prefix(8)?, b"\x89PNG\r\n\x1a\n");
+    /// let mut rest = String::new();
+    /// file.read_to_string(&mut rest)?;
+    /// assert_eq!(rest, "rest of the image");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_prefix(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut prefix = Vec::with_capacity(len.min(self.size() as usize));
+        self.by_ref().take(len as u64).read_to_end(&mut prefix)?;
+        Ok(prefix)
+    }
+
     /// Get the extra data of the zip header for this file
     pub fn extra_data(&self) -> Option<&[u8]> {
         self.get_metadata()
@@ -1647,6 +3216,22 @@ impl<'a> ZipFile<'a> {
     pub fn extra_data_fields(&self) -> impl Iterator<Item = &ExtraField> {
         self.data.extra_fields.iter()
     }
+
+    /// Get the Unix timestamps and ownership of the file, if it has either the extended timestamp
+    /// or the Info-ZIP Unix extra field.
+    pub fn unix_extra(&self) -> Option<UnixExtra> {
+        UnixExtra::from_extra_fields(self.extra_data_fields())
+    }
+
+    /// Get the NTFS timestamps, which have a 100-nanosecond precision, if the file has them.
+    ///
+    /// These are usually written by archivers running on Windows.
+    pub fn ntfs_times(&self) -> Option<&NtfsTimes> {
+        self.extra_data_fields().find_map(|field| match field {
+            ExtraField::NtfsTimes(times) => Some(times),
+            _ => None,
+        })
+    }
 }
 
 impl<'a> HasZipMetadata for ZipFile<'a> {
@@ -1677,14 +3262,18 @@ impl<'a, R: Read> Read for ZipFileSeek<'a, R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         match &mut self.reader {
             ZipFileSeekReader::Raw(r) => r.read(buf),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileSeekReader::Deflated(r) => r.read(buf),
         }
     }
 }
 
-impl<'a, R: Seek> Seek for ZipFileSeek<'a, R> {
+impl<'a, R: Read + Seek> Seek for ZipFileSeek<'a, R> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         match &mut self.reader {
             ZipFileSeekReader::Raw(r) => r.seek(pos),
+            #[cfg(feature = "_deflate-any")]
+            ZipFileSeekReader::Deflated(r) => r.seek(pos),
         }
     }
 }
@@ -1700,6 +3289,12 @@ impl<'a> Drop for ZipFile<'a> {
         // self.data is Owned, this reader is constructed by a streaming reader.
         // In this case, we want to exhaust the reader so that the next file is accessible.
         if let Cow::Owned(_) = self.data {
+            // The end of an entry with a data descriptor can only be found by decompressing it.
+            #[cfg(feature = "_deflate-any")]
+            if let ZipFileReader::DataDescriptor(reader) = &mut self.reader {
+                let _ = copy(reader, &mut sink());
+                return;
+            }
             // Get the inner `Take` reader so all decryption, decompression and CRC calculation is skipped.
             if let Ok(mut inner) = self.take_raw_reader() {
                 let _ = copy(&mut inner, &mut sink());
@@ -1724,7 +3319,53 @@ impl<'a> Drop for ZipFile<'a> {
 /// * `comment`: set to an empty string
 /// * `data_start`: set to 0
 /// * `external_attributes`: `unix_mode()`: will return None
-pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Option<ZipFile<'_>>> {
+pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Option<ZipFile<'a>>> {
+    let Some(result) = read_local_header_from_stream(reader)? else {
+        return Ok(None);
+    };
+    if result.using_data_descriptor {
+        return Err(ZipError::UnsupportedArchive(
+            "The file length is not available in the local header",
+        ));
+    }
+    zipfile_from_stream(result, reader).map(Some)
+}
+
+/// Like [`read_zipfile_from_stream`], but also reads Deflated entries that are followed by a data
+/// descriptor, since a buffered reader can stop at the end of the compressed data.
+pub(crate) fn read_zipfile_from_bufread<'a, R: BufRead>(
+    reader: &'a mut R,
+) -> ZipResult<Option<ZipFile<'a>>> {
+    let Some(result) = read_local_header_from_stream(reader)? else {
+        return Ok(None);
+    };
+    zipfile_from_bufread(result, reader).map(Some)
+}
+
+/// Opens the data of an entry whose local header was just read from `reader`.
+pub(crate) fn zipfile_from_bufread<'a, R: BufRead>(
+    result: ZipFileData,
+    reader: &'a mut R,
+) -> ZipResult<ZipFile<'a>> {
+    if result.using_data_descriptor {
+        #[cfg(feature = "_deflate-any")]
+        if result.compression_method == CompressionMethod::Deflated {
+            let reader = DataDescriptorReader::new(&result, reader);
+            return Ok(ZipFile {
+                data: Cow::Owned(result),
+                reader: ZipFileReader::DataDescriptor(Box::new(reader)),
+            });
+        }
+        return Err(ZipError::UnsupportedArchive(
+            "The file length is not available in the local header",
+        ));
+    }
+    zipfile_from_stream(result, reader)
+}
+
+pub(crate) fn read_local_header_from_stream<R: Read>(
+    reader: &mut R,
+) -> ZipResult<Option<ZipFileData>> {
     // We can't use the typical ::parse() method, as we follow separate code paths depending on the
     // "magic" value (since the magic value will be from the central directory header if we've
     // finished iterating over all the actual files).
@@ -1740,6 +3381,7 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
     }
 
     let block = block.from_le();
+    check_strong_encryption(block.flags)?;
 
     let mut result = ZipFileData::from_local_block(block, reader)?;
 
@@ -1747,17 +3389,30 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
         Ok(..) | Err(ZipError::Io(..)) => {}
         Err(e) => return Err(e),
     }
+    Ok(Some(result))
+}
 
-    let limit_reader = (reader as &'a mut dyn Read).take(result.compressed_size);
-
-    let result_crc32 = result.crc32;
-    let result_compression_method = result.compression_method;
-    let crypto_reader = make_crypto_reader(&result, limit_reader, None, None)?;
-
-    Ok(Some(ZipFile {
+fn zipfile_from_stream<'a>(
+    result: ZipFileData,
+    reader: &'a mut dyn Read,
+) -> ZipResult<ZipFile<'a>> {
+    let limit_reader = reader.take(result.compressed_size);
+
+    let crypto_reader = make_crypto_reader(&result, limit_reader, None, None, None)?;
+    let reader = make_reader(
+        &result,
+        true,
+        true,
+        None,
+        crypto_reader,
+        ReadOptions::default(),
+        None,
+    )?;
+
+    Ok(ZipFile {
         data: Cow::Owned(result),
-        reader: make_reader(result_compression_method, result_crc32, crypto_reader)?,
-    }))
+        reader,
+    })
 }
 
 #[cfg(test)]
@@ -1981,7 +3636,9 @@ mod test {
         let mut v = Vec::new();
         v.extend_from_slice(include_bytes!("../tests/data/chinese.zip"));
         let mut reader = ZipArchive::new(Cursor::new(v)).unwrap();
-        reader.by_name("七个房间.txt").unwrap();
+        let file = reader.by_name("七个房间.txt").unwrap();
+        // The name stored in the header is GBK-encoded
+        assert_eq!(file.name_raw(), b"\xc6\xdf\xb8\xf6\xb7\xbf\xbc\xe4.txt");
     }
 
     #[test]
@@ -1992,6 +3649,47 @@ mod test {
         reader.by_name("你好.txt").unwrap();
     }
 
+    #[test]
+    fn test_cp437_name() -> ZipResult<()> {
+        let v = include_bytes!("../tests/data/cp437_name.zip");
+        let mut reader = ZipArchive::new(Cursor::new(v))?;
+        assert_eq!(reader.by_index(0)?.name(), "M\u{fc}ller.txt");
+        reader.by_name("M\u{fc}ller.txt")?;
+
+        let mut stream = Cursor::new(v);
+        let file = crate::read::read_zipfile_from_stream(&mut stream)?.unwrap();
+        assert_eq!(file.name(), "M\u{fc}ller.txt");
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_encoding() -> ZipResult<()> {
+        use super::NameEncoding;
+
+        fn latin1(raw: &[u8]) -> String {
+            raw.iter().map(|&b| b as char).collect()
+        }
+
+        let v = include_bytes!("../tests/data/cp437_name.zip");
+        for (encoding, name) in [
+            (NameEncoding::Cp437, "M\u{fc}ller.txt"),
+            (NameEncoding::Utf8, "M\u{fffd}ller.txt"),
+            (NameEncoding::Custom(latin1), "M\u{81}ller.txt"),
+        ] {
+            let mut reader = ZipArchive::new_with_encoding(Cursor::new(v), encoding)?;
+            assert_eq!(reader.by_index(0)?.name(), name);
+        }
+
+        // The UTF-8 flag takes precedence
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("caf\u{e9}.txt", SimpleFileOptions::default())?;
+        let bytes = writer.finish()?.into_inner();
+        let mut reader =
+            ZipArchive::new_with_encoding(Cursor::new(bytes), NameEncoding::Custom(latin1))?;
+        assert_eq!(reader.by_index(0)?.name(), "caf\u{e9}.txt");
+        Ok(())
+    }
+
     #[test]
     fn test_utf8_2() {
         let mut v = Vec::new();
@@ -2014,6 +3712,9 @@ mod test {
         }
 
         let mut reader = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(reader.len(), u16::MAX as usize + 1);
+        assert_eq!(reader.total_entries(), u16::MAX as u64 + 1);
+        assert!(!reader.is_empty());
         for i in 0..=u16::MAX {
             let expected_name = format!("{i}.txt");
             let expected_contents = i.to_string();
@@ -2030,4 +3731,1650 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_extract_with_progress() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("dir/", options)?;
+        writer.start_file("dir/big.bin", options)?;
+        writer.write_all(&[7u8; 20000])?;
+        writer.start_file("empty.txt", options)?;
+        let mut reader = writer.finish_into_readable()?;
+
+        let tempdir = TempDir::new("test_extract_with_progress")?;
+        let mut calls = Vec::new();
+        reader.extract_with_progress(&tempdir, |file, written, total| {
+            calls.push((file.name().to_string(), written, total));
+        })?;
+
+        assert!(calls.iter().all(|(name, _, _)| name != "dir/"));
+        let big: Vec<_> = calls
+            .iter()
+            .filter(|(name, _, _)| name == "dir/big.bin")
+            .collect();
+        assert!(big.len() > 2);
+        assert_eq!(big.first().unwrap().1, 0);
+        assert_eq!(big.last().unwrap().1, 20000);
+        assert!(big.iter().all(|(_, _, total)| *total == 20000));
+        assert!(big.windows(2).all(|w| w[0].1 < w[1].1));
+        assert_eq!(calls.last().unwrap(), &("empty.txt".to_string(), 0, 0));
+        assert_eq!(
+            std::fs::read(tempdir.path().join("dir/big.bin"))?,
+            vec![7u8; 20000]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_matching() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        for name in [
+            "a.txt",
+            "b.bin",
+            "src/lib.rs",
+            "src/read/glob.rs",
+            "src/notes.txt",
+        ] {
+            writer.start_file(name, options)?;
+            writer.write_all(name.as_bytes())?;
+        }
+        writer.start_file("../escape.txt", options)?;
+        let mut reader = writer.finish_into_readable()?;
+
+        let tempdir = TempDir::new("test_extract_matching")?;
+        reader.extract_matching(&tempdir, "src/**/*.rs")?;
+        let root = tempdir.path();
+        assert_eq!(std::fs::read(root.join("src/lib.rs"))?, b"src/lib.rs");
+        assert!(root.join("src/read/glob.rs").is_file());
+        assert!(!root.join("src/notes.txt").exists());
+        assert!(!root.join("a.txt").exists());
+        assert!(!root.join("b.bin").exists());
+
+        reader.extract_matching(&tempdir, "*.txt")?;
+        assert!(root.join("a.txt").is_file());
+        assert!(!root.join("src/notes.txt").exists());
+
+        // Matched names are still sanitized
+        assert!(reader.extract_matching(&tempdir, "../*").is_err());
+        assert!(!root.join("../escape.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "_deflate-any")]
+    fn test_raw_reader_skips_crc() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "data.txt",
+            SimpleFileOptions::default().compression_method(crate::CompressionMethod::Deflated),
+        )?;
+        writer.write_all(&b"raw bytes ".repeat(100))?;
+        let mut reader = writer.finish_into_readable()?;
+        let mut compressed = Vec::new();
+        reader
+            .by_name_raw("data.txt")?
+            .read_to_end(&mut compressed)?;
+        let crc32 = reader.by_name("data.txt")?.crc32();
+
+        // Corrupt the stored CRC in the central directory
+        let mut bytes = reader.into_inner().into_inner();
+        let central_crc = bytes.len() - 22 - 46 - "data.txt".len() + 16;
+        bytes[central_crc] ^= 0xFF;
+        let mut reader = ZipArchive::new(Cursor::new(bytes))?;
+        assert!(reader
+            .by_name("data.txt")?
+            .read_to_end(&mut Vec::new())
+            .is_err());
+
+        let mut file = reader.by_name_raw("data.txt")?;
+        assert_eq!(file.compression(), crate::CompressionMethod::Deflated);
+        assert_eq!(file.crc32(), crc32 ^ 0xFF);
+        let mut raw = Vec::new();
+        file.read_to_end(&mut raw)?;
+        assert_eq!(raw, compressed);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(unix, windows))]
+    fn test_symlink_is_enclosed() {
+        use super::symlink_is_enclosed;
+        use std::path::Path;
+
+        let enclosed =
+            |link: &str, target: &str| symlink_is_enclosed(link.as_ref(), target.as_ref());
+        assert!(enclosed("link", "file"));
+        assert!(enclosed("a/b/link", "../../file"));
+        assert!(enclosed("a/link", "../b/c"));
+        assert!(!enclosed("a/link", "b/../c"));
+        assert!(enclosed("link", "."));
+        assert!(!enclosed("link", ".."));
+        assert!(!enclosed("a/link", "../../file"));
+        assert!(!enclosed("a/link", "b/../../../file"));
+        assert!(!enclosed("link", "/etc/passwd"));
+        assert!(!symlink_is_enclosed(Path::new("link"), Path::new("/")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_rejects_escaping_symlink() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_symlink("dir/ok", "../file", SimpleFileOptions::default())?;
+        writer.add_symlink("dir/escape", "../../file", SimpleFileOptions::default())?;
+        let mut reader = writer.finish_into_readable()?;
+
+        let temp_dir = TempDir::new("test_extract_rejects_escaping_symlink")?;
+        assert!(matches!(
+            reader.extract(temp_dir.path()),
+            Err(crate::result::ZipError::InvalidArchive(
+                super::SYMLINK_ESCAPES
+            ))
+        ));
+        assert_eq!(
+            std::fs::read_link(temp_dir.path().join("dir/ok"))?,
+            std::path::Path::new("../file")
+        );
+        assert!(std::fs::symlink_metadata(temp_dir.path().join("dir/escape")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_rejects_chained_symlinks() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_symlink("a", ".", SimpleFileOptions::default())?;
+        writer.add_symlink("a/b", "..", SimpleFileOptions::default())?;
+        writer.start_file("a/b/escaped.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"outside")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let temp_dir = TempDir::new("test_extract_rejects_chained_symlinks")?;
+        let root = temp_dir.path().join("root");
+        assert!(matches!(
+            ZipArchive::new(Cursor::new(&bytes))?.extract(&root),
+            Err(crate::result::ZipError::InvalidArchive(
+                super::PATH_THROUGH_SYMLINK
+            ))
+        ));
+        assert!(!temp_dir.path().join("escaped.txt").exists());
+        assert!(std::fs::symlink_metadata(root.join("b")).is_err());
+
+        let root = temp_dir.path().join("parallel");
+        assert!(ZipArchive::new(Cursor::new(&bytes))?
+            .extract_parallel(&root, 3)
+            .is_err());
+        assert!(!temp_dir.path().join("escaped.txt").exists());
+
+        // A target that goes up through another link, whichever is extracted first
+        for links in [
+            [("x/l1", ".."), ("l2", "x/l1/../../etc")],
+            [("l2", "x/l1/../../etc"), ("x/l1", "..")],
+        ] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            for (link, target) in links {
+                writer.add_symlink(link, target, SimpleFileOptions::default())?;
+            }
+            let root = temp_dir.path().join("through_link");
+            assert!(matches!(
+                writer.finish_into_readable()?.extract(&root),
+                Err(crate::result::ZipError::InvalidArchive(
+                    super::SYMLINK_ESCAPES
+                ))
+            ));
+            assert!(std::fs::symlink_metadata(root.join("l2")).is_err());
+            std::fs::remove_dir_all(&root)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_extract_unix_permissions() -> ZipResult<()> {
+        use super::Config;
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir/", SimpleFileOptions::default().unix_permissions(0o750))?;
+        writer.start_file(
+            "dir/script.sh",
+            SimpleFileOptions::default().unix_permissions(0o755),
+        )?;
+        writer.write_all(b"#!/bin/sh\n")?;
+        let bytes = writer.finish()?.into_inner();
+        let mode = |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode();
+
+        let temp_dir = TempDir::new("test_extract_unix_permissions")?;
+        ZipArchive::new(Cursor::new(&bytes))?.extract(temp_dir.path())?;
+        assert_eq!(mode(&temp_dir.path().join("dir")) & 0o777, 0o750);
+        assert_eq!(mode(&temp_dir.path().join("dir/script.sh")) & 0o777, 0o755);
+
+        let temp_dir = TempDir::new("test_extract_unix_permissions")?;
+        let config = Config {
+            extract_unix_permissions: false,
+            ..Default::default()
+        };
+        ZipArchive::with_config(config, Cursor::new(&bytes))?.extract(temp_dir.path())?;
+        assert_eq!(mode(&temp_dir.path().join("dir/script.sh")) & 0o111, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ntfs_times() -> ZipResult<()> {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut archive = ZipArchive::new(Cursor::new(include_bytes!("../tests/data/lzma.zip")))?;
+        let file = archive.by_name("hello.txt")?;
+        let times = file.ntfs_times().unwrap();
+        assert_eq!(times.mtime(), 133573395835404049);
+        assert_eq!(times.atime(), 0);
+        assert_eq!(
+            times.modified(),
+            Some(UNIX_EPOCH + Duration::new(1712865983, 540404900))
+        );
+        assert_eq!(times.accessed(), None);
+        assert_eq!(times.created(), None);
+        drop(file);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("no_ntfs.txt", SimpleFileOptions::default())?;
+        let mut archive = writer.finish_into_readable()?;
+        assert!(archive.by_index(0)?.ntfs_times().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unix_extra() -> ZipResult<()> {
+        let bytes = include_bytes!("../tests/data/info_zip_unix.zip");
+
+        // The central directory only has the modification time
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let unix_extra = archive.by_name("a.txt")?.unix_extra().unwrap();
+        assert_eq!(unix_extra.mtime(), Some(1704067200));
+        assert_eq!(unix_extra.atime(), None);
+        assert_eq!(unix_extra.ctime(), None);
+        assert_eq!(unix_extra.uid(), Some(1234));
+        assert_eq!(unix_extra.gid(), Some(5678));
+
+        let mut reader = Cursor::new(bytes);
+        let file = super::read_zipfile_from_stream(&mut reader)?.unwrap();
+        let unix_extra = file.unix_extra().unwrap();
+        assert_eq!(unix_extra.mtime(), Some(1704067200));
+        assert_eq!(unix_extra.atime(), Some(1704164645));
+        assert_eq!(unix_extra.uid(), Some(1234));
+        drop(file);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("plain.txt", SimpleFileOptions::default())?;
+        let mut archive = writer.finish_into_readable()?;
+        assert!(archive.by_index(0)?.unix_extra().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dir_from_attributes() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir/", SimpleFileOptions::default())?;
+        writer.start_file("file", SimpleFileOptions::default())?;
+        let mut bytes = writer.finish()?.into_inner();
+        // Drop the trailing slash, so only the attributes show that it's a directory
+        for i in 0..bytes.len() - 3 {
+            if &bytes[i..i + 4] == b"dir/" {
+                bytes[i + 3] = b'_';
+            }
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let dir = archive.by_name("dir_")?;
+        assert!(dir.is_dir());
+        assert!(!dir.is_file());
+        drop(dir);
+        assert!(archive.by_name("file")?.is_file());
+        let temp_dir = TempDir::new("test_dir_from_attributes")?;
+        archive.extract(temp_dir.path())?;
+        assert!(temp_dir.path().join("dir_").is_dir());
+        assert!(temp_dir.path().join("file").is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_prefix() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("long.txt", options)?;
+        writer.write_all(b"%PDF-1.7 and the rest")?;
+        writer.start_file("short.txt", options)?;
+        writer.write_all(b"%P")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut file = archive.by_name("long.txt")?;
+        assert_eq!(file.read_prefix(5)?, b"%PDF-");
+        assert_eq!(file.read_prefix(4)?, b"1.7 ");
+        let mut rest = Vec::new();
+        file.read_to_end(&mut rest)?;
+        assert_eq!(rest, b"and the rest");
+        assert!(file.read_prefix(5)?.is_empty());
+        drop(file);
+
+        let mut file = archive.by_name("short.txt")?;
+        assert_eq!(file.read_prefix(5)?, b"%P");
+        assert_eq!(file.computed_crc32(), Some(file.crc32()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_info_zip_unix2() -> ZipResult<()> {
+        use crate::write::FullFileOptions;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut options = FullFileOptions::default();
+        // 0x7855 is reserved, so it's written as 0xcafe and patched below
+        options.add_extra_data(0xcafe, Box::new([0xe8, 0x03, 0x64, 0x00]), false)?;
+        writer.start_file("unix2.txt", options.clone())?;
+        writer.start_file("both.txt", options.unix_metadata(1234, 5678, 0))?;
+        let mut malformed = FullFileOptions::default();
+        malformed.add_extra_data(0xcafe, Box::new([0xe8, 0x03, 0x64]), false)?;
+        writer.start_file("malformed.txt", malformed)?;
+        let mut bytes = writer.finish()?.into_inner();
+        for i in 0..bytes.len() - 1 {
+            if bytes[i..i + 2] == [0xfe, 0xca] {
+                bytes[i..i + 2].copy_from_slice(&[0x55, 0x78]);
+            }
+        }
+
+        let mut reader = Cursor::new(bytes);
+        let file = super::read_zipfile_from_stream(&mut reader)?.unwrap();
+        let unix_extra = file.unix_extra().unwrap();
+        assert_eq!(unix_extra.uid(), Some(1000));
+        assert_eq!(unix_extra.gid(), Some(100));
+        drop(file);
+        let file = super::read_zipfile_from_stream(&mut reader)?.unwrap();
+        let unix_extra = file.unix_extra().unwrap();
+        assert_eq!(unix_extra.uid(), Some(1234));
+        assert_eq!(unix_extra.gid(), Some(5678));
+        drop(file);
+        let file = super::read_zipfile_from_stream(&mut reader)?.unwrap();
+        assert_eq!(file.name(), "malformed.txt");
+        assert!(file.unix_extra().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_entries() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory("dir/", SimpleFileOptions::default())?;
+        writer.start_file("dir/a.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"hello")?;
+        let reader = writer.finish_into_readable()?;
+
+        let entries: Vec<_> = reader
+            .entries()
+            .map(|entry| (&*entry.file_name, entry.uncompressed_size, entry.crc32))
+            .collect();
+        assert_eq!(
+            entries,
+            [("dir/", 0, 0), ("dir/a.txt", 5, crc32fast::hash(b"hello"))]
+        );
+        assert!(reader.entries().next().unwrap().is_dir());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_names_sorted() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["b.txt", "a/", "a/c.txt", "B.txt", "\u{e9}.txt", "a.txt"] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+        }
+        let reader = writer.finish_into_readable()?;
+
+        assert_eq!(
+            reader.file_names().collect::<Vec<_>>(),
+            ["b.txt", "a/", "a/c.txt", "B.txt", "\u{e9}.txt", "a.txt"]
+        );
+        assert_eq!(
+            reader.file_names_sorted().collect::<Vec<_>>(),
+            ["B.txt", "a.txt", "a/", "a/c.txt", "b.txt", "\u{e9}.txt"]
+        );
+        // The cached order is reused
+        assert!(reader.file_names_sorted().eq(reader.file_names_sorted()));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "_deflate-any")]
+    fn test_deflate_hint() -> ZipResult<()> {
+        use crate::types::DeflateHint;
+        use crate::CompressionMethod::Deflated;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "deflated.txt",
+            SimpleFileOptions::default().compression_method(Deflated),
+        )?;
+        writer.write_all(b"hello")?;
+        writer.start_file(
+            "stored.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"hello")?;
+        let mut bytes = writer.finish()?.into_inner();
+        let central_start = bytes
+            .windows(4)
+            .position(|magic| magic == b"PK\x01\x02")
+            .unwrap();
+
+        for (bits, hint) in [
+            (0b000, DeflateHint::Normal),
+            (0b010, DeflateHint::Maximum),
+            (0b100, DeflateHint::Fast),
+            (0b110, DeflateHint::SuperFast),
+        ] {
+            bytes[central_start + 8] = bits;
+            let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+            assert_eq!(archive.by_name("deflated.txt")?.deflate_hint(), Some(hint));
+            assert_eq!(archive.by_name("stored.txt")?.deflate_hint(), None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_limits() -> ZipResult<()> {
+        use super::ArchiveLimits;
+        use crate::result::ZipError;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["a.txt", "long_name.txt", "c.txt"] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+        }
+        let bytes = writer.finish()?.into_inner();
+
+        let limits = ArchiveLimits {
+            max_entries: 3,
+            max_name_len: 13,
+            max_extra_len: 0,
+        };
+        assert_eq!(
+            ZipArchive::with_limits(Cursor::new(&bytes), limits)?.len(),
+            3
+        );
+        for limits in [
+            ArchiveLimits {
+                max_entries: 2,
+                ..limits
+            },
+            ArchiveLimits {
+                max_name_len: 12,
+                ..limits
+            },
+        ] {
+            assert!(matches!(
+                ZipArchive::with_limits(Cursor::new(&bytes), limits),
+                Err(ZipError::LimitExceeded(_))
+            ));
+        }
+        // The defaults don't limit anything
+        ZipArchive::with_limits(Cursor::new(&bytes), ArchiveLimits::default())?;
+
+        // The extended timestamp and Info-ZIP Unix fields take 9 + 15 bytes
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("a.txt", SimpleFileOptions::default().unix_metadata(1, 1, 0))?;
+        let bytes = writer.finish()?.into_inner();
+        let limits = ArchiveLimits {
+            max_extra_len: 24,
+            ..Default::default()
+        };
+        ZipArchive::with_limits(Cursor::new(&bytes), limits)?;
+        let limits = ArchiveLimits {
+            max_extra_len: 23,
+            ..Default::default()
+        };
+        assert!(matches!(
+            ZipArchive::with_limits(Cursor::new(&bytes), limits),
+            Err(ZipError::LimitExceeded(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "_deflate-any")]
+    fn test_decompression_limits() -> ZipResult<()> {
+        use super::{Config, DecompressionLimits};
+        use crate::result::ZipError;
+        use crate::CompressionMethod::Deflated;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "zeros",
+            SimpleFileOptions::default().compression_method(Deflated),
+        )?;
+        writer.write_all(&[0u8; 100_000])?;
+        let bytes = writer.finish()?.into_inner();
+        let with_limits = |decompression_limits| {
+            ZipArchive::with_config(
+                Config {
+                    decompression_limits,
+                    ..Default::default()
+                },
+                Cursor::new(bytes.clone()),
+            )
+        };
+
+        // The ratio is far above 100
+        let mut archive = with_limits(DecompressionLimits::default().max_expansion_ratio(100))?;
+        let err = archive
+            .by_index(0)?
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(
+            ZipError::from_read_error(err),
+            ZipError::DecompressionLimitExceeded
+        ));
+        let dir = TempDir::new("test_decompression_limits")?;
+        assert!(matches!(
+            archive.extract(dir.path()),
+            Err(ZipError::DecompressionLimitExceeded)
+        ));
+        let mut archive = with_limits(DecompressionLimits::default().max_expansion_ratio(10_000))?;
+        assert_eq!(archive.by_index(0)?.read_to_end(&mut Vec::new())?, 100_000);
+
+        let mut archive = with_limits(DecompressionLimits::default().max_total_bytes(99_999))?;
+        assert!(matches!(
+            archive.extract(dir.path()),
+            Err(ZipError::DecompressionLimitExceeded)
+        ));
+        // Reading a single entry isn't limited by the total
+        assert_eq!(archive.by_index(0)?.read_to_end(&mut Vec::new())?, 100_000);
+        let mut archive = with_limits(DecompressionLimits::default().max_total_bytes(100_000))?;
+        archive.extract(dir.path())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_comment_str() -> ZipResult<()> {
+        for (comment, expected) in [
+            (&b"plain"[..], "plain"),
+            ("caf\u{e9}".as_bytes(), "caf\u{e9}"),
+            (&b"M\x81nchen"[..], "M\u{fc}nchen"),
+        ] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.set_raw_comment(comment.into());
+            let archive = writer.finish_into_readable()?;
+            assert_eq!(archive.comment(), comment);
+            assert_eq!(archive.comment_str(), expected);
+        }
+
+        let mut archive =
+            ZipArchive::new(Cursor::new(include_bytes!("../tests/data/mimetype.zip")))?;
+        let file = archive.by_index(0)?;
+        assert_eq!(file.comment_str(), file.comment());
+        Ok(())
+    }
+
+    #[test]
+    fn test_by_index_seek() -> ZipResult<()> {
+        use std::io::{Seek, SeekFrom};
+
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "stored",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(&data)?;
+        #[cfg(feature = "_deflate-any")]
+        {
+            writer.start_file(
+                "deflated",
+                SimpleFileOptions::default().compression_method(crate::CompressionMethod::Deflated),
+            )?;
+            writer.write_all(&data)?;
+        }
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut names = vec!["stored"];
+        if cfg!(feature = "_deflate-any") {
+            names.push("deflated");
+        }
+        for name in names {
+            let mut file = archive.by_name_seek(name)?;
+            let mut buf = [0u8; 10];
+            for pos in [
+                SeekFrom::Start(5000),
+                SeekFrom::Current(-2000),
+                SeekFrom::End(-10),
+                SeekFrom::Start(0),
+            ] {
+                let offset = file.seek(pos)? as usize;
+                file.read_exact(&mut buf)?;
+                assert_eq!(buf, data[offset..offset + 10]);
+            }
+            assert_eq!(file.stream_position()?, 10);
+            assert_eq!(file.seek(SeekFrom::End(5))?, 10_000);
+            assert!(file.seek(SeekFrom::Current(-10_001)).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_by_index_stored_slice() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "stored.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"stored contents")?;
+        #[cfg(feature = "_deflate-any")]
+        {
+            writer.start_file(
+                "deflated.txt",
+                SimpleFileOptions::default().compression_method(crate::CompressionMethod::Deflated),
+            )?;
+            writer.write_all(b"deflated contents")?;
+        }
+        let bytes = writer.finish()?.into_inner();
+
+        let archive = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+        let slice = archive.by_index_stored_slice(0).unwrap();
+        assert_eq!(slice, b"stored contents");
+        // The slice points into the original buffer
+        assert!(bytes.as_ptr_range().contains(&slice.as_ptr()));
+        assert_eq!(archive.by_index_stored_slice(1), None);
+        assert_eq!(archive.by_index_stored_slice(2), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc32_mismatch() -> ZipResult<()> {
+        use crate::result::ZipError;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "file.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"contents")?;
+        let mut bytes = writer.finish()?.into_inner();
+        let data_start = bytes
+            .windows(8)
+            .position(|window| window == b"contents")
+            .unwrap();
+        bytes[data_start] = b'C';
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+
+        let err = archive
+            .by_index(0)?
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let Some(ZipError::Crc32Mismatch {
+            expected,
+            actual,
+            entry,
+        }) = err.get_ref().and_then(|inner| inner.downcast_ref())
+        else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert_eq!(*expected, crc32fast::hash(b"contents"));
+        assert_eq!(*actual, crc32fast::hash(b"Contents"));
+        assert_eq!(entry, "file.txt");
+        assert!(err.to_string().starts_with("Invalid checksum for file.txt"));
+
+        let mut contents = Vec::new();
+        archive
+            .by_index_no_crc_check(0)?
+            .read_to_end(&mut contents)?;
+        assert_eq!(contents, b"Contents");
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_mismatch() -> ZipResult<()> {
+        use super::Config;
+        use crate::result::ZipError;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "file.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"contents")?;
+        let mut bytes = writer.finish()?.into_inner();
+        // Declare one more byte than there is in the central directory
+        let central_start = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        bytes[central_start + 24] += 1;
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        let err = archive
+            .by_index(0)?
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        let Some(ZipError::SizeMismatch {
+            expected,
+            actual,
+            entry,
+        }) = err.get_ref().and_then(|inner| inner.downcast_ref())
+        else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert_eq!((*expected, *actual), (9, 8));
+        assert_eq!(entry, "file.txt");
+
+        let config = Config {
+            verify_sizes: false,
+            ..Default::default()
+        };
+        let mut archive = ZipArchive::with_config(config, Cursor::new(bytes))?;
+        let mut contents = Vec::new();
+        archive.by_index(0)?.read_to_end(&mut contents)?;
+        assert_eq!(contents, b"contents");
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_parallel() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().unix_permissions(0o755);
+        writer.add_directory("read_only/", options.unix_permissions(0o555))?;
+        for i in 0..50 {
+            writer.start_file(format!("dir{}/file{i}.txt", i % 5), options)?;
+            writer.write_all(i.to_string().repeat(1000).as_bytes())?;
+            writer.start_file(format!("read_only/file{i}.txt"), options)?;
+        }
+        let bytes = writer.finish()?.into_inner();
+        let archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+
+        let tempdir = TempDir::new("test_extract_parallel")?;
+        archive.extract_parallel(tempdir.path(), 4)?;
+        for i in 0..50 {
+            assert_eq!(
+                std::fs::read_to_string(tempdir.path().join(format!("dir{}/file{i}.txt", i % 5)))?,
+                i.to_string().repeat(1000)
+            );
+            assert!(tempdir
+                .path()
+                .join(format!("read_only/file{i}.txt"))
+                .exists());
+        }
+
+        let path = tempdir.path().join("archive.zip");
+        std::fs::write(&path, bytes)?;
+        let archive = ZipArchive::new(std::fs::File::open(&path)?)?;
+        let outdir = tempdir.path().join("from_file");
+        archive.extract_parallel_with(&outdir, 3, || std::fs::File::open(&path))?;
+        assert_eq!(
+            std::fs::read_to_string(outdir.join("dir4/file49.txt"))?,
+            "49".repeat(1000)
+        );
+        #[cfg(unix)]
+        for dir in [tempdir.path().join("read_only"), outdir.join("read_only")] {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&dir)?.permissions().mode() & 0o777, 0o555);
+            // Let the temporary directory be cleaned up
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_total_sizes() -> ZipResult<()> {
+        let archive = ZipArchive::new(Cursor::new(
+            include_bytes!("../tests/data/data_descriptor.zip").to_vec(),
+        ))?;
+        assert_eq!(archive.uncompressed_size(), 12);
+        assert_eq!(archive.compressed_size(), 14);
+        assert_eq!(archive.decompressed_size(), None);
+
+        let archive = ZipArchive::new(Cursor::new(
+            include_bytes!("../tests/data/files_and_dirs.zip").to_vec(),
+        ))?;
+        assert_eq!(archive.uncompressed_size(), 18);
+        assert_eq!(archive.compressed_size(), 18);
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_volume() -> ZipResult<()> {
+        use super::MultiVolumeReader;
+
+        let volumes = MultiVolumeReader::open("tests/data/split/split.zip", "tests/data/split")?;
+        assert_eq!(volumes.volume_count(), 2);
+        let mut archive = ZipArchive::new_multi_volume(volumes)?;
+        assert_eq!(archive.len(), 3);
+        let mut content = String::new();
+        archive.by_name("small.txt")?.read_to_string(&mut content)?;
+        assert_eq!(content, "small file\n");
+
+        // Spans both volumes; reading to the end checks the CRC-32
+        let mut big = Vec::new();
+        archive.by_name("big.bin")?.read_to_end(&mut big)?;
+        assert_eq!(big.len(), 100000);
+
+        // Stored on the second volume, at an offset relative to it
+        let mut tail = archive.by_name("tail.txt")?;
+        assert!(tail.header_start() > 65536);
+        content.clear();
+        tail.read_to_string(&mut content)?;
+        assert_eq!(content, "tail file\n");
+
+        // The split archive can't be read as a single volume
+        assert!(ZipArchive::new(std::fs::File::open("tests/data/split/split.zip")?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_chunks() -> ZipResult<()> {
+        let content = b"chunk".repeat(20_000);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("data.txt", SimpleFileOptions::default())?;
+        writer.write_all(&content)?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut chunks = Vec::new();
+        archive.by_name("data.txt")?.read_chunks(|chunk| {
+            chunks.push(chunk.to_vec());
+            Ok(())
+        })?;
+        assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+        assert_eq!(chunks.concat(), content);
+
+        let error = archive
+            .by_name("data.txt")?
+            .read_chunks(|_| Err(std::io::Error::new(std::io::ErrorKind::Other, "stop")))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "stop");
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_data_range() -> ZipResult<()> {
+        use crate::write::FullFileOptions;
+
+        let mut options = FullFileOptions::default()
+            .compression_method(Stored)
+            .with_alignment(512);
+        options.add_extra_data(0xbeef, vec![1; 10].into_boxed_slice(), false)?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("first.txt", options.clone())?;
+        writer.write_all(b"first")?;
+        writer.start_file("second.txt", options)?;
+        writer.write_all(b"second")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let range = archive.entry_data_range("second.txt")?;
+        assert_eq!(range.start % 512, 0);
+        let bytes = archive.into_inner().into_inner();
+        assert_eq!(&bytes[range.start as usize..range.end as usize], b"second");
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert!(matches!(
+            archive.entry_data_range("missing.txt"),
+            Err(crate::result::ZipError::FileNotFound)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_header_mismatch() -> ZipResult<()> {
+        use crate::result::ZipError;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "a.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"content")?;
+        writer.start_file(
+            "large.txt",
+            SimpleFileOptions::default()
+                .compression_method(Stored)
+                .large_file(true),
+        )?;
+        writer.write_all(b"large")?;
+        let bytes = writer.finish()?.into_inner();
+        let mut archive = ZipArchive::new_strict(Cursor::new(bytes.clone()))?;
+        let mut content = String::new();
+        archive.by_name("a.txt")?.read_to_string(&mut content)?;
+        archive.by_name("large.txt")?.read_to_string(&mut content)?;
+        assert_eq!(content, "contentlarge");
+        let mut archive = ZipArchive::new_strict(Cursor::new(
+            include_bytes!("../tests/data/data_descriptor.zip").to_vec(),
+        ))?;
+        archive.by_index_raw(0)?;
+
+        for (offset, value, field) in [(30, b'b', "name"), (18, 6, "compressed size")] {
+            let mut tampered = bytes.clone();
+            tampered[offset] = value;
+            let mut archive = ZipArchive::new(Cursor::new(tampered.clone()))?;
+            archive.by_name("a.txt")?;
+            let mut archive = ZipArchive::new_strict(Cursor::new(tampered))?;
+            match archive.by_name("a.txt") {
+                Err(ZipError::HeaderMismatch {
+                    field: found,
+                    entry,
+                }) => {
+                    assert_eq!(found, field);
+                    assert_eq!(entry, "a.txt");
+                }
+                _ => panic!("expected a header mismatch for the {field}"),
+            }
+            assert!(archive.by_name_raw("a.txt").is_err());
+            assert!(archive.by_name("large.txt").is_ok());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_path_extra_field() -> ZipResult<()> {
+        let mut archive = ZipArchive::new(Cursor::new(
+            include_bytes!("../tests/data/unicode_path_extra.zip").to_vec(),
+        ))?;
+        // The header holds the CP437 name, and the extra field the UTF-8 one
+        let file = archive.by_index(0)?;
+        assert_eq!(file.name(), "café.txt");
+        assert_eq!(file.name_raw(), b"caf\x82.txt");
+        drop(file);
+        // The extra field's checksum is for a different name, so it's ignored
+        assert_eq!(archive.by_index(1)?.name(), "old.txt");
+        Ok(())
+    }
+
+    #[test]
+    fn test_strong_encryption_detected() -> ZipResult<()> {
+        use crate::result::ZipError;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "a.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"hello")?;
+        let bytes = writer.finish()?.into_inner();
+        let central_start = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+
+        for (flags, message) in [
+            (1 | 1 << 6, "the file data is encrypted"),
+            (1 | 1 << 6 | 1 << 13, "the central directory is encrypted"),
+        ] {
+            let mut patched = bytes.clone();
+            patched[central_start + 8..central_start + 10]
+                .copy_from_slice(&(flags as u16).to_le_bytes());
+            match ZipArchive::new(Cursor::new(patched)) {
+                Err(ZipError::StrongEncryptionUnsupported(found)) => assert_eq!(found, message),
+                other => panic!("expected a strong encryption error, got {:?}", other.err()),
+            }
+        }
+
+        let mut patched = bytes.clone();
+        patched[central_start..central_start + 4].copy_from_slice(b"PK\x06\x08");
+        assert!(matches!(
+            ZipArchive::new(Cursor::new(patched)),
+            Err(ZipError::StrongEncryptionUnsupported(_))
+        ));
+
+        // Streaming readers check the local header
+        let mut patched = bytes;
+        patched[6..8].copy_from_slice(&(1u16 | 1 << 6).to_le_bytes());
+        assert!(matches!(
+            crate::read::read_zipfile_from_stream(&mut &patched[..]),
+            Err(ZipError::StrongEncryptionUnsupported(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_computed_crc32() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("a.txt", SimpleFileOptions::default())?;
+        writer.write_all(&b"hello world\n".repeat(100))?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+
+        let mut file = archive.by_index(0)?;
+        assert_eq!(file.computed_crc32(), None);
+        let mut buf = [0; 10];
+        file.read_exact(&mut buf)?;
+        assert_eq!(file.computed_crc32(), None);
+        std::io::copy(&mut file, &mut std::io::sink())?;
+        assert_eq!(file.computed_crc32(), Some(file.crc32()));
+        assert_eq!(file.crc32(), crc32fast::hash(&b"hello world\n".repeat(100)));
+        drop(file);
+
+        let mut raw = archive.by_index_raw(0)?;
+        std::io::copy(&mut raw, &mut std::io::sink())?;
+        assert_eq!(raw.computed_crc32(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_layout() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file("a.txt", options)?;
+        writer.write_all(b"hello")?;
+        writer.start_file("b.txt", options.with_deprecated_encryption(b"password"))?;
+        writer.write_all(b"world")?;
+        let bytes = writer.finish()?.into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+
+        let layout = archive.entry_layout()?;
+        assert_eq!(layout.len(), 2);
+        assert_eq!(&*layout[0].name, "a.txt");
+        assert_eq!(layout[0].header_start, 0);
+        assert_eq!(layout[0].data_start, 30 + 5);
+        assert_eq!(layout[0].compressed_size, 5);
+        assert_eq!(layout[0].compression_method, Stored);
+        assert_eq!(layout[0].flags, 0);
+        assert_eq!(&bytes[35..40], b"hello");
+        assert_eq!(layout[1].header_start, 40);
+        assert_eq!(layout[1].flags & 1, 1);
+        assert_eq!(
+            archive.entry_data_range("b.txt")?,
+            layout[1].data_start..layout[1].data_start + layout[1].compressed_size
+        );
+        for entry in &layout {
+            let start = entry.central_header_start as usize;
+            assert_eq!(&bytes[start..start + 4], b"PK\x01\x02");
+        }
+        assert!(layout[0].central_header_start < layout[1].central_header_start);
+        Ok(())
+    }
+
+    #[cfg(feature = "legacy")]
+    #[test]
+    fn test_implode() -> ZipResult<()> {
+        let mut line = b"Imploding was the strongest method in PKZIP 1.1. ".repeat(3);
+        line.extend(0..=255u8);
+        line.push(b'\n');
+        let text = line.repeat(12);
+        let mut eight_k_plain = text[..2000].to_vec();
+        eight_k_plain.extend([0; 2000]);
+        eight_k_plain.extend(&text[..100]);
+        let mut four_k_literal_tree = b"aaaaaaaaaaaaaaaaaaaaaaaaab".repeat(40);
+        four_k_literal_tree.extend(b"tail");
+
+        let mut archive =
+            ZipArchive::new(Cursor::new(include_bytes!("../tests/data/implode.zip")))?;
+        for (name, expected) in [
+            ("eight_k_literal_tree.txt", &text),
+            ("four_k_plain.txt", &text),
+            ("eight_k_plain.txt", &eight_k_plain),
+            ("four_k_literal_tree.txt", &four_k_literal_tree),
+        ] {
+            let mut file = archive.by_name(name)?;
+            assert_eq!(file.compression(), crate::CompressionMethod::Implode);
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)?;
+            assert_eq!(&content, expected, "{name}");
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "legacy")]
+    #[test]
+    fn test_shrink_and_reduce() -> ZipResult<()> {
+        use crate::CompressionMethod::{Reduce, Shrink};
+
+        let mut line = b"Shrinking and reducing came before imploding in PKZIP. ".repeat(3);
+        line.extend(0..=255u8);
+        line.push(b'\n');
+        let text = line.repeat(12);
+        let mut with_zeros = text[..2000].to_vec();
+        with_zeros.extend([0; 2000]);
+        with_zeros.extend(&text[..100]);
+        let mut with_dle = b"aaaaaaaaaaaaaaaaaaaaaaaaab".repeat(40);
+        with_dle.extend(b"\x90tail\x90");
+
+        let mut archive = ZipArchive::new(Cursor::new(include_bytes!(
+            "../tests/data/shrink_reduce.zip"
+        )))?;
+        for (name, method, expected) in [
+            ("shrink.txt", Shrink, &text),
+            ("reduce_1.txt", Reduce(1), &text),
+            ("reduce_2.txt", Reduce(2), &with_zeros),
+            ("reduce_3.txt", Reduce(3), &text),
+            ("reduce_4.txt", Reduce(4), &with_dle),
+        ] {
+            let mut file = archive.by_name(name)?;
+            assert_eq!(file.compression(), method);
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)?;
+            assert_eq!(&content, expected, "{name}");
+        }
+
+        // Enough codes to fill the table twice, so that it's partially cleared. Reading checks
+        // the CRC-32.
+        let mut content = Vec::new();
+        archive
+            .by_name("shrink_partial_clear.txt")?
+            .read_to_end(&mut content)?;
+        assert_eq!(content.len(), 40000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> ZipResult<()> {
+        use crate::result::ZipError;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        for name in ["first.txt", "corrupt.txt", "last.txt"] {
+            writer.start_file(name, options)?;
+            writer.write_all(b"contents")?;
+        }
+        writer.start_file(
+            "encrypted.txt",
+            options.with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(b"secret")?;
+        let mut bytes = writer.finish()?.into_inner();
+        let data_start = 30 + 9 + 8 + 30 + 11;
+        bytes[data_start] ^= 1;
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let results = archive.verify()?;
+        let outcomes: Vec<_> = results
+            .iter()
+            .map(|result| (&*result.name, result.is_ok()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("first.txt", true),
+                ("corrupt.txt", false),
+                ("last.txt", true),
+                ("encrypted.txt", false)
+            ]
+        );
+        assert!(matches!(results[1].result, Err(ZipError::Io(_))));
+        assert!(matches!(
+            results[3].result,
+            Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_invalid_entry_names() -> ZipResult<()> {
+        use super::Config;
+        use crate::result::ZipError;
+        use crate::types::MAX_PATH_LEN;
+
+        let archive_with = |name: &str| -> ZipResult<Vec<u8>> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.start_file("dir\\file.txt", SimpleFileOptions::default())?;
+            writer.write_all(b"backslash")?;
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(b"contents")?;
+            Ok(writer.finish()?.into_inner())
+        };
+        let long_name = "a/".repeat(MAX_PATH_LEN / 2) + "file.txt";
+        let tempdir = TempDir::new("test_extract_invalid_entry_names")?;
+        for (name, reason) in [
+            ("bell\x07.txt", "it contains control characters"),
+            (&*long_name, "it's too long"),
+        ] {
+            let mut archive = ZipArchive::new(Cursor::new(archive_with(name)?))?;
+            match archive.extract(tempdir.path()) {
+                Err(ZipError::InvalidEntryName {
+                    reason: actual,
+                    entry,
+                }) => {
+                    assert_eq!(actual, reason);
+                    assert_eq!(entry, name);
+                }
+                result => panic!("unexpected result: {result:?}"),
+            }
+        }
+        assert_eq!(
+            std::fs::read_to_string(tempdir.path().join("dir/file.txt"))?,
+            "backslash"
+        );
+
+        let config = Config {
+            sanitize_entry_names: true,
+            ..Default::default()
+        };
+        let bytes = archive_with("bell\x07.txt")?;
+        let mut archive = ZipArchive::with_config(config, Cursor::new(bytes))?;
+        archive.extract(tempdir.path())?;
+        assert_eq!(
+            std::fs::read_to_string(tempdir.path().join("bell.txt"))?,
+            "contents"
+        );
+
+        let bytes = archive_with("..\\escape.txt")?;
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert!(matches!(
+            archive.extract(tempdir.path()),
+            Err(ZipError::InvalidArchive("Invalid file path"))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_windows_paths() -> ZipResult<()> {
+        use crate::result::ZipError;
+
+        let tempdir = TempDir::new("test_extract_windows_paths")?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["C:\\evil.txt", "d:relative.txt", "C:/dir\\..\\forward.txt"] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(name.as_bytes())?;
+        }
+        let bytes = writer.finish()?.into_inner();
+        ZipArchive::new(Cursor::new(bytes))?.extract(tempdir.path())?;
+        for (path, name) in [
+            ("evil.txt", "C:\\evil.txt"),
+            ("relative.txt", "d:relative.txt"),
+            ("forward.txt", "C:/dir\\..\\forward.txt"),
+        ] {
+            assert_eq!(std::fs::read_to_string(tempdir.path().join(path))?, name);
+        }
+
+        for name in [
+            "\\\\server\\share\\unc.txt",
+            "\\\\?\\C:\\verbatim.txt",
+            "\\\\.\\device.txt",
+            "//server/share/forward.txt",
+        ] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.start_file(name, SimpleFileOptions::default())?;
+            let bytes = writer.finish()?.into_inner();
+            let result = ZipArchive::new(Cursor::new(bytes))?.extract(tempdir.path());
+            assert!(
+                matches!(result, Err(ZipError::InvalidEntryName { .. })),
+                "{name}: {result:?}"
+            );
+        }
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("C:\\..\\escape.txt", SimpleFileOptions::default())?;
+        let bytes = writer.finish()?.into_inner();
+        assert!(matches!(
+            ZipArchive::new(Cursor::new(bytes))?.extract(tempdir.path()),
+            Err(ZipError::InvalidArchive("Invalid file path"))
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn test_read_options() -> ZipResult<()> {
+        use super::ReadOptions;
+
+        let mut archive = ZipArchive::new(Cursor::new(include_bytes!("../tests/data/xz.zip")))?;
+
+        let options = ReadOptions::default().buffer_size(4);
+        let mut contents = Vec::new();
+        archive
+            .by_name_with_options("hello.txt", options)?
+            .read_to_end(&mut contents)?;
+        assert_eq!(contents, b"Hello world\n");
+
+        let options = options.max_dictionary_size(1024);
+        let err = archive
+            .by_index_with_options(0, options)?
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_buffer_size() -> ZipResult<()> {
+        use super::ReadOptions;
+
+        let options = ReadOptions::default().buffer_size(0);
+        assert_eq!(options, ReadOptions::default().buffer_size(1));
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"contents")?;
+        let mut archive = writer.finish_into_readable()?;
+        let mut contents = Vec::new();
+        archive
+            .by_index_with_options(0, options)?
+            .read_to_end(&mut contents)?;
+        assert_eq!(contents, b"contents");
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_length_comment_with_fake_end_records() -> ZipResult<()> {
+        // The comment ends with the end of central directory record of an empty archive, and
+        // has more signatures scattered through it
+        let mut comment = b"PK\x05\x06 not a record ".repeat(2900);
+        comment.resize(u16::MAX as usize - 22, b'.');
+        comment.extend_from_slice(b"PK\x05\x06");
+        comment.extend_from_slice(&[0; 18]);
+        assert_eq!(comment.len(), u16::MAX as usize);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file.txt", SimpleFileOptions::default())?;
+        writer.write_all(&[b'x'; 100_000])?;
+        writer.set_raw_comment(comment.clone().into_boxed_slice());
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.comment(), comment);
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive.by_index(0)?.size(), 100_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_self_extracting_archive() -> ZipResult<()> {
+        for large_file in [false, true] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            let options = SimpleFileOptions::default().large_file(large_file);
+            writer.start_file("dir/file.txt", options)?;
+            writer.write_all(b"extracted")?;
+            writer.start_file("other.txt", options)?;
+            writer.write_all(b"other")?;
+            writer.set_comment("comment");
+            let zip = writer.finish()?.into_inner();
+
+            // The stub has local header signatures of its own
+            let mut sfx = b"MZ\x90\0 PK\x03\x04 stub ".repeat(100);
+            let stub_len = sfx.len() as u64;
+            sfx.extend_from_slice(&zip);
+            let mut archive = ZipArchive::new(Cursor::new(sfx))?;
+            assert_eq!(archive.offset(), stub_len);
+            assert_eq!(archive.prefix_len(), stub_len);
+            assert_eq!(archive.comment(), b"comment");
+            let mut contents = String::new();
+            archive
+                .by_name("other.txt")?
+                .read_to_string(&mut contents)?;
+            assert_eq!(contents, "other");
+
+            let tempdir = TempDir::new("test_self_extracting_archive")?;
+            archive.extract(tempdir.path())?;
+            assert_eq!(
+                std::fs::read_to_string(tempdir.path().join("dir/file.txt"))?,
+                "extracted"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefix_len() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"contents")?;
+        let zip = writer.finish()?.into_inner();
+        let mut sfx = b"#!/bin/sh\nexit 0\n".to_vec();
+        sfx.extend_from_slice(&zip);
+
+        let archive = ZipArchive::new(Cursor::new(sfx))?;
+        assert_eq!(archive.prefix_len(), 17);
+        let mut reader = archive.into_inner_at_zip_start()?;
+        let mut stripped = Vec::new();
+        reader.read_to_end(&mut stripped)?;
+        assert_eq!(stripped, zip);
+
+        // Offsets that already account for the prefix, as after `zip -A`
+        let mut stub = Cursor::new(b"stub".to_vec());
+        stub.set_position(4);
+        let mut writer = ZipWriter::new(stub);
+        writer.start_file("file.txt", SimpleFileOptions::default())?;
+        let archive = ZipArchive::new(Cursor::new(writer.finish()?.into_inner()))?;
+        assert_eq!(archive.offset(), 0);
+        assert_eq!(archive.prefix_len(), 4);
+
+        let archive = ZipArchive::new(Cursor::new(
+            ZipWriter::new(Cursor::new(Vec::new()))
+                .finish()?
+                .into_inner(),
+        ))?;
+        assert_eq!(archive.prefix_len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_method_histogram() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file("first.txt", options)?;
+        writer.write_all(&[b'a'; 100])?;
+        writer.add_directory("dir", options)?;
+        #[cfg(feature = "_deflate-any")]
+        {
+            writer.start_file(
+                "deflated.txt",
+                options.compression_method(crate::CompressionMethod::Deflated),
+            )?;
+            writer.write_all(&[b'a'; 100])?;
+        }
+        let archive = writer.finish_into_readable()?;
+
+        let histogram = archive.method_histogram();
+        assert_eq!(histogram[&Stored], 2);
+        #[cfg(feature = "_deflate-any")]
+        {
+            assert_eq!(histogram[&crate::CompressionMethod::Deflated], 1);
+            assert!(archive.compression_ratio() < 0.6);
+        }
+        #[cfg(not(feature = "_deflate-any"))]
+        assert_eq!(archive.compression_ratio(), 1.0);
+
+        let archive = ZipWriter::new(Cursor::new(Vec::new())).finish_into_readable()?;
+        assert!(archive.method_histogram().is_empty());
+        assert_eq!(archive.compression_ratio(), 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_concatenated_reader() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("first.log", options.compression_method(Stored))?;
+        writer.write_all(b"one\ntwo\n")?;
+        writer.add_directory("logs", options)?;
+        writer.start_file("logs/empty.log", options)?;
+        writer.start_file("logs/second.log", options)?;
+        writer.write_all(&b"three\n".repeat(1000))?;
+        #[cfg(feature = "xz")]
+        {
+            let mut xz = ZipArchive::new(Cursor::new(include_bytes!("../tests/data/xz.zip")))?;
+            writer.raw_copy_file_rename(xz.by_name("hello.txt")?, "logs/xz.log")?;
+        }
+        let archive = writer.finish_into_readable()?;
+
+        let mut expected = b"> first.log\none\ntwo\n> logs/empty.log\n> logs/second.log\n".to_vec();
+        expected.extend_from_slice(&b"three\n".repeat(1000));
+        #[cfg(feature = "xz")]
+        expected.extend_from_slice(b"> logs/xz.log\nHello world\n");
+        let mut reader =
+            archive.into_concatenated_reader(|file| format!("> {}\n", file.name()).into_bytes());
+        let mut contents = Vec::new();
+        let mut buf = [0; 100];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => break,
+                count => contents.extend_from_slice(&buf[..count]),
+            }
+        }
+        assert_eq!(contents, expected);
+
+        // The archive can be taken back partway through a file
+        let mut reader = reader
+            .into_inner()?
+            .into_concatenated_reader(|_| Vec::new());
+        reader.read_exact(&mut buf[..10])?;
+        assert_eq!(&buf[..10], b"one\ntwo\nth");
+        assert_eq!(
+            reader.into_inner()?.len(),
+            if cfg!(feature = "xz") { 5 } else { 4 }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_by_central_offset() -> ZipResult<()> {
+        use crate::result::ZipError;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["first.txt", "second.txt"] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(name.as_bytes())?;
+        }
+        let mut archive = ZipArchive::new(Cursor::new(writer.finish()?.into_inner()))?;
+        for layout in archive.entry_layout()? {
+            for offset in [layout.central_header_start, layout.header_start] {
+                let mut contents = String::new();
+                archive
+                    .by_central_offset(offset)?
+                    .read_to_string(&mut contents)?;
+                assert_eq!(contents, &*layout.name);
+            }
+        }
+        assert!(matches!(
+            archive.by_central_offset(1),
+            Err(ZipError::FileNotFound)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_names() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [
+            ("dup.txt", "first"),
+            ("other", "other"),
+            ("tmp.txt", "second"),
+        ] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(contents.as_bytes())?;
+        }
+        let mut bytes = writer.finish()?.into_inner();
+        // Rename the last entry in both its local header and its central directory header
+        for start in (0..bytes.len() - 7).rev() {
+            if &bytes[start..start + 7] == b"tmp.txt" {
+                bytes[start..start + 7].copy_from_slice(b"dup.txt");
+            }
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.len(), 3);
+        assert_eq!(archive.all_indices_for_name("dup.txt"), vec![0, 2]);
+        assert_eq!(archive.all_indices_for_name("other"), vec![1]);
+        assert!(archive.all_indices_for_name("missing").is_empty());
+        assert_eq!(archive.index_for_name("dup.txt"), Some(2));
+        let mut contents = String::new();
+        archive.by_name("dup.txt")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "second");
+        contents.clear();
+        archive.by_index(0)?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "first");
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_macos_metadata() -> ZipResult<()> {
+        use super::ExtractOptions;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in [
+            "photo.jpg",
+            "__MACOSX/._photo.jpg",
+            "docs/._notes.txt",
+            "docs/notes.txt",
+            "docs/.hidden",
+        ] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(name.as_bytes())?;
+        }
+        let mut archive = ZipArchive::new(Cursor::new(writer.finish()?.into_inner()))?;
+        let metadata: Vec<bool> = (0..archive.len())
+            .map(|i| archive.by_index(i).map(|file| file.is_macos_metadata()))
+            .collect::<ZipResult<_>>()?;
+        assert_eq!(metadata, [false, true, true, false, false]);
+
+        let dir = TempDir::new("test_skip_macos_metadata")?;
+        archive.extract_with_options(
+            dir.path(),
+            ExtractOptions::default().skip_macos_metadata(true),
+        )?;
+        assert!(dir.path().join("photo.jpg").is_file());
+        assert!(dir.path().join("docs/notes.txt").is_file());
+        assert!(dir.path().join("docs/.hidden").is_file());
+        assert!(!dir.path().join("__MACOSX").exists());
+        assert!(!dir.path().join("docs/._notes.txt").exists());
+
+        let dir = TempDir::new("test_skip_macos_metadata")?;
+        archive.extract_with_options(dir.path(), ExtractOptions::default())?;
+        assert!(dir.path().join("__MACOSX/._photo.jpg").is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_entries_without_zip64_record() -> ZipResult<()> {
+        use crate::spec;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..spec::ZIP64_ENTRY_THR {
+            writer.start_file(i.to_string(), SimpleFileOptions::default())?;
+            writer.write_all(&[i as u8])?;
+        }
+        let mut bytes = writer.finish()?.into_inner();
+        // Remove the Zip64 end record and locator, as older versions of this crate didn't write
+        // them for exactly 65,535 entries
+        let end = bytes.len() - 22;
+        let record = bytes.windows(4).rposition(|w| w == b"PK\x06\x06").unwrap();
+        bytes.drain(record..end);
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.len(), spec::ZIP64_ENTRY_THR);
+        let mut content = Vec::new();
+        archive.by_name("65534")?.read_to_end(&mut content)?;
+        assert_eq!(content, [65534u16 as u8]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zip64_end_record_is_authoritative() -> ZipResult<()> {
+        use crate::result::ZipError;
+        use crate::write::Zip64Mode;
+
+        let write_archive = |mode| -> ZipResult<Vec<u8>> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.set_zip64_mode(mode);
+            for name in ["first.txt", "second.txt"] {
+                writer.start_file(name, SimpleFileOptions::default())?;
+                writer.write_all(name.as_bytes())?;
+            }
+            Ok(writer.finish()?.into_inner())
+        };
+        // Replace the counts, size and offset in the classic end of central directory record
+        let set_sentinels = |bytes: &mut Vec<u8>| {
+            let end = bytes.len() - 22;
+            bytes[end + 8..end + 12].copy_from_slice(&[0xFF; 4]);
+            bytes[end + 12..end + 20].copy_from_slice(&[0xFF; 8]);
+        };
+
+        let mut zip64 = write_archive(Zip64Mode::Always)?;
+        set_sentinels(&mut zip64);
+        let archive = ZipArchive::new(Cursor::new(zip64.clone()))?;
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.name_for_index(1), Some("second.txt"));
+
+        // A directory that would overlap the Zip64 end record
+        let record = zip64.windows(4).rposition(|w| w == b"PK\x06\x06").unwrap();
+        zip64[record + 40..record + 48].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            ZipArchive::new(Cursor::new(zip64)),
+            Err(ZipError::InvalidArchive(_))
+        ));
+
+        let mut zip32 = write_archive(Zip64Mode::Auto)?;
+        set_sentinels(&mut zip32);
+        assert!(matches!(
+            ZipArchive::new(Cursor::new(zip32)),
+            Err(ZipError::InvalidArchive(super::MISSING_ZIP64_RECORD))
+        ));
+        Ok(())
+    }
 }
@@ -0,0 +1,46 @@
+//! Bit-level input for the legacy compression methods
+
+use std::io::{Read, Result};
+
+/// Reads bits from the least significant end of each byte, as shrink, reduce and implode all
+/// store them.
+#[derive(Debug)]
+pub(crate) struct BitReader<R> {
+    inner: R,
+    buffer: u32,
+    count: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    pub fn new(inner: R) -> Self {
+        BitReader {
+            inner,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Reads up to 24 bits.
+    pub fn bits(&mut self, count: u32) -> Result<u32> {
+        while self.count < count {
+            let mut byte = [0u8];
+            self.inner.read_exact(&mut byte)?;
+            self.buffer |= (byte[0] as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << count) - 1);
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    /// Gets a mutable reference to the underlying reader, for reading whole bytes before any bits
+    /// have been read.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
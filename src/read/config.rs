@@ -1,9 +1,268 @@
+use crate::cp437::FromCp437;
+
 /// Configuration for reading ZIP archives.
-#[repr(transparent)]
-#[derive(Debug, Default, Clone, Copy)]
+///
+/// ```
+/// # use zip::read::{ArchiveOffset, Config};
+/// let config = Config::default()
+///     .archive_offset(ArchiveOffset::FromCentralDirectory)
+///     .verify_local_headers(true);
+/// ```
+#[derive(Debug, Clone, Copy)]
 pub struct Config {
-    /// An offset into the reader to use to find the start of the archive.
-    pub archive_offset: ArchiveOffset,
+    pub(crate) archive_offset: ArchiveOffset,
+    pub(crate) extract_unix_permissions: bool,
+    pub(crate) limits: ArchiveLimits,
+    pub(crate) decompression_limits: DecompressionLimits,
+    pub(crate) name_encoding: NameEncoding,
+    pub(crate) verify_sizes: bool,
+    pub(crate) verify_local_headers: bool,
+    pub(crate) sanitize_entry_names: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            archive_offset: ArchiveOffset::default(),
+            extract_unix_permissions: true,
+            limits: ArchiveLimits::default(),
+            decompression_limits: DecompressionLimits::default(),
+            name_encoding: NameEncoding::default(),
+            verify_sizes: true,
+            verify_local_headers: false,
+            sanitize_entry_names: false,
+        }
+    }
+}
+
+impl Config {
+    /// Sets an offset into the reader to use to find the start of the archive.
+    #[must_use]
+    pub const fn archive_offset(mut self, offset: ArchiveOffset) -> Self {
+        self.archive_offset = offset;
+        self
+    }
+
+    /// Sets whether [`ZipArchive::extract`](crate::ZipArchive::extract) should apply the Unix
+    /// permissions stored for each file and directory. Defaults to `true`. This has no effect on
+    /// other platforms.
+    #[must_use]
+    pub const fn extract_unix_permissions(mut self, extract: bool) -> Self {
+        self.extract_unix_permissions = extract;
+        self
+    }
+
+    /// Sets limits on the central directory, checked while it's parsed. Unbounded by default.
+    #[must_use]
+    pub const fn limits(mut self, limits: ArchiveLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets guards against entries that decompress to far more data than expected. Disabled by
+    /// default.
+    #[must_use]
+    pub const fn decompression_limits(mut self, limits: DecompressionLimits) -> Self {
+        self.decompression_limits = limits;
+        self
+    }
+
+    /// Sets how to decode the names and comments of entries without the UTF-8 flag. Defaults to
+    /// [`NameEncoding::Cp437`], as the specification requires.
+    #[must_use]
+    pub const fn name_encoding(mut self, encoding: NameEncoding) -> Self {
+        self.name_encoding = encoding;
+        self
+    }
+
+    /// Sets whether reading an entry checks that it decompresses to the uncompressed size
+    /// recorded in the archive, failing with
+    /// [`ZipError::SizeMismatch`](crate::result::ZipError::SizeMismatch) otherwise. Defaults to
+    /// `true`.
+    #[must_use]
+    pub const fn verify_sizes(mut self, verify: bool) -> Self {
+        self.verify_sizes = verify;
+        self
+    }
+
+    /// Sets whether opening an entry checks that its local header has the same name, compression
+    /// method and sizes as its central directory record, failing with
+    /// [`ZipError::HeaderMismatch`](crate::result::ZipError::HeaderMismatch) otherwise. This
+    /// reads each local header again, so it defaults to `false`.
+    #[must_use]
+    pub const fn verify_local_headers(mut self, verify: bool) -> Self {
+        self.verify_local_headers = verify;
+        self
+    }
+
+    /// Sets whether [`ZipArchive::extract`](crate::ZipArchive::extract) fixes entry names that
+    /// contain control characters or are longer than the platform allows, by removing the
+    /// control characters and truncating the name. Defaults to `false`, in which case those
+    /// entries fail with
+    /// [`ZipError::InvalidEntryName`](crate::result::ZipError::InvalidEntryName).
+    #[must_use]
+    pub const fn sanitize_entry_names(mut self, sanitize: bool) -> Self {
+        self.sanitize_entry_names = sanitize;
+        self
+    }
+}
+
+/// Limits for reading untrusted archives. An archive that exceeds any of them is rejected with
+/// [`ZipError::LimitExceeded`](crate::result::ZipError::LimitExceeded) before its entries are
+/// read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ArchiveLimits {
+    /// The maximum number of entries the central directory may declare.
+    pub max_entries: usize,
+    /// The maximum length in bytes of an entry's name.
+    pub max_name_len: usize,
+    /// The maximum length in bytes of an entry's extra field in the central directory.
+    pub max_extra_len: usize,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        ArchiveLimits {
+            max_entries: usize::MAX,
+            max_name_len: usize::MAX,
+            max_extra_len: usize::MAX,
+        }
+    }
+}
+
+/// Limits on decompressed data, to defend against zip bombs. Exceeding one of them fails with
+/// [`ZipError::DecompressionLimitExceeded`](crate::result::ZipError::DecompressionLimitExceeded).
+///
+/// ```
+/// # use zip::read::{Config, DecompressionLimits};
+/// let config = Config::default().decompression_limits(
+///     DecompressionLimits::default()
+///         .max_expansion_ratio(100)
+///         .max_total_bytes(1 << 30),
+/// );
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecompressionLimits {
+    pub(crate) max_expansion_ratio: Option<u64>,
+    pub(crate) max_total_bytes: Option<u64>,
+}
+
+impl DecompressionLimits {
+    /// Fails reading an entry once its decompressed data is more than `ratio` times its
+    /// compressed size. This applies to every entry read with decompression, not just during
+    /// extraction.
+    #[must_use]
+    pub const fn max_expansion_ratio(mut self, ratio: u64) -> Self {
+        self.max_expansion_ratio = Some(ratio);
+        self
+    }
+
+    /// Fails [`ZipArchive::extract`](crate::ZipArchive::extract) once it has written more than
+    /// `bytes` bytes of file contents in total.
+    #[must_use]
+    pub const fn max_total_bytes(mut self, bytes: u64) -> Self {
+        self.max_total_bytes = Some(bytes);
+        self
+    }
+}
+
+/// Options for reading a single entry with
+/// [`ZipArchive::by_index_with_options`](crate::ZipArchive::by_index_with_options) or
+/// [`ZipArchive::by_name_with_options`](crate::ZipArchive::by_name_with_options), to control the
+/// memory used to decompress it.
+///
+/// ```
+/// # use zip::read::ReadOptions;
+/// let options = ReadOptions::default()
+///     .buffer_size(512)
+///     .max_dictionary_size(1 << 20);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReadOptions {
+    pub(crate) buffer_size: Option<usize>,
+    pub(crate) max_dictionary_size: Option<u32>,
+    #[cfg(feature = "zstd")]
+    pub(crate) zstd_window_log_max: Option<u32>,
+}
+
+impl ReadOptions {
+    /// Sets the size in bytes of the buffer the compressed data is read into. Defaults to the
+    /// standard library's buffer size, currently 8 KiB. A size of 0 is raised to 1 byte.
+    #[must_use]
+    pub const fn buffer_size(mut self, bytes: usize) -> Self {
+        self.buffer_size = Some(if bytes == 0 { 1 } else { bytes });
+        self
+    }
+
+    /// Rejects XZ entries that declare an LZMA2 dictionary larger than `bytes`. Such entries fail
+    /// to read with [`std::io::ErrorKind::InvalidData`]. There's no limit by default.
+    ///
+    /// This only checks the declared size: the decoder still holds each decompressed XZ block
+    /// in memory, however small its dictionary. To bound the decompressed size, use
+    /// [`DecompressionLimits`].
+    #[must_use]
+    pub const fn max_dictionary_size(mut self, bytes: u32) -> Self {
+        self.max_dictionary_size = Some(bytes);
+        self
+    }
+
+    /// Accepts Zstandard entries with windows of up to 2^`window_log` bytes, such as those
+    /// written with a large window log through
+    /// [`FileOptions::zstd_options`](crate::write::FileOptions::zstd_options). The decoder needs
+    /// that much memory, so by default it keeps zstd's own limit of 2^27 bytes. Reading fails if
+    /// `window_log` is above 31 (30 on 32-bit targets).
+    #[must_use]
+    #[cfg(feature = "zstd")]
+    pub const fn zstd_window_log_max(mut self, window_log: u32) -> Self {
+        self.zstd_window_log_max = Some(window_log);
+        self
+    }
+}
+
+/// Options for [`ZipArchive::extract_with_options`](crate::ZipArchive::extract_with_options).
+///
+/// ```
+/// # use zip::read::ExtractOptions;
+/// let options = ExtractOptions::default().skip_macos_metadata(true);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExtractOptions {
+    pub(crate) skip_macos_metadata: bool,
+}
+
+impl ExtractOptions {
+    /// Whether to skip the `__MACOSX/` directory and the `._` AppleDouble files that macOS adds
+    /// to archives to hold resource forks and extended attributes, as reported by
+    /// [`ZipFile::is_macos_metadata`](crate::read::ZipFile::is_macos_metadata). Defaults to
+    /// `false`.
+    #[must_use]
+    pub const fn skip_macos_metadata(mut self, skip: bool) -> Self {
+        self.skip_macos_metadata = skip;
+        self
+    }
+}
+
+/// The encoding of the names and comments of entries that don't have the UTF-8 flag (general
+/// purpose bit 11) set. Entries with the flag are always decoded as UTF-8.
+#[derive(Debug, Default, Clone, Copy)]
+pub enum NameEncoding {
+    /// Decode as UTF-8, replacing invalid sequences with U+FFFD.
+    Utf8,
+    /// Decode as IBM code page 437, the encoding the specification prescribes.
+    #[default]
+    Cp437,
+    /// Decode with the given function, for encodings such as Shift-JIS or GBK.
+    Custom(fn(&[u8]) -> String),
+}
+
+impl NameEncoding {
+    pub(crate) fn decode(&self, raw: &[u8]) -> Box<str> {
+        match self {
+            NameEncoding::Utf8 => String::from_utf8_lossy(raw).into(),
+            NameEncoding::Cp437 => raw.from_cp437().into(),
+            NameEncoding::Custom(decode) => decode(raw).into_boxed_str(),
+        }
+    }
 }
 
 /// The offset of the start of the archive from the beginning of the reader.
@@ -0,0 +1,194 @@
+//! A small shell-style glob matcher for entry names.
+//!
+//! - `?` matches any character except `/`
+//! - `*` matches any sequence of characters except `/`
+//! - `**` matches any sequence of characters, including `/`; `**/` may also
+//!   match no directory at all
+//! - `[abc]`, `[a-z]` and `[!abc]` match one character (other than `/`) in or
+//!   out of a set
+//!
+//! Any other character, including an unclosed `[`, matches itself.
+
+pub(crate) fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let tokens = tokenize(&pattern);
+    let name: Vec<char> = name.chars().collect();
+    matches(&tokens, &name)
+}
+
+enum Token<'a> {
+    Char(char),
+    /// `?`
+    Any,
+    /// `[...]`: whether it's negated, and the characters between the brackets
+    Class(bool, &'a [char]),
+    /// `*`
+    Star,
+    /// `**`
+    DoubleStar,
+    /// `**/`, which matches nothing or anything ending with `/`
+    DoubleStarSlash,
+}
+
+impl Token<'_> {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            Token::Char(p) => p == c,
+            Token::Any => c != '/',
+            Token::Class(negated, body) => c != '/' && class_contains(body, c) != negated,
+            Token::Star | Token::DoubleStar | Token::DoubleStarSlash => false,
+        }
+    }
+}
+
+fn tokenize(mut pattern: &[char]) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    while let [c, rest @ ..] = pattern {
+        let (token, rest) = match (c, rest) {
+            ('*', ['*', '/', rest @ ..]) => (Token::DoubleStarSlash, rest),
+            ('*', ['*', rest @ ..]) => (Token::DoubleStar, rest),
+            ('*', _) => (Token::Star, rest),
+            ('?', _) => (Token::Any, rest),
+            ('[', _) => match parse_class(rest) {
+                Some((negated, body, rest)) => (Token::Class(negated, body), rest),
+                None => (Token::Char('['), rest),
+            },
+            _ => (Token::Char(*c), rest),
+        };
+        tokens.push(token);
+        pattern = rest;
+    }
+    tokens
+}
+
+/// Matches without recursion, in the manner of the usual two-pointer wildcard algorithm. On a
+/// mismatch, the last `*` takes one more character, as long as that isn't a `/`; otherwise the
+/// last `**` does, and any `*` after it starts over. Earlier stars never need to be revisited,
+/// so this takes at most quadratic time.
+fn matches(tokens: &[Token], name: &[char]) -> bool {
+    let (mut t, mut n) = (0, 0);
+    // Where to resume after the last `*` and the last `**`, as token and name indices
+    let mut star: Option<(usize, usize)> = None;
+    let mut double_star: Option<(usize, usize)> = None;
+    loop {
+        match tokens.get(t) {
+            Some(Token::Star) => {
+                star = Some((t + 1, n));
+                t += 1;
+                continue;
+            }
+            Some(Token::DoubleStar | Token::DoubleStarSlash) => {
+                double_star = Some((t, n));
+                star = None;
+                t += 1;
+                continue;
+            }
+            Some(token) if name.get(n).is_some_and(|&c| token.matches(c)) => {
+                t += 1;
+                n += 1;
+                continue;
+            }
+            None if n == name.len() => return true,
+            _ => {}
+        }
+        // Mismatch, so backtrack
+        if let Some((star_t, star_n)) = star {
+            if name.get(star_n).is_some_and(|&c| c != '/') {
+                star = Some((star_t, star_n + 1));
+                t = star_t;
+                n = star_n + 1;
+                continue;
+            }
+        }
+        let Some((double_star_t, double_star_n)) = double_star else {
+            return false;
+        };
+        let next_n = match tokens[double_star_t] {
+            // The next place just after a `/`
+            Token::DoubleStarSlash => match name[double_star_n..].iter().position(|&c| c == '/') {
+                Some(i) => double_star_n + i + 1,
+                None => return false,
+            },
+            _ if double_star_n < name.len() => double_star_n + 1,
+            _ => return false,
+        };
+        double_star = Some((double_star_t, next_n));
+        star = None;
+        t = double_star_t + 1;
+        n = next_n;
+    }
+}
+
+/// Parses a character class following its opening `[`, returning whether it's negated, its
+/// body and the remaining pattern, or `None` if the class isn't closed.
+fn parse_class(pattern: &[char]) -> Option<(bool, &[char], &[char])> {
+    let (negated, body_start) = match pattern.first() {
+        Some('!') => (true, 1),
+        _ => (false, 0),
+    };
+    // A `]` right after the opening bracket is part of the set
+    let end = pattern
+        .iter()
+        .skip(body_start + 1)
+        .position(|&c| c == ']')?
+        + body_start
+        + 1;
+    Some((negated, &pattern[body_start..end], &pattern[end + 1..]))
+}
+
+fn class_contains(body: &[char], c: char) -> bool {
+    let mut i = 0;
+    while i < body.len() {
+        if i + 2 < body.len() && body[i + 1] == '-' {
+            if (body[i]..=body[i + 2]).contains(&c) {
+                return true;
+            }
+            i += 3;
+        } else {
+            if body[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::glob_matches;
+
+    #[test]
+    fn wildcards() {
+        assert!(glob_matches("*.txt", "a.txt"));
+        assert!(!glob_matches("*.txt", "dir/a.txt"));
+        assert!(glob_matches("*/*.txt", "dir/a.txt"));
+        assert!(glob_matches("src/**", "src/a/b/c.rs"));
+        assert!(glob_matches("src/**", "src/"));
+        assert!(!glob_matches("src/**", "other/src/a.rs"));
+        assert!(glob_matches("**/*.rs", "main.rs"));
+        assert!(glob_matches("**/*.rs", "src/read/glob.rs"));
+        assert!(!glob_matches("**/*.rs", "xmain.rsx"));
+        assert!(glob_matches("a?c", "abc"));
+        assert!(!glob_matches("a?c", "a/c"));
+        assert!(glob_matches("exact", "exact"));
+        assert!(!glob_matches("exact", "exactly"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(glob_matches("file[0-9].txt", "file7.txt"));
+        assert!(!glob_matches("file[0-9].txt", "filex.txt"));
+        assert!(glob_matches("file[!0-9].txt", "filex.txt"));
+        assert!(glob_matches("[]x]", "]"));
+        assert!(glob_matches("[abc", "[abc"));
+    }
+
+    #[test]
+    fn no_exponential_backtracking() {
+        let name = "a".repeat(100);
+        assert!(!glob_matches("a*a*a*a*a*a*a*a*a*a*b", &name));
+        assert!(!glob_matches("**a**a**a**a**a**a**a**a**b", &name));
+        assert!(glob_matches("a*a*a*a*a*a*a*a*a*a*", &name));
+    }
+}
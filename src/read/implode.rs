@@ -0,0 +1,235 @@
+use super::bit_reader::BitReader;
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// General purpose flag bit selecting the 8 KiB dictionary instead of the 4 KiB one.
+const LARGE_WINDOW_FLAG: u16 = 1 << 1;
+/// General purpose flag bit signalling that literals are coded with a Shannon-Fano tree.
+const LITERAL_TREE_FLAG: u16 = 1 << 2;
+
+const MAX_CODE_LENGTH: usize = 16;
+const WINDOW_SIZE: usize = 8192;
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// A Shannon-Fano tree, stored as the number of codes of each length and the symbols in code
+/// order.
+#[derive(Debug)]
+struct Tree {
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    symbols: Vec<u16>,
+}
+
+impl Tree {
+    /// Reads a tree of `symbol_count` code lengths, run-length encoded as bytes holding a length
+    /// in the low nibble and a repeat count in the high one, following a count of those bytes.
+    fn read<R: Read>(reader: &mut R, symbol_count: usize) -> Result<Self> {
+        let mut byte = [0u8];
+        reader.read_exact(&mut byte)?;
+        let mut runs = vec![0u8; byte[0] as usize + 1];
+        reader.read_exact(&mut runs)?;
+        let mut lengths = Vec::with_capacity(symbol_count);
+        for run in runs {
+            let length = (run & 0x0F) + 1;
+            let count = (run >> 4) as usize + 1;
+            lengths.extend(std::iter::repeat(length).take(count));
+        }
+        if lengths.len() != symbol_count {
+            return Err(invalid_data("Implode tree has the wrong number of codes"));
+        }
+
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for &length in &lengths {
+            counts[length as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid_data("Implode tree is over-subscribed"));
+            }
+        }
+        // Codes are assigned shortest first, and in symbol order within a length
+        let mut symbols = Vec::with_capacity(symbol_count);
+        for length in 1..=MAX_CODE_LENGTH as u8 {
+            symbols.extend(
+                (0..symbol_count as u16).filter(|&symbol| lengths[symbol as usize] == length),
+            );
+        }
+        Ok(Tree { counts, symbols })
+    }
+
+    /// Decodes a symbol. Shannon-Fano codes are the complement of the canonical Huffman codes
+    /// for the same lengths, and are stored starting from their most significant bit.
+    fn decode<R: Read>(&self, reader: &mut BitReader<R>) -> Result<u16> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for &count in &self.counts[1..] {
+            code |= (reader.bits(1)? ^ 1) as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid_data("Invalid implode code"))
+    }
+}
+
+#[derive(Debug)]
+struct Trees {
+    literal: Option<Tree>,
+    length: Tree,
+    distance: Tree,
+}
+
+/// Decoder for entries compressed with the implode method (6) of PKZIP 1.x.
+///
+/// The data starts with the Shannon-Fano trees, followed by literals and matches into a 4 KiB or
+/// 8 KiB dictionary. Bits 1 and 2 of the general purpose flags select the dictionary size and
+/// whether literals are coded with a third tree. The stream has no end marker, so it's decoded
+/// until the uncompressed size from the entry's headers is reached.
+#[derive(Debug)]
+pub struct ImplodeDecoder<R> {
+    reader: BitReader<R>,
+    /// Read with the first byte of output, so that creating the decoder doesn't fail
+    trees: Option<Trees>,
+    large_window: bool,
+    literal_tree: bool,
+    window: Box<[u8]>,
+    /// The number of bytes produced so far
+    position: u64,
+    uncompressed_size: u64,
+    /// The distance and remaining length of the match being copied
+    pending_match: (usize, usize),
+}
+
+impl<R: Read> ImplodeDecoder<R> {
+    pub fn new(inner: R, uncompressed_size: u64, flags: u16) -> Self {
+        ImplodeDecoder {
+            reader: BitReader::new(inner),
+            trees: None,
+            large_window: flags & LARGE_WINDOW_FLAG != 0,
+            literal_tree: flags & LITERAL_TREE_FLAG != 0,
+            window: vec![0; WINDOW_SIZE].into_boxed_slice(),
+            position: 0,
+            uncompressed_size,
+            pending_match: (0, 0),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+
+    fn read_trees(&mut self) -> Result<Trees> {
+        let reader = self.reader.get_mut();
+        let literal = match self.literal_tree {
+            true => Some(Tree::read(reader, 256)?),
+            false => None,
+        };
+        Ok(Trees {
+            literal,
+            length: Tree::read(reader, 64)?,
+            distance: Tree::read(reader, 64)?,
+        })
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.window[self.position as usize % WINDOW_SIZE] = byte;
+        self.position += 1;
+    }
+
+    /// Decodes the next literal or match, returning the literal or starting the match.
+    fn decode(&mut self, trees: &Trees) -> Result<Option<u8>> {
+        if self.reader.bits(1)? == 1 {
+            let literal = match &trees.literal {
+                Some(tree) => tree.decode(&mut self.reader)? as u8,
+                None => self.reader.bits(8)? as u8,
+            };
+            return Ok(Some(literal));
+        }
+        let low_bits = if self.large_window { 7 } else { 6 };
+        let low = self.reader.bits(low_bits)? as usize;
+        let high = trees.distance.decode(&mut self.reader)? as usize;
+        let distance = (high << low_bits | low) + 1;
+        let mut length = trees.length.decode(&mut self.reader)? as usize;
+        if length == 63 {
+            length += self.reader.bits(8)? as usize;
+        }
+        length += if self.literal_tree { 3 } else { 2 };
+        self.pending_match = (distance, length);
+        Ok(None)
+    }
+}
+
+impl<R: Read> Read for ImplodeDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let trees = match self.trees.take() {
+            Some(trees) => trees,
+            None if self.position < self.uncompressed_size => self.read_trees()?,
+            None => return Ok(0),
+        };
+        let mut count = 0;
+        let result = (|| {
+            while count < buf.len() && self.position < self.uncompressed_size {
+                let byte = match self.pending_match {
+                    (distance, length) if length > 0 => {
+                        self.pending_match.1 -= 1;
+                        // Distances reaching before the start of the data read zeros, which the
+                        // window is filled with
+                        let from = self.position.wrapping_sub(distance as u64) as usize;
+                        self.window[from % WINDOW_SIZE]
+                    }
+                    _ => match self.decode(&trees)? {
+                        Some(literal) => literal,
+                        None => continue,
+                    },
+                };
+                self.push(byte);
+                buf[count] = byte;
+                count += 1;
+            }
+            Ok(())
+        })();
+        self.trees = Some(trees);
+        match result {
+            Err(e) if count == 0 => Err(e),
+            _ => Ok(count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ImplodeDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn truncated() {
+        // A length tree and a distance tree, each with 64 codes of length 6
+        let mut data = vec![
+            3,
+            0x0F << 4 | 5,
+            0x0F << 4 | 5,
+            0x0F << 4 | 5,
+            0x0F << 4 | 5,
+        ];
+        data.extend_from_within(..);
+        let mut decoder = ImplodeDecoder::new(&data[..], 10, 0);
+        let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn wrong_code_count() {
+        let data = [0, 0x0F << 4 | 5];
+        let mut decoder = ImplodeDecoder::new(&data[..], 10, 0);
+        let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
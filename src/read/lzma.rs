@@ -1,47 +1,153 @@
 use lzma_rs::decompress::{Options, Stream, UnpackedSize};
 use std::collections::VecDeque;
-use std::io::{BufRead, Read, Result, Write};
+use std::io::{BufRead, Error, ErrorKind, Read, Result, Write};
 
-const OPTIONS: Options = Options {
-    unpacked_size: UnpackedSize::ReadFromHeader,
-    memlimit: None,
-    allow_incomplete: true,
-};
+/// Size of the LZMA properties that follow the 4-byte ZIP LZMA header.
+const PROPERTIES_SIZE: u16 = 5;
 
+#[derive(Debug)]
+enum State {
+    /// The 4-byte header (LZMA SDK version and properties size) hasn't been read yet.
+    Header,
+    Data(Box<Stream<VecDeque<u8>>>),
+    Finished(VecDeque<u8>),
+}
+
+/// Decoder for entries compressed with [`crate::CompressionMethod::Lzma`].
+///
+/// Unlike a `.lzma` file, the data starts with a 2-byte LZMA SDK version and a
+/// 2-byte properties size, followed by the 5 bytes of LZMA properties and the
+/// raw LZMA1 stream. There is no uncompressed size in the header, so the size
+/// from the entry's headers is used, unless general purpose bit 1 says that the
+/// stream ends with an end-of-stream marker. With a known size, a marker written
+/// after that many bytes is ignored.
 #[derive(Debug)]
 pub struct LzmaDecoder<R> {
     compressed_reader: R,
-    stream: Stream<VecDeque<u8>>,
+    uncompressed_size: Option<u64>,
+    state: State,
 }
 
 impl<R: Read> LzmaDecoder<R> {
-    pub fn new(inner: R) -> Self {
+    /// `uncompressed_size` is `None` if the stream ends with an end-of-stream
+    /// marker instead.
+    pub fn new(inner: R, uncompressed_size: Option<u64>) -> Self {
         LzmaDecoder {
             compressed_reader: inner,
-            stream: Stream::new_with_options(&OPTIONS, VecDeque::new()),
+            uncompressed_size,
+            state: State::Header,
         }
     }
 
     pub fn into_inner(self) -> R {
         self.compressed_reader
     }
+
+    fn read_header(&mut self) -> Result<()> {
+        let mut header = [0u8; 4];
+        self.compressed_reader.read_exact(&mut header)?;
+        let properties_size = u16::from_le_bytes([header[2], header[3]]);
+        if properties_size != PROPERTIES_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "Unsupported LZMA properties size",
+            ));
+        }
+        let options = Options {
+            unpacked_size: UnpackedSize::UseProvided(self.uncompressed_size),
+            memlimit: None,
+            allow_incomplete: false,
+        };
+        self.state = State::Data(Box::new(Stream::new_with_options(
+            &options,
+            VecDeque::new(),
+        )));
+        Ok(())
+    }
 }
 
 impl<R: BufRead> Read for LzmaDecoder<R> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let mut bytes_read = self.stream.get_output_mut().unwrap().read(buf)?;
-        while bytes_read < buf.len() {
-            let compressed_bytes = self.compressed_reader.fill_buf()?;
-            if compressed_bytes.is_empty() {
-                break;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match &mut self.state {
+                State::Header => self.read_header()?,
+                State::Data(stream) => {
+                    let bytes_read = stream.get_output_mut().unwrap().read(buf)?;
+                    if bytes_read > 0 {
+                        return Ok(bytes_read);
+                    }
+                    let compressed_bytes = self.compressed_reader.fill_buf()?;
+                    // The decoder stops taking input once it has produced the
+                    // expected number of bytes; anything left (such as an
+                    // end-of-stream marker) is ignored.
+                    let len = match compressed_bytes {
+                        [] => 0,
+                        bytes => stream.write(bytes)?,
+                    };
+                    if len == 0 {
+                        let State::Data(stream) = std::mem::replace(&mut self.state, State::Header)
+                        else {
+                            unreachable!()
+                        };
+                        self.state = State::Finished(stream.finish()?);
+                        continue;
+                    }
+                    self.compressed_reader.consume(len);
+                }
+                State::Finished(output) => return output.read(buf),
             }
-            self.stream.write_all(compressed_bytes)?;
-            bytes_read += self
-                .stream
-                .get_output_mut()
-                .unwrap()
-                .read(&mut buf[bytes_read..])?;
         }
-        Ok(bytes_read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LzmaDecoder;
+    use std::io::{Cursor, Read};
+
+    /// The LZMA data of `hello.txt` in `lzma_method_14.zip`, which ends with an
+    /// end-of-stream marker.
+    fn hello_data() -> &'static [u8] {
+        let archive = include_bytes!("../../tests/data/lzma_method_14.zip");
+        &archive[30 + "hello.txt".len()..][..31]
+    }
+
+    #[test]
+    fn known_size_ignores_end_marker() {
+        let mut decoder = LzmaDecoder::new(Cursor::new(hello_data()), Some(12));
+        let mut content = Vec::new();
+        decoder.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"Hello world\n");
+    }
+
+    #[test]
+    fn end_marker() {
+        let mut decoder = LzmaDecoder::new(Cursor::new(hello_data()), None);
+        let mut content = Vec::new();
+        decoder.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"Hello world\n");
+
+        // Without the marker, the stream is incomplete
+        let data = &hello_data()[..25];
+        let mut decoder = LzmaDecoder::new(Cursor::new(data), None);
+        decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    }
+
+    #[test]
+    fn wrong_size() {
+        let mut decoder = LzmaDecoder::new(Cursor::new(hello_data()), Some(13));
+        decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    }
+
+    #[test]
+    fn bad_properties_size() {
+        let mut data = hello_data().to_vec();
+        data[2] = 4;
+        let mut decoder = LzmaDecoder::new(Cursor::new(data), Some(12));
+        let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
     }
 }
@@ -0,0 +1,167 @@
+//! Reading archives split into several volumes
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Presents the volumes of a split archive, such as `archive.z01`, `archive.z02` and
+/// `archive.zip`, as one contiguous stream.
+///
+/// Offsets recorded in a split archive are relative to the volume they point into, so open the
+/// stream with [`ZipArchive::new_multi_volume`](crate::ZipArchive::new_multi_volume) rather than
+/// [`ZipArchive::new`](crate::ZipArchive::new), which only supports archives on a single disk.
+///
+/// ```no_run
+/// # fn doit() -> zip::result::ZipResult<()>
+/// # {
+/// use zip::read::MultiVolumeReader;
+/// use zip::ZipArchive;
+///
+/// let volumes = MultiVolumeReader::open("backup/archive.zip", "backup")?;
+/// let mut archive = ZipArchive::new_multi_volume(volumes)?;
+/// archive.extract("restored")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MultiVolumeReader<R> {
+    volumes: Vec<R>,
+    /// Where each volume starts in the stream, followed by the total length
+    starts: Vec<u64>,
+    position: u64,
+}
+
+impl MultiVolumeReader<File> {
+    /// Opens the volumes of a split archive, given its last volume and the directory holding the
+    /// others.
+    ///
+    /// The other volumes are named after the last one with the extensions `.z01`, `.z02` and so
+    /// on, and are opened in that order until one is missing. If there are none, the archive is
+    /// read as a single volume.
+    pub fn open<P: AsRef<Path>, Q: AsRef<Path>>(last_volume: P, parts_dir: Q) -> io::Result<Self> {
+        let last_volume = last_volume.as_ref();
+        let stem = last_volume.file_stem().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Volume path has no file name")
+        })?;
+        let mut volumes = Vec::new();
+        for number in 1.. {
+            let mut name = stem.to_owned();
+            name.push(format!(".z{number:02}"));
+            match File::open(parts_dir.as_ref().join(name)) {
+                Ok(volume) => volumes.push(volume),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+                Err(e) => return Err(e),
+            }
+        }
+        volumes.push(File::open(last_volume)?);
+        Self::new(volumes)
+    }
+}
+
+impl<R: Read + Seek> MultiVolumeReader<R> {
+    /// Joins the given volumes, which must be in order, ending with the one holding the central
+    /// directory.
+    pub fn new(mut volumes: Vec<R>) -> io::Result<Self> {
+        if volumes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "A split archive needs at least one volume",
+            ));
+        }
+        let mut starts = Vec::with_capacity(volumes.len() + 1);
+        let mut length = 0u64;
+        for volume in &mut volumes {
+            starts.push(length);
+            length = length
+                .checked_add(volume.seek(SeekFrom::End(0))?)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Volumes too large"))?;
+        }
+        starts.push(length);
+        Ok(MultiVolumeReader {
+            volumes,
+            starts,
+            position: 0,
+        })
+    }
+
+    /// Returns the number of volumes.
+    pub fn volume_count(&self) -> usize {
+        self.volumes.len()
+    }
+
+    /// Unwraps the volumes.
+    pub fn into_inner(self) -> Vec<R> {
+        self.volumes
+    }
+
+    /// Where each volume starts in the stream, indexed by disk number.
+    pub(crate) fn volume_starts(&self) -> &[u64] {
+        &self.starts[..self.volumes.len()]
+    }
+}
+
+impl<R: Read + Seek> Read for MultiVolumeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = self.starts[self.volumes.len()];
+        if self.position >= length || buf.is_empty() {
+            return Ok(0);
+        }
+        // Empty volumes share their start with the next one, so this finds the last volume
+        // starting at or before the position, which is the one holding it.
+        let index = self.starts.partition_point(|&start| start <= self.position) - 1;
+        let offset = self.position - self.starts[index];
+        let remaining = self.starts[index + 1] - self.position;
+        let limit = buf
+            .len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        let volume = &mut self.volumes[index];
+        volume.seek(SeekFrom::Start(offset))?;
+        let count = volume.read(&mut buf[..limit])?;
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for MultiVolumeReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.starts[self.volumes.len()].checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MultiVolumeReader;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    #[test]
+    fn read_across_volumes() -> std::io::Result<()> {
+        let volumes = vec![
+            Cursor::new(b"abc".to_vec()),
+            Cursor::new(Vec::new()),
+            Cursor::new(b"defg".to_vec()),
+        ];
+        let mut reader = MultiVolumeReader::new(volumes)?;
+        assert_eq!(reader.volume_starts(), &[0, 3, 3]);
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        assert_eq!(content, "abcdefg");
+
+        assert_eq!(reader.seek(SeekFrom::End(-5))?, 2);
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"cde");
+        assert!(reader.seek(SeekFrom::Current(-6)).is_err());
+        Ok(())
+    }
+}
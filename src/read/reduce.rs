@@ -0,0 +1,159 @@
+use super::bit_reader::BitReader;
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// Introduces a match, or a literal 144 if followed by a zero.
+const DLE: u8 = 144;
+/// Large enough for the farthest match with a compression factor of 4.
+const WINDOW_SIZE: usize = 4096;
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Decoder for entries compressed with the reduce methods (2 to 5) of PKZIP 0.9.
+///
+/// The data starts with a set of up to 63 likely followers for each byte value, after which
+/// each byte is either an index into the followers of the byte before it, or a literal. The
+/// resulting bytes are themselves compressed with matches introduced by [`DLE`], whose length
+/// and distance are split according to the compression factor, from 1 to 4.
+#[derive(Debug)]
+pub struct ReduceDecoder<R> {
+    reader: BitReader<R>,
+    factor: u8,
+    /// Read with the first byte of output, so that creating the decoder doesn't fail
+    followers: Option<Vec<Vec<u8>>>,
+    /// The last byte read through the follower sets
+    last: u8,
+    window: Box<[u8]>,
+    /// The number of bytes produced so far
+    position: u64,
+    uncompressed_size: u64,
+    /// The distance and remaining length of the match being copied
+    pending_match: (usize, usize),
+}
+
+impl<R: Read> ReduceDecoder<R> {
+    /// Creates a decoder for the given compression factor, which must be from 1 to 4.
+    pub fn new(inner: R, uncompressed_size: u64, factor: u8) -> Self {
+        debug_assert!((1..=4).contains(&factor));
+        ReduceDecoder {
+            reader: BitReader::new(inner),
+            factor,
+            followers: None,
+            last: 0,
+            window: vec![0; WINDOW_SIZE].into_boxed_slice(),
+            position: 0,
+            uncompressed_size,
+            pending_match: (0, 0),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+
+    /// Reads the follower sets, which are stored from the one for 255 down to the one for 0.
+    fn read_followers(&mut self) -> Result<Vec<Vec<u8>>> {
+        let mut followers = vec![Vec::new(); 256];
+        for set in followers.iter_mut().rev() {
+            let count = self.reader.bits(6)?;
+            for _ in 0..count {
+                set.push(self.reader.bits(8)? as u8);
+            }
+        }
+        Ok(followers)
+    }
+
+    fn next_byte(&mut self, followers: &[Vec<u8>]) -> Result<u8> {
+        let set = &followers[self.last as usize];
+        let byte = if set.is_empty() || self.reader.bits(1)? == 1 {
+            self.reader.bits(8)? as u8
+        } else {
+            // Enough bits for the largest index, and at least one
+            let width = (usize::BITS - (set.len() - 1).leading_zeros()).max(1);
+            let index = self.reader.bits(width)? as usize;
+            *set.get(index)
+                .ok_or_else(|| invalid_data("Invalid reduce follower index"))?
+        };
+        self.last = byte;
+        Ok(byte)
+    }
+
+    /// Decodes the next literal or match, returning the literal or starting the match.
+    fn decode(&mut self, followers: &[Vec<u8>]) -> Result<Option<u8>> {
+        let byte = self.next_byte(followers)?;
+        if byte != DLE {
+            return Ok(Some(byte));
+        }
+        let first = self.next_byte(followers)?;
+        if first == 0 {
+            return Ok(Some(DLE));
+        }
+        let length_mask = 0x7F >> (self.factor - 1);
+        let mut length = (first & length_mask) as usize;
+        if length == length_mask as usize {
+            length += self.next_byte(followers)? as usize;
+        }
+        let high = (first >> (8 - self.factor)) as usize;
+        let distance = (high << 8 | self.next_byte(followers)? as usize) + 1;
+        self.pending_match = (distance, length + 3);
+        Ok(None)
+    }
+}
+
+impl<R: Read> Read for ReduceDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let followers = match self.followers.take() {
+            Some(followers) => followers,
+            None if self.position < self.uncompressed_size => self.read_followers()?,
+            None => return Ok(0),
+        };
+        let mut count = 0;
+        let result = (|| {
+            while count < buf.len() && self.position < self.uncompressed_size {
+                let byte = match self.pending_match {
+                    (distance, length) if length > 0 => {
+                        self.pending_match.1 -= 1;
+                        // Distances reaching before the start of the data read zeros, which the
+                        // window is filled with
+                        let from = self.position.wrapping_sub(distance as u64) as usize;
+                        self.window[from % WINDOW_SIZE]
+                    }
+                    _ => match self.decode(&followers)? {
+                        Some(literal) => literal,
+                        None => continue,
+                    },
+                };
+                self.window[self.position as usize % WINDOW_SIZE] = byte;
+                self.position += 1;
+                buf[count] = byte;
+                count += 1;
+            }
+            Ok(())
+        })();
+        self.followers = Some(followers);
+        match result {
+            Err(e) if count == 0 => Err(e),
+            _ => Ok(count),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ReduceDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn literals_and_matches() {
+        // Empty follower sets, so that every byte is 8 bits: "ab", a literal 144, then a match
+        // of length 4 at distance 2
+        let mut data = vec![0; 256 * 6 / 8];
+        data.extend([b'a', b'b', 144, 0, 144, 1, 1]);
+        let mut content = Vec::new();
+        ReduceDecoder::new(&data[..], 7, 1)
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, b"ab\x90b\x90b\x90");
+    }
+}
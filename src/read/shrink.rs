@@ -0,0 +1,208 @@
+use super::bit_reader::BitReader;
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// Introduces a code size increase or a partial clear, in the code that follows it.
+const CONTROL_CODE: u16 = 256;
+const INCREASE_CODE_SIZE: u32 = 1;
+const PARTIAL_CLEAR: u32 = 2;
+const FIRST_FREE_CODE: usize = 257;
+const MAX_CODE_SIZE: u32 = 13;
+const TABLE_SIZE: usize = 1 << MAX_CODE_SIZE;
+/// Marks a table entry that isn't in use.
+const FREE: u16 = u16::MAX;
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Decoder for entries compressed with the shrink method (1) of PKZIP 1.x.
+///
+/// Shrink is LZW with codes growing from 9 to 13 bits. Unlike other LZW variants, the code size
+/// only grows when the encoder says so, and a full table is made room in by a partial clear,
+/// which frees the codes that aren't a prefix of any other. New codes take the lowest free one.
+#[derive(Debug)]
+pub struct ShrinkDecoder<R> {
+    reader: BitReader<R>,
+    code_size: u32,
+    /// The code of each string without its last byte, or [`FREE`]. Only codes from 257 use
+    /// the table, since the lower ones stand for single bytes.
+    prefixes: Box<[u16]>,
+    /// The last byte of each string
+    suffixes: Box<[u8]>,
+    /// Every code from 257 up to this one is in use
+    next_free: usize,
+    previous: Option<u16>,
+    /// The rest of the current string, last byte first
+    pending: Vec<u8>,
+    /// The number of bytes produced so far
+    position: u64,
+    uncompressed_size: u64,
+}
+
+impl<R: Read> ShrinkDecoder<R> {
+    pub fn new(inner: R, uncompressed_size: u64) -> Self {
+        ShrinkDecoder {
+            reader: BitReader::new(inner),
+            code_size: 9,
+            prefixes: vec![FREE; TABLE_SIZE].into_boxed_slice(),
+            suffixes: vec![0; TABLE_SIZE].into_boxed_slice(),
+            next_free: FIRST_FREE_CODE,
+            previous: None,
+            pending: Vec::new(),
+            position: 0,
+            uncompressed_size,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+
+    fn next_free_code(&mut self) -> Option<u16> {
+        while self.next_free < TABLE_SIZE && self.prefixes[self.next_free] != FREE {
+            self.next_free += 1;
+        }
+        (self.next_free < TABLE_SIZE).then_some(self.next_free as u16)
+    }
+
+    /// Frees every code that isn't the prefix of another.
+    fn partial_clear(&mut self) {
+        let mut is_prefix = vec![false; TABLE_SIZE];
+        for &prefix in &self.prefixes[FIRST_FREE_CODE..] {
+            if prefix != FREE {
+                is_prefix[prefix as usize] = true;
+            }
+        }
+        for (prefix, is_prefix) in self
+            .prefixes
+            .iter_mut()
+            .zip(is_prefix)
+            .skip(FIRST_FREE_CODE)
+        {
+            if !is_prefix {
+                *prefix = FREE;
+            }
+        }
+        self.next_free = FIRST_FREE_CODE;
+    }
+
+    /// Puts the string of `code` in `pending`.
+    fn expand(&mut self, mut code: u16) -> Result<()> {
+        self.pending.clear();
+        while code > CONTROL_CODE {
+            // A string can't be longer than the chain of codes leading to it, so a longer one
+            // means the prefixes form a loop
+            if self.prefixes[code as usize] == FREE || self.pending.len() == TABLE_SIZE {
+                return Err(invalid_data("Invalid shrink code"));
+            }
+            self.pending.push(self.suffixes[code as usize]);
+            code = self.prefixes[code as usize];
+        }
+        if code == CONTROL_CODE {
+            return Err(invalid_data("Invalid shrink code"));
+        }
+        self.pending.push(code as u8);
+        Ok(())
+    }
+
+    /// Reads codes up to the next string, and adds a code for the previous string followed by
+    /// the first byte of this one.
+    fn decode(&mut self) -> Result<()> {
+        let code = loop {
+            let code = self.reader.bits(self.code_size)? as u16;
+            if code != CONTROL_CODE {
+                break code;
+            }
+            match self.reader.bits(self.code_size)? {
+                INCREASE_CODE_SIZE if self.code_size < MAX_CODE_SIZE => self.code_size += 1,
+                PARTIAL_CLEAR => self.partial_clear(),
+                _ => return Err(invalid_data("Invalid shrink control code")),
+            }
+        };
+        let Some(previous) = self.previous else {
+            self.previous = Some(code);
+            return self.expand(code);
+        };
+        let new_code = self.next_free_code();
+        if new_code == Some(code) {
+            // The code is the one being added, so its string is the previous one followed by
+            // its own first byte
+            self.expand(previous)?;
+            self.prefixes[code as usize] = previous;
+            self.suffixes[code as usize] = self.pending[self.pending.len() - 1];
+        }
+        self.expand(code)?;
+        if let Some(new_code) = new_code {
+            self.prefixes[new_code as usize] = previous;
+            self.suffixes[new_code as usize] = self.pending[self.pending.len() - 1];
+        }
+        self.previous = Some(code);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for ShrinkDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut count = 0;
+        while count < buf.len() && self.position < self.uncompressed_size {
+            match self.pending.pop() {
+                Some(byte) => {
+                    buf[count] = byte;
+                    count += 1;
+                    self.position += 1;
+                }
+                None => self.decode()?,
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ShrinkDecoder;
+    use std::io::{ErrorKind, Read};
+
+    /// Packs codes of the given sizes, least significant bit first.
+    fn pack(codes: &[(u32, u32)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let (mut buffer, mut count) = (0u32, 0);
+        for &(code, size) in codes {
+            buffer |= code << count;
+            count += size;
+            while count >= 8 {
+                data.push(buffer as u8);
+                buffer >>= 8;
+                count -= 8;
+            }
+        }
+        data.push(buffer as u8);
+        data
+    }
+
+    #[test]
+    fn increase_code_size() {
+        // "a", then 10-bit codes, starting with the one for "aa" before it's added
+        let data = pack(&[(97, 9), (256, 9), (1, 9), (257, 10), (98, 10)]);
+        let mut content = Vec::new();
+        ShrinkDecoder::new(&data[..], 4)
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, b"aaab");
+    }
+
+    #[test]
+    fn invalid_codes() {
+        for codes in [
+            &[(97, 9), (256, 9), (3, 9)][..],
+            &[(97, 9), (258, 9)],
+            &[(256, 9), (2, 9), (300, 9)],
+        ] {
+            let data = pack(codes);
+            let err = ShrinkDecoder::new(&data[..], 10)
+                .read_to_end(&mut Vec::new())
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -1,50 +1,74 @@
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
 
 use super::{
-    central_header_to_zip_file_inner, read_zipfile_from_stream, ZipCentralEntryBlock, ZipError,
-    ZipFile, ZipFileData, ZipResult,
+    central_header_to_zip_file_inner, read_local_header_from_stream, read_zipfile_from_bufread,
+    zipfile_from_bufread, ZipCentralEntryBlock, ZipFile, ZipFileData, ZipResult,
 };
 use crate::spec::FixedSizeBlock;
+use crate::CompressionMethod;
 
 /// Stream decoder for zip.
 #[derive(Debug)]
-pub struct ZipStreamReader<R>(R);
+pub struct ZipStreamReader<R> {
+    reader: R,
+    /// The entry whose local header was read by [`ZipStreamReader::peek_entry`]
+    peeked: Option<ZipStreamFileMetadata>,
+    /// Whether the start of the central directory has been read
+    finished: bool,
+}
 
 impl<R> ZipStreamReader<R> {
     /// Create a new ZipStreamReader
     pub const fn new(reader: R) -> Self {
-        Self(reader)
+        Self {
+            reader,
+            peeked: None,
+            finished: false,
+        }
     }
 }
 
-impl<R: Read> ZipStreamReader<R> {
-    fn parse_central_directory(&mut self) -> ZipResult<ZipStreamFileMetadata> {
-        // Give archive_offset and central_header_start dummy value 0, since
-        // they are not used in the output.
-        let archive_offset = 0;
-        let central_header_start = 0;
-
-        // Parse central header
-        let block = ZipCentralEntryBlock::parse(&mut self.0)?;
-        let file = central_header_to_zip_file_inner(
-            &mut self.0,
-            archive_offset,
-            central_header_start,
-            block,
-        )?;
-        Ok(ZipStreamFileMetadata(file))
-    }
+fn parse_central_directory<R: Read>(reader: &mut R) -> ZipResult<ZipStreamFileMetadata> {
+    // Give archive_offset and central_header_start dummy value 0, since
+    // they are not used in the output.
+    let archive_offset = 0;
+    let central_header_start = 0;
+
+    // Parse central header
+    let block = ZipCentralEntryBlock::parse(reader)?;
+    let file = central_header_to_zip_file_inner(
+        reader,
+        archive_offset,
+        central_header_start,
+        Default::default(),
+        block,
+    )?;
+    Ok(ZipStreamFileMetadata(file))
+}
 
+impl<R: Read> ZipStreamReader<R> {
     /// Iterate over the stream and extract all file and their
     /// metadata.
-    pub fn visit<V: ZipStreamVisitor>(mut self, visitor: &mut V) -> ZipResult<()> {
-        while let Some(mut file) = read_zipfile_from_stream(&mut self.0)? {
-            visitor.visit_file(&mut file)?;
+    ///
+    /// Deflated entries that are followed by a data descriptor, as written by
+    /// streaming archivers, are supported: their data is decompressed until
+    /// the end of the Deflate stream, then checked against the descriptor.
+    ///
+    /// An entry already returned by [`ZipStreamReader::peek_entry`] is visited first.
+    pub fn visit<V: ZipStreamVisitor>(self, visitor: &mut V) -> ZipResult<()> {
+        let mut reader = BufReader::new(self.reader);
+        if let Some(peeked) = self.peeked {
+            visitor.visit_file(&mut zipfile_from_bufread(peeked.0, &mut reader)?)?;
+        }
+        if !self.finished {
+            while let Some(mut file) = read_zipfile_from_bufread(&mut reader)? {
+                visitor.visit_file(&mut file)?;
+            }
         }
 
-        while let Ok(metadata) = self.parse_central_directory() {
+        while let Ok(metadata) = parse_central_directory(&mut reader) {
             visitor.visit_additional_metadata(&metadata)?;
         }
 
@@ -52,17 +76,22 @@ impl<R: Read> ZipStreamReader<R> {
     }
 
     /// Extract a Zip archive into a directory, overwriting files if they
-    /// already exist. Paths are sanitized with [`ZipFile::enclosed_name`].
+    /// already exist. Paths are checked like those of [`ZipArchive::extract`] with the default
+    /// [`Config`]: names with control characters or longer than the platform's path limit fail
+    /// with [`ZipError::InvalidEntryName`], and UNC paths or names that would lead out of the
+    /// directory are rejected.
     ///
     /// Extraction is not atomic; If an error is encountered, some of the files
     /// may be left on disk.
+    ///
+    /// [`ZipArchive::extract`]: crate::ZipArchive::extract
+    /// [`Config`]: crate::read::Config
+    /// [`ZipError::InvalidEntryName`]: crate::result::ZipError::InvalidEntryName
     pub fn extract<P: AsRef<Path>>(self, directory: P) -> ZipResult<()> {
         struct Extractor<'a>(&'a Path);
         impl ZipStreamVisitor for Extractor<'_> {
             fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
-                let filepath = file
-                    .enclosed_name()
-                    .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
+                let filepath = file.data.extraction_path(false)?;
 
                 let outpath = self.0.join(filepath);
 
@@ -86,9 +115,7 @@ impl<R: Read> ZipStreamReader<R> {
             ) -> ZipResult<()> {
                 #[cfg(unix)]
                 {
-                    let filepath = metadata
-                        .enclosed_name()
-                        .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
+                    let filepath = metadata.0.extraction_path(false)?;
 
                     let outpath = self.0.join(filepath);
 
@@ -106,6 +133,86 @@ impl<R: Read> ZipStreamReader<R> {
     }
 }
 
+impl<R: BufRead> ZipStreamReader<R> {
+    /// Reads the local header of the next entry, leaving its data unread so that it can be
+    /// opened with [`ZipStreamReader::next_entry`] or passed over with
+    /// [`ZipStreamReader::skip_entry`]. Returns `None` once the central directory is reached.
+    ///
+    /// Peeking again without moving on returns the same entry. The header lacks the metadata
+    /// that's only in the central directory, as described in [`ZipStreamVisitor::visit_file`].
+    ///
+    /// ```
+    /// # fn doit() -> zip::result::ZipResult<()>
+    /// # {
+    /// use std::io::{BufReader, Read};
+    /// use zip::unstable::stream::ZipStreamReader;
+    ///
+    /// # let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// # writer.start_file("a.txt", zip::write::SimpleFileOptions::default())?;
+    /// # let bytes = writer.finish()?.into_inner();
+    /// let mut reader = ZipStreamReader::new(BufReader::new(&bytes[..]));
+    /// while let Some(entry) = reader.peek_entry()? {
+    ///     if entry.name().ends_with(".txt") {
+    ///         let mut content = String::new();
+    ///         reader.next_entry()?.unwrap().read_to_string(&mut content)?;
+    ///     } else {
+    ///         reader.skip_entry()?;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # doit().unwrap();
+    /// ```
+    pub fn peek_entry(&mut self) -> ZipResult<Option<&ZipStreamFileMetadata>> {
+        if self.peeked.is_none() && !self.finished {
+            self.peeked =
+                read_local_header_from_stream(&mut self.reader)?.map(ZipStreamFileMetadata);
+            self.finished = self.peeked.is_none();
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    /// Opens the next entry for reading, or returns `None` once the central directory is reached.
+    ///
+    /// Like [`crate::read::read_zipfile_from_stream`], dropping the entry moves past the rest of
+    /// its data.
+    pub fn next_entry(&mut self) -> ZipResult<Option<ZipFile<'_>>> {
+        self.peek_entry()?;
+        match self.peeked.take() {
+            Some(peeked) => zipfile_from_bufread(peeked.0, &mut self.reader).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Moves past the next entry without decompressing it, returning whether there was one.
+    ///
+    /// The data is skipped using the sizes in the local header, so entries whose compression
+    /// method isn't supported can be skipped too. The exception is entries followed by a data
+    /// descriptor: their end can only be found by decompressing them, and their CRC-32 and sizes
+    /// are checked against the descriptor.
+    pub fn skip_entry(&mut self) -> ZipResult<bool> {
+        self.peek_entry()?;
+        let Some(peeked) = self.peeked.take() else {
+            return Ok(false);
+        };
+        if !peeked.0.using_data_descriptor {
+            io::copy(
+                &mut (&mut self.reader).take(peeked.0.compressed_size),
+                &mut io::sink(),
+            )?;
+            return Ok(true);
+        }
+        let mut file = zipfile_from_bufread(peeked.0, &mut self.reader)?;
+        #[cfg(feature = "_deflate-any")]
+        if let super::ZipFileReader::DataDescriptor(reader) = &mut file.reader {
+            io::copy(reader, &mut io::sink())?;
+            return Ok(true);
+        }
+        io::copy(&mut file.take_raw_reader()?, &mut io::sink())?;
+        Ok(true)
+    }
+}
+
 /// Visitor for ZipStreamReader
 pub trait ZipStreamVisitor {
     ///  * `file` - contains the content of the file and most of the metadata,
@@ -177,12 +284,9 @@ impl ZipStreamFileMetadata {
         self.0.enclosed_name()
     }
 
-    /// Returns whether the file is actually a directory
+    /// Returns whether the file is actually a directory, as with [`ZipFile::is_dir`]
     pub fn is_dir(&self) -> bool {
-        self.name()
-            .chars()
-            .next_back()
-            .map_or(false, |c| c == '/' || c == '\\')
+        self.0.is_dir()
     }
 
     /// Returns whether the file is a regular file
@@ -199,6 +303,25 @@ impl ZipStreamFileMetadata {
     pub const fn unix_mode(&self) -> Option<u32> {
         self.0.unix_mode()
     }
+
+    /// Get the compression method used to store the file
+    pub const fn compression(&self) -> CompressionMethod {
+        self.0.compression_method
+    }
+
+    /// Get the size of the file, in bytes, in the archive
+    ///
+    /// This is zero in the local header of an entry followed by a data descriptor.
+    pub const fn compressed_size(&self) -> u64 {
+        self.0.compressed_size
+    }
+
+    /// Get the size of the file, in bytes, when uncompressed
+    ///
+    /// This is zero in the local header of an entry followed by a data descriptor.
+    pub const fn size(&self) -> u64 {
+        self.0.uncompressed_size
+    }
 }
 
 #[cfg(test)]
@@ -360,4 +483,208 @@ mod test {
         .visit(&mut DummyVisitor)
         .unwrap_err();
     }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn data_descriptor() {
+        let data = include_bytes!("../../tests/data/data_descriptor_stream.zip");
+
+        #[derive(Default)]
+        struct V {
+            contents: Vec<(Box<str>, Vec<u8>)>,
+        }
+        impl ZipStreamVisitor for V {
+            fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
+                // Leave the second entry unread, so it's skipped when dropped.
+                let mut content = Vec::new();
+                if file.name() != "second.txt" {
+                    file.read_to_end(&mut content)?;
+                }
+                self.contents.push((file.name().into(), content));
+                Ok(())
+            }
+            fn visit_additional_metadata(
+                &mut self,
+                _metadata: &ZipStreamFileMetadata,
+            ) -> ZipResult<()> {
+                Ok(())
+            }
+        }
+
+        let mut visitor = V::default();
+        ZipStreamReader::new(io::Cursor::new(data))
+            .visit(&mut visitor)
+            .unwrap();
+        assert_eq!(visitor.contents.len(), 3);
+        assert_eq!(&*visitor.contents[0].0, "first.txt");
+        assert_eq!(visitor.contents[0].1, b"first file, streamed\n".repeat(50));
+        assert_eq!(&*visitor.contents[1].0, "second.txt");
+        assert_eq!(&*visitor.contents[2].0, "empty.txt");
+        assert!(visitor.contents[2].1.is_empty());
+
+        // Corrupt the CRC-32 in the first entry's data descriptor
+        let mut corrupt = data.to_vec();
+        corrupt[0x4b] ^= 0xff;
+        let mut visitor = V::default();
+        ZipStreamReader::new(io::Cursor::new(corrupt))
+            .visit(&mut visitor)
+            .unwrap_err();
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn data_descriptor_without_signature() -> ZipResult<()> {
+        // The descriptor signatures of data_descriptor_stream.zip, including the Zip64 one of
+        // second.txt, removed
+        let data = include_bytes!("../../tests/data/data_descriptor_no_signature_stream.zip");
+        let mut reader = ZipStreamReader::new(io::Cursor::new(data));
+        let mut file = reader.next_entry()?.unwrap();
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        assert_eq!(content, b"first file, streamed\n".repeat(50));
+        drop(file);
+        let mut file = reader.next_entry()?.unwrap();
+        assert_eq!(file.name(), "second.txt");
+        assert!(crate::HasZipMetadata::get_metadata(&file).large_file);
+        content.clear();
+        file.read_to_end(&mut content)?;
+        drop(file);
+        assert_eq!(reader.next_entry()?.unwrap().name(), "empty.txt");
+        assert!(reader.next_entry()?.is_none());
+        Ok(())
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn data_descriptor_crc_equals_signature() -> ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use std::io::Write;
+
+        // The last 4 bytes make the CRC-32 0x08074b50, the descriptor signature
+        let contents = b"crc collides with the signature: \x64\xe2\xfc\xa0";
+        let mut writer = crate::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.start_file_streaming("collision.txt", options)?;
+        writer.write_all(contents)?;
+        writer.start_file("next.txt", options)?;
+        writer.write_all(b"next")?;
+        let with_signature = writer.finish()?.into_inner();
+        let signature = crate::spec::Magic::DATA_DESCRIPTOR_SIGNATURE.to_le_bytes();
+        let descriptor = with_signature
+            .windows(8)
+            .position(|window| window[..4] == signature && window[4..] == signature)
+            .unwrap();
+        let mut without_signature = with_signature.clone();
+        without_signature.drain(descriptor..descriptor + 4);
+
+        for data in [with_signature, without_signature] {
+            let mut reader = ZipStreamReader::new(io::Cursor::new(data));
+            let mut file = reader.next_entry()?.unwrap();
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)?;
+            assert_eq!(content, contents);
+            assert_eq!(file.computed_crc32(), Some(0x08074b50));
+            drop(file);
+            let mut file = reader.next_entry()?.unwrap();
+            assert_eq!(file.name(), "next.txt");
+            content.clear();
+            file.read_to_end(&mut content)?;
+            assert_eq!(content, b"next");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn peek_and_skip_entries() -> ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use std::io::Write;
+
+        let mut writer = crate::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("skipped.bin", options)?;
+        writer.write_all(&[0xAA; 1000])?;
+        writer.start_file("read.txt", options)?;
+        writer.write_all(b"kept")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut reader = ZipStreamReader::new(io::BufReader::new(&bytes[..]));
+        let entry = reader.peek_entry()?.unwrap();
+        assert_eq!(entry.name(), "skipped.bin");
+        assert_eq!(entry.compression(), CompressionMethod::Stored);
+        assert_eq!(entry.size(), 1000);
+        assert_eq!(reader.peek_entry()?.unwrap().name(), "skipped.bin");
+        assert!(reader.skip_entry()?);
+
+        assert_eq!(reader.peek_entry()?.unwrap().compressed_size(), 4);
+        let mut content = String::new();
+        reader.next_entry()?.unwrap().read_to_string(&mut content)?;
+        assert_eq!(content, "kept");
+        assert!(reader.peek_entry()?.is_none());
+        assert!(!reader.skip_entry()?);
+        assert!(reader.next_entry()?.is_none());
+
+        // Skipping doesn't need a decoder for the compression method
+        let mut unsupported = bytes.clone();
+        unsupported[8..10].copy_from_slice(&97u16.to_le_bytes());
+        let mut reader = ZipStreamReader::new(io::BufReader::new(&unsupported[..]));
+        assert_eq!(reader.peek_entry()?.unwrap().name(), "skipped.bin");
+        assert!(reader.skip_entry()?);
+        assert_eq!(reader.peek_entry()?.unwrap().name(), "read.txt");
+
+        // Visiting after peeking starts with the peeked entry
+        let mut reader = ZipStreamReader::new(io::BufReader::new(&bytes[..]));
+        reader.peek_entry()?;
+        let mut counter = CounterVisitor::default();
+        reader.visit(&mut counter)?;
+        assert_eq!(counter.0, 2);
+        Ok(())
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn skip_data_descriptor_entries() -> ZipResult<()> {
+        let data = include_bytes!("../../tests/data/data_descriptor_stream.zip");
+        let mut reader = ZipStreamReader::new(io::Cursor::new(data));
+        let entry = reader.peek_entry()?.unwrap();
+        assert_eq!(entry.name(), "first.txt");
+        assert_eq!(entry.compressed_size(), 0);
+        assert!(reader.skip_entry()?);
+        assert!(reader.skip_entry()?);
+        let mut file = reader.next_entry()?.unwrap();
+        assert_eq!(file.name(), "empty.txt");
+        assert_eq!(file.read(&mut [0; 1])?, 0);
+        drop(file);
+        assert!(reader.peek_entry()?.is_none());
+
+        // The descriptor is still checked
+        let mut corrupt = data.to_vec();
+        corrupt[0x4b] ^= 0xff;
+        let mut reader = ZipStreamReader::new(io::Cursor::new(corrupt));
+        assert!(reader.skip_entry().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn extract_checks_entry_names() -> ZipResult<()> {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use std::io::Write;
+
+        for (name, is_invalid_name) in [("bad\u{1}name.txt", true), ("../escape.txt", false)] {
+            let mut writer = crate::ZipWriter::new(io::Cursor::new(Vec::new()));
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(b"data")?;
+            let bytes = writer.finish()?.into_inner();
+
+            let directory = tempdir::TempDir::new("stream_extract_checks_entry_names")?;
+            let result = ZipStreamReader::new(&bytes[..]).extract(directory.path());
+            assert_eq!(
+                matches!(result, Err(ZipError::InvalidEntryName { .. })),
+                is_invalid_name
+            );
+            assert!(result.is_err());
+            assert_eq!(fs::read_dir(directory.path())?.count(), 0);
+        }
+        Ok(())
+    }
 }
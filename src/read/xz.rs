@@ -1,21 +1,31 @@
 use crc32fast::Hasher;
+use displaydoc::Display;
 use lzma_rs::decompress::raw::Lzma2Decoder;
 use std::{
     collections::VecDeque,
     io::{BufRead, Error, Read, Result, Write},
 };
+use thiserror::Error;
 
+mod filter;
+use filter::{Filter, FILTER_LZMA2};
+
+/// Reads an XZ stream and decompresses it.
 #[derive(Debug)]
 pub struct XzDecoder<R: BufRead> {
     compressed_reader: R,
-    stream_size: usize,
+    stream_size: u64,
     buf: VecDeque<u8>,
-    check_size: usize,
-    records: Vec<(usize, usize)>,
+    check_size: u64,
+    records: Vec<(u64, u64)>,
     flags: [u8; 2],
+    stream_sizes: Option<(u64, u64)>,
+    validate_check: bool,
+    max_dict_size: u32,
 }
 
 impl<R: BufRead> XzDecoder<R> {
+    /// Creates a decoder reading the compressed stream from `inner`.
     pub fn new(inner: R) -> Self {
         XzDecoder {
             compressed_reader: inner,
@@ -24,19 +34,70 @@ impl<R: BufRead> XzDecoder<R> {
             check_size: 0,
             records: vec![],
             flags: [0, 0],
+            stream_sizes: None,
+            validate_check: false,
+            max_dict_size: u32::MAX,
         }
     }
+
+    /// Sets whether the check stored after each block is compared against
+    /// the decompressed data.
+    ///
+    /// This is off by default, since the CRC32 of a ZIP entry already covers
+    /// the same data. CRC32 and CRC64 checks are verified, while SHA-256 checks
+    /// are skipped. A mismatch is reported as [`std::io::ErrorKind::InvalidData`].
+    pub fn with_check_validation(mut self, validate: bool) -> Self {
+        self.validate_check = validate;
+        self
+    }
+
+    /// Sets the largest LZMA2 dictionary size accepted, in bytes, to cap the
+    /// memory used for decoding.
+    ///
+    /// Blocks that declare a larger dictionary fail with
+    /// [`std::io::ErrorKind::InvalidData`] before any of their data is decoded.
+    /// There's no limit by default.
+    pub fn with_max_dict_size(mut self, max_dict_size: u32) -> Self {
+        self.max_dict_size = max_dict_size;
+        self
+    }
+
+    /// Resets the decoder to read a new stream from `inner`, returning the
+    /// previous reader.
+    ///
+    /// The decoder is left in the same state as one returned by [`XzDecoder::new`],
+    /// except that its buffers are kept for reuse and the check validation and
+    /// dictionary size settings are preserved.
+    pub fn reset(&mut self, inner: R) -> R {
+        self.stream_size = 0;
+        self.buf.clear();
+        self.check_size = 0;
+        self.records.clear();
+        self.flags = [0, 0];
+        self.stream_sizes = None;
+        std::mem::replace(&mut self.compressed_reader, inner)
+    }
+
+    /// Returns the compressed and uncompressed sizes of the stream, once its
+    /// index has been read and validated.
+    ///
+    /// The compressed size covers the whole stream, including its header,
+    /// index and footer. For concatenated streams, the sizes of all the
+    /// streams read so far and the padding between them are added up.
+    pub fn stream_sizes(&self) -> Option<(u64, u64)> {
+        self.stream_sizes
+    }
 }
 
 struct CountReader<'a, R: BufRead> {
     inner: &'a mut R,
-    count: &'a mut usize,
+    count: &'a mut u64,
 }
 
 impl<R: BufRead> Read for CountReader<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         let count = self.inner.read(buf)?;
-        *self.count += count;
+        *self.count += count as u64;
         Ok(count)
     }
 }
@@ -48,14 +109,14 @@ impl<R: BufRead> BufRead for CountReader<'_, R> {
 
     fn consume(&mut self, amt: usize) {
         self.inner.consume(amt);
-        *self.count += amt;
+        *self.count += amt as u64;
     }
 }
 
 struct BufWriter<'a> {
     inner: &'a mut [u8],
     written: &'a mut usize,
-    total: &'a mut usize,
+    total: &'a mut u64,
     rest: &'a mut VecDeque<u8>,
 }
 
@@ -65,11 +126,11 @@ impl<'a> Write for BufWriter<'a> {
             let len = std::cmp::min(buf.len(), self.inner.len() - *self.written);
             self.inner[*self.written..*self.written + len].copy_from_slice(&buf[..len]);
             *self.written += len;
-            *self.total += len;
+            *self.total += len as u64;
             Ok(len)
         } else {
             self.rest.extend(buf.iter());
-            *self.total += buf.len();
+            *self.total += buf.len() as u64;
             Ok(buf.len())
         }
     }
@@ -79,8 +140,149 @@ impl<'a> Write for BufWriter<'a> {
     }
 }
 
-fn error<T>(s: &'static str) -> Result<T> {
-    Err(Error::new(std::io::ErrorKind::InvalidData, s))
+const CHECK_CRC32: u8 = 0x01;
+const CHECK_CRC64: u8 = 0x04;
+
+/// CRC-64 as used by XZ (ECMA-182 polynomial, reflected).
+struct Crc64(u64);
+
+impl Crc64 {
+    const TABLE: [u64; 256] = {
+        let mut table = [0u64; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u64;
+            let mut j = 0;
+            while j < 8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xC96C_5795_D787_0F42
+                } else {
+                    crc >> 1
+                };
+                j += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    fn new() -> Self {
+        Crc64(!0)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for &b in data {
+            self.0 = Self::TABLE[((self.0 ^ b as u64) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    fn finalize(self) -> u64 {
+        !self.0
+    }
+}
+
+/// Error decoding an XZ stream.
+///
+/// The decoder reports it as an [`std::io::Error`] of kind
+/// [`std::io::ErrorKind::InvalidData`], from which it can be recovered with
+/// [`std::io::Error::get_ref`] and [`downcast_ref`](trait@std::error::Error#method.downcast_ref).
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum XzError {
+    /// Invalid multi-byte encoding
+    InvalidMultibyte,
+
+    /// Unsupported XZ filter properties
+    UnsupportedFilterProperties,
+
+    /// Invalid XZ stream padding
+    BadStreamPadding,
+
+    /// Invalid XZ header
+    BadMagic,
+
+    /// Truncated XZ header
+    TruncatedHeader,
+
+    /// Invalid XZ stream flags
+    BadStreamFlags,
+
+    /// Invalid XZ stream flags CRC32
+    HeaderCrcMismatch,
+
+    /// Invalid XZ index record count
+    IndexRecordCountMismatch,
+
+    /// Invalid XZ unpadded size
+    IndexUnpaddedSizeMismatch,
+
+    /// Invalid XZ uncompressed size
+    IndexUncompressedSizeMismatch,
+
+    /// Invalid XZ index padding
+    BadIndexPadding,
+
+    /// Invalid XZ index CRC32
+    IndexCrcMismatch,
+
+    /// Invalid XZ footer CRC32
+    FooterCrcMismatch,
+
+    /// Invalid XZ footer size
+    FooterSizeMismatch,
+
+    /// Invalid XZ footer flags
+    FooterFlagsMismatch,
+
+    /// Invalid XZ footer magic
+    BadFooterMagic,
+
+    /// Invalid XZ footer padding
+    BadFooterPadding,
+
+    /// Invalid XZ block flags
+    BadBlockFlags,
+
+    /// Invalid XZ filter chain (LZMA2 must be last)
+    BadFilterChain,
+
+    /// Unsupported XZ filter properties size
+    UnsupportedFilterPropertiesSize,
+
+    /// Unsupported XZ filter ID
+    UnsupportedFilter,
+
+    /// Invalid XZ block header (too short)
+    BlockHeaderTooShort,
+
+    /// Invalid XZ block header padding
+    BadBlockHeaderPadding,
+
+    /// Invalid XZ block header CRC32
+    BlockHeaderCrcMismatch,
+
+    /// Invalid XZ block padding
+    BadBlockPadding,
+
+    /// Invalid XZ block CRC32
+    BlockCrc32Mismatch,
+
+    /// Invalid XZ block CRC64
+    BlockCrc64Mismatch,
+
+    /// XZ dictionary size exceeds the configured limit
+    DictionaryTooLarge,
+}
+
+impl From<XzError> for Error {
+    fn from(err: XzError) -> Error {
+        Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+fn error<T>(err: XzError) -> Result<T> {
+    Err(err.into())
 }
 
 fn get_multibyte<R: BufRead>(input: &mut R, hasher: &mut Hasher) -> Result<u64> {
@@ -95,7 +297,16 @@ fn get_multibyte<R: BufRead>(input: &mut R, hasher: &mut Hasher) -> Result<u64>
             return Ok(result);
         }
     }
-    error("Invalid multi-byte encoding")
+    error(XzError::InvalidMultibyte)
+}
+
+/// Decodes the dictionary size from the LZMA2 filter properties byte.
+fn lzma2_dict_size(properties: u8) -> Result<u32> {
+    match properties {
+        0..=39 => Ok((2 | (properties as u32 & 1)) << (properties / 2 + 11)),
+        40 => Ok(u32::MAX),
+        _ => error(XzError::UnsupportedFilterProperties),
+    }
 }
 
 impl<R: BufRead> Read for XzDecoder<R> {
@@ -111,28 +322,56 @@ impl<R: BufRead> Read for XzDecoder<R> {
             count: &mut self.stream_size,
         };
         if *reader.count == 0 {
+            if let Some((compressed_size, _)) = &mut self.stream_sizes {
+                // Another stream may follow, after padding made of multiples
+                // of four null bytes.
+                let mut padding = 0;
+                loop {
+                    let data = reader.fill_buf()?;
+                    let zeros = data.iter().take_while(|&&b| b == 0).count();
+                    let done = zeros < data.len() || data.is_empty();
+                    reader.inner.consume(zeros);
+                    padding += zeros;
+                    if done {
+                        break;
+                    }
+                }
+                if padding & 0x3 != 0 {
+                    return error(XzError::BadStreamPadding);
+                }
+                *compressed_size += padding as u64;
+            }
+            if reader.fill_buf()?.is_empty() {
+                return Ok(0);
+            }
             let mut b = [0u8; 12];
-            match reader.read(&mut b) {
-                Ok(0) => return Ok(0),
-                Err(e) => return Err(e),
-                _ => (),
+            if let Err(e) = reader.read_exact(&mut b) {
+                if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                    return error(XzError::TruncatedHeader);
+                }
+                return Err(e);
             }
             if b[..6] != b"\xFD7zXZ\0"[..] {
-                return error("Invalid XZ header");
+                return error(XzError::BadMagic);
             }
             self.flags = [b[6], b[7]];
             if self.flags[0] != 0 || self.flags[1] & 0xF0 != 0 {
-                return error("Invalid XZ stream flags");
-            }
-            match self.flags[1] & 0x0F {
-                0 => self.check_size = 0,
-                1 => self.check_size = 4,
-                _ => return error("Unsupported XZ stream flags"),
+                return error(XzError::BadStreamFlags);
             }
+            // The size of each check type is fixed by the format, even for
+            // IDs which aren't assigned yet.
+            self.check_size = match self.flags[1] & 0x0F {
+                0 => 0,
+                1..=3 => 4,
+                4..=6 => 8,
+                7..=9 => 16,
+                10..=12 => 32,
+                _ => 64,
+            };
             let mut digest = Hasher::new();
             digest.update(&self.flags);
             if digest.finalize().to_le_bytes() != b[8..] {
-                return error("Invalid XZ stream flags CRC32");
+                return error(XzError::HeaderCrcMismatch);
             }
         }
 
@@ -146,54 +385,61 @@ impl<R: BufRead> Read for XzDecoder<R> {
             // index
             let num_records = get_multibyte(&mut reader, &mut digest)?;
             if num_records != self.records.len() as u64 {
-                return error("Invalid XZ index record count");
+                return error(XzError::IndexRecordCountMismatch);
             }
             for (unpadded_size, total) in &self.records {
-                if get_multibyte(&mut reader, &mut digest)? != *unpadded_size as u64 {
-                    return error("Invalid XZ unpadded size");
+                if get_multibyte(&mut reader, &mut digest)? != *unpadded_size {
+                    return error(XzError::IndexUnpaddedSizeMismatch);
                 }
-                if get_multibyte(&mut reader, &mut digest)? != *total as u64 {
-                    return error("Invalid XZ uncompressed size");
+                if get_multibyte(&mut reader, &mut digest)? != *total {
+                    return error(XzError::IndexUncompressedSizeMismatch);
                 }
             }
             let mut size = *reader.count - block_begin;
-            let mut b = vec![0u8; (4 - (size & 0x3)) & 0x3];
+            let mut b = vec![0u8; ((4 - (size & 0x3)) & 0x3) as usize];
             reader.read_exact(b.as_mut_slice())?;
             if !b.iter().all(|&b| b == 0) {
-                return error("Invalid XZ index padding");
+                return error(XzError::BadIndexPadding);
             }
             digest.update(b.as_slice());
-            size += b.len();
+            size += b.len() as u64;
             let mut b = [0u8; 16];
             reader.read_exact(&mut b)?;
             if digest.finalize().to_le_bytes() != b[..4] {
-                return error("Invalid XZ index CRC32");
+                return error(XzError::IndexCrcMismatch);
             }
             let mut digest = Hasher::new();
             digest.update(&b[8..14]);
             if digest.finalize().to_le_bytes() != b[4..8] {
-                return error("Invalid XZ footer CRC32");
+                return error(XzError::FooterCrcMismatch);
             }
             if b[8..12] != ((size >> 2) as u32).to_le_bytes() {
-                return error("Invalid XZ footer size");
+                return error(XzError::FooterSizeMismatch);
             }
             if self.flags != b[12..14] {
-                return error("Invalid XZ footer flags");
+                return error(XzError::FooterFlagsMismatch);
             }
             if &b[14..16] != b"YZ" {
-                return error("Invalid XZ footer magic");
+                return error(XzError::BadFooterMagic);
             }
-            let mut b = vec![0u8; (4 - (*reader.count & 0x3)) & 0x3];
+            let mut b = vec![0u8; ((4 - (*reader.count & 0x3)) & 0x3) as usize];
             reader.read_exact(b.as_mut_slice())?;
             if !b.iter().all(|&b| b == 0) {
-                return error("Invalid XZ footer padding");
+                return error(XzError::BadFooterPadding);
             }
+            let uncompressed_size: u64 = self.records.iter().map(|(_, total)| *total).sum();
+            let (previous_compressed, previous_uncompressed) = self.stream_sizes.unwrap_or((0, 0));
+            self.stream_sizes = Some((
+                previous_compressed + *reader.count,
+                previous_uncompressed + uncompressed_size,
+            ));
+            self.records.clear();
             *reader.count = 0;
             return self.read(buf);
         }
 
         // block
-        let header_end = ((b[0] as usize) << 2) - 1 + *reader.count;
+        let header_end = ((b[0] as u64) << 2) - 1 + *reader.count;
         let mut b = [0u8; 1];
         reader.read_exact(&mut b)?;
         digest.update(&b);
@@ -201,7 +447,7 @@ impl<R: BufRead> Read for XzDecoder<R> {
         let num_filters = (flags & 0x03) + 1;
 
         if flags & 0x3C != 0 {
-            return error("Invalid XZ block flags");
+            return error(XzError::BadBlockFlags);
         }
         if flags & 0x40 != 0 {
             get_multibyte(&mut reader, &mut digest)?;
@@ -209,62 +455,427 @@ impl<R: BufRead> Read for XzDecoder<R> {
         if flags & 0x80 != 0 {
             get_multibyte(&mut reader, &mut digest)?;
         }
-        for _ in 0..num_filters {
+        // The last filter of the chain must be LZMA2; the others are applied
+        // to its output in reverse order.
+        let mut filters = Vec::with_capacity(num_filters as usize - 1);
+        for i in 0..num_filters {
             let filter_id = get_multibyte(&mut reader, &mut digest)?;
-            if filter_id != 0x21 {
-                return error("Unsupported XZ filter ID");
-            }
             let properties_size = get_multibyte(&mut reader, &mut digest)?;
-            if properties_size != 1 {
-                return error("Unsupported XZ filter properties size");
-            }
-            reader.read_exact(&mut b)?;
-            if b[0] & 0xC0 != 0 {
-                return error("Unsupported XZ filter properties");
+            if i == num_filters - 1 {
+                if filter_id != FILTER_LZMA2 {
+                    return error(XzError::BadFilterChain);
+                }
+                if properties_size != 1 {
+                    return error(XzError::UnsupportedFilterPropertiesSize);
+                }
+                reader.read_exact(&mut b)?;
+                // lzma-rs keeps the whole block as its window, so the
+                // dictionary size only needs to be validated here.
+                if lzma2_dict_size(b[0])? > self.max_dict_size {
+                    return error(XzError::DictionaryTooLarge);
+                }
+                digest.update(&b);
+            } else {
+                if filter_id == FILTER_LZMA2 {
+                    return error(XzError::BadFilterChain);
+                }
+                let Some(properties_size) = Filter::properties_size(filter_id, properties_size)
+                else {
+                    return error(XzError::UnsupportedFilter);
+                };
+                let mut properties = [0u8; 4];
+                let properties = &mut properties[..properties_size];
+                reader.read_exact(properties)?;
+                digest.update(properties);
+                filters.push(Filter::new(filter_id, properties)?);
             }
-            digest.update(&b);
         }
         let Some(padding_bytes) = header_end.checked_sub(*reader.count) else {
-            return error("Invalid XZ block header (too short)");
+            return error(XzError::BlockHeaderTooShort);
         };
-        let mut b = vec![0u8; padding_bytes];
+        let mut b = vec![0u8; padding_bytes as usize];
         reader.read_exact(b.as_mut_slice())?;
         if !b.iter().all(|&b| b == 0) {
-            return error("Invalid XZ block header padding");
+            return error(XzError::BadBlockHeaderPadding);
         }
         digest.update(b.as_slice());
 
         let mut b = [0u8; 4];
         reader.read_exact(&mut b)?;
         if digest.finalize().to_le_bytes() != b {
-            return error("Invalid XZ block header CRC32");
+            return error(XzError::BlockHeaderCrcMismatch);
         }
         let mut written = 0;
         let mut total = 0;
-        Lzma2Decoder::new().decompress(
-            &mut reader,
-            &mut BufWriter {
+        if filters.is_empty() {
+            Lzma2Decoder::new().decompress(
+                &mut reader,
+                &mut BufWriter {
+                    inner: buf,
+                    written: &mut written,
+                    rest: &mut self.buf,
+                    total: &mut total,
+                },
+            )?;
+        } else {
+            // Filters may look ahead, so decode the whole block before
+            // handing out any of it.
+            let mut block = Vec::new();
+            Lzma2Decoder::new().decompress(&mut reader, &mut block)?;
+            for filter in filters.iter().rev() {
+                filter.decode(&mut block);
+            }
+            BufWriter {
                 inner: buf,
                 written: &mut written,
                 rest: &mut self.buf,
                 total: &mut total,
-            },
-        )?;
+            }
+            .write_all(&block)?;
+        }
 
         let unpadded_size = *reader.count - block_begin;
-        self.records.push((unpadded_size, total));
-        // ignore check here since zip itself will check it
-        let mut b = vec![0u8; ((4 - (unpadded_size & 0x3)) & 0x3) + self.check_size];
+        self.records.push((unpadded_size + self.check_size, total));
+        let padding_bytes = ((4 - (unpadded_size & 0x3)) & 0x3) as usize;
+        let mut b = vec![0u8; padding_bytes + self.check_size as usize];
         reader.read_exact(b.as_mut_slice())?;
-        if !b.as_slice()[..self.check_size].iter().all(|&b| b == 0) {
-            return error("Invalid XZ block padding");
+        if !b.as_slice()[..padding_bytes].iter().all(|&b| b == 0) {
+            return error(XzError::BadBlockPadding);
+        }
+        // Unless asked to, ignore the check since zip itself will check it.
+        // The block's output is in `buf` and `self.buf`, which was empty
+        // before this block was decoded.
+        if self.validate_check {
+            let (front, back) = self.buf.as_slices();
+            let output = [&buf[..written], front, back];
+            let check = &b[padding_bytes..];
+            match self.flags[1] & 0x0F {
+                CHECK_CRC32 => {
+                    let mut digest = Hasher::new();
+                    output.iter().for_each(|data| digest.update(data));
+                    if digest.finalize().to_le_bytes() != check {
+                        return error(XzError::BlockCrc32Mismatch);
+                    }
+                }
+                CHECK_CRC64 => {
+                    let mut digest = Crc64::new();
+                    output.iter().for_each(|data| digest.update(data));
+                    if digest.finalize().to_le_bytes() != check {
+                        return error(XzError::BlockCrc64Mismatch);
+                    }
+                }
+                // SHA-256 and the reserved check types can only be skipped
+                _ => {}
+            }
         }
         Ok(written)
     }
 }
 
 impl<R: BufRead> XzDecoder<R> {
+    /// Consumes this decoder, returning the underlying reader.
     pub fn into_inner(self) -> R {
         self.compressed_reader
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+
+    /// Pseudo-random data sprinkled with branch instructions for every BCJ
+    /// filter, matching the input the fixtures were compressed from.
+    fn filter_test_data() -> Vec<u8> {
+        let mut x = 1u32;
+        let mut data: Vec<u8> = (0..8192)
+            .map(|_| {
+                x = x.wrapping_mul(1103515245).wrapping_add(12345);
+                (x >> 24) as u8
+            })
+            .collect();
+        for chunk in data.chunks_exact_mut(64) {
+            chunk[0] = 0xE8;
+            chunk[3] = 0;
+            chunk[4] = 0;
+            chunk[11] = 0xEB;
+            chunk[16] = 0x48;
+            chunk[19] = (chunk[19] & !3) | 1;
+            chunk[24] = 0x40;
+            chunk[25] &= 0x3F;
+            chunk[33] = 0xF0 | (chunk[33] & 7);
+            chunk[35] = 0xF8 | (chunk[35] & 7);
+            chunk[43] = 0x94;
+        }
+        data
+    }
+
+    fn decompress(compressed: &[u8]) -> Result<Vec<u8>> {
+        let mut decompressed = Vec::new();
+        XzDecoder::new(compressed).read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    #[test]
+    fn stream_sizes() {
+        let mut archive = crate::ZipArchive::new(std::io::Cursor::new(
+            &include_bytes!("../../tests/data/xz.zip")[..],
+        ))
+        .unwrap();
+        let mut compressed = Vec::new();
+        archive
+            .by_index_raw(0)
+            .unwrap()
+            .read_to_end(&mut compressed)
+            .unwrap();
+
+        let mut decoder = XzDecoder::new(&compressed[..]);
+        assert_eq!(decoder.stream_sizes(), None);
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, b"Hello world\n");
+        assert_eq!(
+            decoder.stream_sizes(),
+            Some((compressed.len() as u64, decompressed.len() as u64))
+        );
+    }
+
+    #[test]
+    fn filter_chains() {
+        let expected = filter_test_data();
+        for compressed in [
+            &include_bytes!("../../tests/data/xz_filters/delta.xz")[..],
+            include_bytes!("../../tests/data/xz_filters/x86.xz"),
+            include_bytes!("../../tests/data/xz_filters/x86_start.xz"),
+            include_bytes!("../../tests/data/xz_filters/powerpc.xz"),
+            include_bytes!("../../tests/data/xz_filters/ia64.xz"),
+            include_bytes!("../../tests/data/xz_filters/arm.xz"),
+            include_bytes!("../../tests/data/xz_filters/armthumb.xz"),
+            include_bytes!("../../tests/data/xz_filters/sparc.xz"),
+            include_bytes!("../../tests/data/xz_filters/arm64.xz"),
+            include_bytes!("../../tests/data/xz_filters/delta_x86.xz"),
+        ] {
+            assert_eq!(decompress(compressed).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn check_validation() {
+        let mut compressed = include_bytes!("../../tests/data/xz_filters/x86.xz").to_vec();
+        // A single block is followed by its CRC32, a 12-byte index and a
+        // 12-byte footer.
+        let check_start = compressed.len() - 28;
+        let expected = filter_test_data();
+        let mut digest = Hasher::new();
+        digest.update(&expected);
+        assert_eq!(
+            compressed[check_start..check_start + 4],
+            digest.finalize().to_le_bytes()
+        );
+
+        let mut decompressed = Vec::new();
+        XzDecoder::new(&compressed[..])
+            .with_check_validation(true)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, expected);
+
+        compressed[check_start] ^= 0xFF;
+        assert_eq!(decompress(&compressed).unwrap(), expected);
+        let err = XzDecoder::new(&compressed[..])
+            .with_check_validation(true)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid XZ block CRC32");
+    }
+
+    #[test]
+    fn check_types() {
+        let expected = b"Hello world\n".repeat(100);
+        for compressed in [
+            &include_bytes!("../../tests/data/xz_checks/crc64.xz")[..],
+            include_bytes!("../../tests/data/xz_checks/sha256.xz"),
+        ] {
+            let mut decompressed = Vec::new();
+            XzDecoder::new(compressed)
+                .with_check_validation(true)
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, expected);
+        }
+
+        let mut compressed = include_bytes!("../../tests/data/xz_checks/crc64.xz").to_vec();
+        let check_start = compressed.len() - 32;
+        compressed[check_start] ^= 0xFF;
+        assert_eq!(decompress(&compressed).unwrap(), expected);
+        let err = XzDecoder::new(&compressed[..])
+            .with_check_validation(true)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid XZ block CRC64");
+    }
+
+    #[test]
+    fn dict_size() {
+        assert_eq!(lzma2_dict_size(0).unwrap(), 4 << 10);
+        assert_eq!(lzma2_dict_size(1).unwrap(), 6 << 10);
+        assert_eq!(lzma2_dict_size(22).unwrap(), 8 << 20);
+        assert_eq!(lzma2_dict_size(28).unwrap(), 64 << 20);
+        assert_eq!(lzma2_dict_size(39).unwrap(), 3 << 30);
+        assert_eq!(lzma2_dict_size(40).unwrap(), u32::MAX);
+        assert!(lzma2_dict_size(41).is_err());
+    }
+
+    #[test]
+    fn large_dict() {
+        // Compressed with `xz -9`, which uses a 64 MiB dictionary. The data
+        // repeats its first 4 KiB after 9 MiB of zeros.
+        let compressed = include_bytes!("../../tests/data/xz_large_dict.xz");
+        assert_eq!(compressed[20..23], [0x21, 1, 28]);
+        let mut x = 1u32;
+        let head: Vec<u8> = (0..4096)
+            .map(|_| {
+                x = x.wrapping_mul(1103515245).wrapping_add(12345);
+                (x >> 24) as u8
+            })
+            .collect();
+        let decompressed = decompress(compressed).unwrap();
+        assert_eq!(decompressed.len(), (9 << 20) + 2 * head.len());
+        assert_eq!(decompressed[..4096], head);
+        assert!(decompressed[4096..(9 << 20) + 4096].iter().all(|&b| b == 0));
+        assert_eq!(decompressed[(9 << 20) + 4096..], head);
+    }
+
+    #[test]
+    fn max_dict_size() {
+        let compressed = include_bytes!("../../tests/data/xz_large_dict.xz");
+        let mut decoder = XzDecoder::new(&compressed[..]).with_max_dict_size(32 << 20);
+        let err = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "XZ dictionary size exceeds the configured limit"
+        );
+
+        let mut decoder = XzDecoder::new(&compressed[..]).with_max_dict_size(64 << 20);
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed.len(), (9 << 20) + 2 * 4096);
+    }
+
+    #[test]
+    fn concatenated_streams() {
+        let first = include_bytes!("../../tests/data/xz_checks/crc64.xz");
+        let second = include_bytes!("../../tests/data/xz_checks/sha256.xz");
+        let mut compressed = first.to_vec();
+        compressed.extend_from_slice(&[0; 8]);
+        compressed.extend_from_slice(second);
+        compressed.extend_from_slice(&[0; 4]);
+
+        let mut decoder = XzDecoder::new(&compressed[..]);
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, b"Hello world\n".repeat(200));
+        assert_eq!(
+            decoder.stream_sizes(),
+            Some((compressed.len() as u64, decompressed.len() as u64))
+        );
+
+        compressed.pop();
+        let err = decompress(&compressed).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid XZ stream padding");
+    }
+
+    #[test]
+    fn reset() {
+        let first = &include_bytes!("../../tests/data/xz_checks/crc64.xz")[..];
+        let second = &include_bytes!("../../tests/data/xz_filters/x86.xz")[..];
+        let mut decoder = XzDecoder::new(first).with_check_validation(true);
+        // Stop in the middle of the stream, with output left in the buffer.
+        let mut partial = [0u8; 5];
+        decoder.read_exact(&mut partial).unwrap();
+        assert_eq!(&partial, b"Hello");
+
+        decoder.reset(second);
+        assert_eq!(decoder.stream_sizes(), None);
+        let mut decompressed = Vec::new();
+        decoder.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, filter_test_data());
+        assert_eq!(
+            decoder.stream_sizes(),
+            Some((second.len() as u64, decompressed.len() as u64))
+        );
+    }
+
+    #[test]
+    fn truncated_header() {
+        let compressed = include_bytes!("../../tests/data/xz_checks/crc64.xz");
+        assert_eq!(decompress(&[]).unwrap(), b"");
+        for len in 1..12 {
+            // Split the header over two reads, as a short read would
+            let reader = BufReader::with_capacity(4, &compressed[..len]);
+            let err = XzDecoder::new(reader)
+                .read_to_end(&mut Vec::new())
+                .unwrap_err();
+            assert_eq!(
+                err.get_ref().unwrap().downcast_ref::<XzError>(),
+                Some(&XzError::TruncatedHeader)
+            );
+        }
+        let reader = BufReader::with_capacity(4, &compressed[..]);
+        let mut decompressed = Vec::new();
+        XzDecoder::new(reader)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, b"Hello world\n".repeat(100));
+    }
+
+    #[test]
+    fn unknown_filter_id() {
+        let mut compressed = include_bytes!("../../tests/data/xz_filters/delta.xz").to_vec();
+        // Replace the delta filter ID with an unassigned one and fix up the
+        // block header CRC32.
+        assert_eq!(compressed[18], 0x03);
+        compressed[18] = 0x1F;
+        let header_len = ((compressed[12] as usize) + 1) * 4;
+        let mut digest = Hasher::new();
+        digest.update(&compressed[12..12 + header_len - 4]);
+        compressed[12 + header_len - 4..12 + header_len]
+            .copy_from_slice(&digest.finalize().to_le_bytes());
+        let err = decompress(&compressed).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Unsupported XZ filter ID");
+        assert_eq!(
+            err.get_ref().unwrap().downcast_ref::<XzError>(),
+            Some(&XzError::UnsupportedFilter)
+        );
+    }
+
+    #[test]
+    fn index_size_mismatch() {
+        let original = include_bytes!("../../tests/data/xz_filters/x86.xz");
+        // The index holds one record, the unpadded and uncompressed sizes of
+        // the block, followed by padding and its CRC32.
+        let index_start = original.len() - 24;
+        assert_eq!(
+            original[index_start..index_start + 6],
+            [0, 1, 0xF4, 0x3F, 0x80, 0x40]
+        );
+        for (offset, expected) in [
+            (2, XzError::IndexUnpaddedSizeMismatch),
+            (4, XzError::IndexUncompressedSizeMismatch),
+        ] {
+            let mut compressed = original.to_vec();
+            compressed[index_start + offset] += 1;
+            let mut digest = Hasher::new();
+            digest.update(&compressed[index_start..index_start + 8]);
+            compressed[index_start + 8..index_start + 12]
+                .copy_from_slice(&digest.finalize().to_le_bytes());
+            let err = decompress(&compressed).unwrap_err();
+            assert_eq!(
+                err.get_ref().unwrap().downcast_ref::<XzError>(),
+                Some(&expected)
+            );
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Decoding stages for the non-LZMA2 filters of an XZ filter chain.
+//!
+//! Every block of an XZ stream is decoded independently, so the filters are
+//! applied in place on the fully decompressed block contents.
+
+use super::{error, XzError};
+use std::io::Result;
+
+const FILTER_DELTA: u64 = 0x03;
+const FILTER_X86: u64 = 0x04;
+const FILTER_POWERPC: u64 = 0x05;
+const FILTER_IA64: u64 = 0x06;
+const FILTER_ARM: u64 = 0x07;
+const FILTER_ARMTHUMB: u64 = 0x08;
+const FILTER_SPARC: u64 = 0x09;
+const FILTER_ARM64: u64 = 0x0A;
+pub(super) const FILTER_LZMA2: u64 = 0x21;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Filter {
+    Delta { distance: usize },
+    X86 { start_offset: u32 },
+    PowerPc { start_offset: u32 },
+    Ia64 { start_offset: u32 },
+    Arm { start_offset: u32 },
+    ArmThumb { start_offset: u32 },
+    Sparc { start_offset: u32 },
+    Arm64 { start_offset: u32 },
+}
+
+impl Filter {
+    /// Returns the properties size expected for the given filter ID, or
+    /// `None` if the ID isn't a supported non-LZMA2 filter.
+    pub(super) fn properties_size(id: u64, declared: u64) -> Option<usize> {
+        match id {
+            FILTER_DELTA => (declared == 1).then_some(1),
+            FILTER_X86..=FILTER_ARM64 => matches!(declared, 0 | 4).then_some(declared as usize),
+            _ => None,
+        }
+    }
+
+    pub(super) fn new(id: u64, properties: &[u8]) -> Result<Filter> {
+        if id == FILTER_DELTA {
+            return Ok(Filter::Delta {
+                distance: properties[0] as usize + 1,
+            });
+        }
+        let start_offset = match properties {
+            [] => 0,
+            [a, b, c, d] => u32::from_le_bytes([*a, *b, *c, *d]),
+            _ => return error(XzError::UnsupportedFilterPropertiesSize),
+        };
+        Ok(match id {
+            FILTER_X86 => Filter::X86 { start_offset },
+            FILTER_POWERPC => Filter::PowerPc { start_offset },
+            FILTER_IA64 => Filter::Ia64 { start_offset },
+            FILTER_ARM => Filter::Arm { start_offset },
+            FILTER_ARMTHUMB => Filter::ArmThumb { start_offset },
+            FILTER_SPARC => Filter::Sparc { start_offset },
+            FILTER_ARM64 => Filter::Arm64 { start_offset },
+            _ => return error(XzError::UnsupportedFilter),
+        })
+    }
+
+    /// Reverses the filter on a whole decompressed block.
+    pub(super) fn decode(&self, buf: &mut [u8]) {
+        match *self {
+            Filter::Delta { distance } => {
+                for i in distance..buf.len() {
+                    buf[i] = buf[i].wrapping_add(buf[i - distance]);
+                }
+            }
+            Filter::X86 { start_offset } => x86(buf, start_offset),
+            Filter::PowerPc { start_offset } => powerpc(buf, start_offset),
+            Filter::Ia64 { start_offset } => ia64(buf, start_offset),
+            Filter::Arm { start_offset } => arm(buf, start_offset),
+            Filter::ArmThumb { start_offset } => armthumb(buf, start_offset),
+            Filter::Sparc { start_offset } => sparc(buf, start_offset),
+            Filter::Arm64 { start_offset } => arm64(buf, start_offset),
+        }
+    }
+}
+
+fn x86(buf: &mut [u8], now_pos: u32) {
+    const MASK_TO_ALLOWED_STATUS: [bool; 8] = [true, true, true, false, true, false, false, false];
+    const MASK_TO_BIT_NUMBER: [u32; 8] = [0, 1, 2, 2, 3, 3, 3, 3];
+    let test_ms_byte = |b: u8| b == 0 || b == 0xFF;
+
+    if buf.len() < 5 {
+        return;
+    }
+    let mut prev_mask = 0u32;
+    let mut prev_pos = now_pos.wrapping_sub(5);
+    let limit = buf.len() - 5;
+    let mut pos = 0;
+    while pos <= limit {
+        let b = buf[pos];
+        if b != 0xE8 && b != 0xE9 {
+            pos += 1;
+            continue;
+        }
+        let cur = now_pos.wrapping_add(pos as u32);
+        let offset = cur.wrapping_sub(prev_pos);
+        prev_pos = cur;
+        if offset > 5 {
+            prev_mask = 0;
+        } else {
+            for _ in 0..offset {
+                prev_mask &= 0x77;
+                prev_mask <<= 1;
+            }
+        }
+        let b = buf[pos + 4];
+        if test_ms_byte(b)
+            && MASK_TO_ALLOWED_STATUS[((prev_mask >> 1) & 0x7) as usize]
+            && (prev_mask >> 1) < 0x10
+        {
+            let mut src = u32::from_le_bytes([buf[pos + 1], buf[pos + 2], buf[pos + 3], b]);
+            let mut dest;
+            loop {
+                dest = src.wrapping_sub(cur.wrapping_add(5));
+                if prev_mask == 0 {
+                    break;
+                }
+                let i = MASK_TO_BIT_NUMBER[(prev_mask >> 1) as usize];
+                if !test_ms_byte((dest >> (24 - i * 8)) as u8) {
+                    break;
+                }
+                src = dest ^ ((1u32 << (32 - i * 8)) - 1);
+            }
+            let dest = (dest & 0x00FF_FFFF) | (((dest >> 24) & 1).wrapping_neg() << 24);
+            buf[pos + 1..pos + 5].copy_from_slice(&dest.to_le_bytes());
+            pos += 5;
+            prev_mask = 0;
+        } else {
+            pos += 1;
+            prev_mask |= 1;
+            if test_ms_byte(b) {
+                prev_mask |= 0x10;
+            }
+        }
+    }
+}
+
+fn powerpc(buf: &mut [u8], now_pos: u32) {
+    for (i, chunk) in buf.chunks_exact_mut(4).enumerate() {
+        if chunk[0] >> 2 == 0x12 && chunk[3] & 3 == 1 {
+            let src = u32::from_be_bytes([chunk[0] & 3, chunk[1], chunk[2], chunk[3] & !3]);
+            let dest = src.wrapping_sub(now_pos.wrapping_add((i * 4) as u32));
+            chunk[0] = 0x48 | ((dest >> 24) & 0x03) as u8;
+            chunk[1] = (dest >> 16) as u8;
+            chunk[2] = (dest >> 8) as u8;
+            chunk[3] = (chunk[3] & 0x03) | dest as u8;
+        }
+    }
+}
+
+fn ia64(buf: &mut [u8], now_pos: u32) {
+    const BRANCH_TABLE: [u32; 32] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 4, 6, 6, 0, 0, 7, 7, 4, 4, 0, 0, 4, 4,
+        0, 0,
+    ];
+    for (i, bundle) in buf.chunks_exact_mut(16).enumerate() {
+        let mask = BRANCH_TABLE[(bundle[0] & 0x1F) as usize];
+        for slot in 0..3 {
+            if (mask >> slot) & 1 == 0 {
+                continue;
+            }
+            let bit_pos = 5 + 41 * slot;
+            let byte_pos = (bit_pos >> 3) as usize;
+            let bit_res = bit_pos & 0x7;
+            let mut instruction = 0u64;
+            for j in 0..6 {
+                instruction |= (bundle[byte_pos + j] as u64) << (8 * j);
+            }
+            let mut inst_norm = instruction >> bit_res;
+            if (inst_norm >> 37) & 0xF == 0x5 && (inst_norm >> 9) & 0x7 == 0 {
+                let mut src = ((inst_norm >> 13) & 0xFFFFF) as u32;
+                src |= (((inst_norm >> 36) & 1) as u32) << 20;
+                src <<= 4;
+                let dest = src.wrapping_sub(now_pos.wrapping_add((i * 16) as u32)) >> 4;
+                inst_norm &= !(0x8FFFFFu64 << 13);
+                inst_norm |= ((dest & 0xFFFFF) as u64) << 13;
+                inst_norm |= ((dest & 0x100000) as u64) << (36 - 20);
+                instruction &= (1u64 << bit_res) - 1;
+                instruction |= inst_norm << bit_res;
+                for j in 0..6 {
+                    bundle[byte_pos + j] = (instruction >> (8 * j)) as u8;
+                }
+            }
+        }
+    }
+}
+
+fn arm(buf: &mut [u8], now_pos: u32) {
+    for (i, chunk) in buf.chunks_exact_mut(4).enumerate() {
+        if chunk[3] == 0xEB {
+            let src = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], 0]) << 2;
+            let dest = src.wrapping_sub(now_pos.wrapping_add((i * 4 + 8) as u32)) >> 2;
+            chunk[..3].copy_from_slice(&dest.to_le_bytes()[..3]);
+        }
+    }
+}
+
+fn armthumb(buf: &mut [u8], now_pos: u32) {
+    let mut i = 0;
+    while i + 4 <= buf.len() {
+        if buf[i + 1] & 0xF8 == 0xF0 && buf[i + 3] & 0xF8 == 0xF8 {
+            let src = (((buf[i + 1] as u32) & 7) << 19)
+                | ((buf[i] as u32) << 11)
+                | (((buf[i + 3] as u32) & 7) << 8)
+                | buf[i + 2] as u32;
+            let dest = (src << 1).wrapping_sub(now_pos.wrapping_add(i as u32 + 4)) >> 1;
+            buf[i + 1] = 0xF0 | ((dest >> 19) & 0x7) as u8;
+            buf[i] = (dest >> 11) as u8;
+            buf[i + 3] = 0xF8 | ((dest >> 8) & 0x7) as u8;
+            buf[i + 2] = dest as u8;
+            i += 2;
+        }
+        i += 2;
+    }
+}
+
+fn sparc(buf: &mut [u8], now_pos: u32) {
+    for (i, chunk) in buf.chunks_exact_mut(4).enumerate() {
+        if (chunk[0] == 0x40 && chunk[1] & 0xC0 == 0x00)
+            || (chunk[0] == 0x7F && chunk[1] & 0xC0 == 0xC0)
+        {
+            let src = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) << 2;
+            let dest = src.wrapping_sub(now_pos.wrapping_add((i * 4) as u32)) >> 2;
+            let dest = ((((dest >> 22) & 1).wrapping_neg() << 22) & 0x3FFF_FFFF)
+                | (dest & 0x3F_FFFF)
+                | 0x4000_0000;
+            chunk.copy_from_slice(&dest.to_be_bytes());
+        }
+    }
+}
+
+fn arm64(buf: &mut [u8], now_pos: u32) {
+    for (i, chunk) in buf.chunks_exact_mut(4).enumerate() {
+        let pc = now_pos.wrapping_add((i * 4) as u32);
+        let mut instr = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        if instr >> 26 == 0x25 {
+            // BL
+            let src = instr;
+            instr = 0x9400_0000 | (src.wrapping_sub(pc >> 2) & 0x03FF_FFFF);
+        } else if instr & 0x9F00_0000 == 0x9000_0000 {
+            // ADRP
+            let src = ((instr >> 29) & 3) | ((instr >> 3) & 0x001F_FFFC);
+            if src.wrapping_add(0x0002_0000) & 0x001C_0000 != 0 {
+                continue;
+            }
+            let dest = src.wrapping_sub(pc >> 12);
+            instr &= 0x9000_001F;
+            instr |= (dest & 3) << 29;
+            instr |= (dest & 0x0003_FFFC) << 3;
+            instr |= (dest & 0x0002_0000).wrapping_neg() & 0x00E0_0000;
+        } else {
+            continue;
+        }
+        chunk.copy_from_slice(&instr.to_le_bytes());
+    }
+}
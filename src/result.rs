@@ -32,6 +32,51 @@ pub enum ZipError {
 
     /// The password provided is incorrect
     InvalidPassword,
+
+    /// archive exceeds a configured limit: {0}
+    LimitExceeded(&'static str),
+
+    /// decompressed data exceeds the configured limit
+    DecompressionLimitExceeded,
+
+    /// Invalid checksum for {entry}: expected {expected:#010x}, found {actual:#010x}
+    Crc32Mismatch {
+        /// The CRC-32 recorded in the archive
+        expected: u32,
+        /// The CRC-32 of the data that was read
+        actual: u32,
+        /// The name of the entry
+        entry: String,
+    },
+
+    /// Size mismatch for {entry}: expected {expected} bytes, found {actual}
+    SizeMismatch {
+        /// The uncompressed size recorded in the archive
+        expected: u64,
+        /// The number of bytes the data decompressed to, so far
+        actual: u64,
+        /// The name of the entry
+        entry: String,
+    },
+
+    /// unsupported PKWARE Strong Encryption: {0}
+    StrongEncryptionUnsupported(&'static str),
+
+    /// Local header of {entry} doesn't match the central directory: the {field} differs
+    HeaderMismatch {
+        /// The field that differs, such as `name` or `compressed size`
+        field: &'static str,
+        /// The name of the entry, as recorded in the central directory
+        entry: String,
+    },
+
+    /// Entry name {entry:?} can't be extracted: {reason}
+    InvalidEntryName {
+        /// What's wrong with the name
+        reason: &'static str,
+        /// The name of the entry
+        entry: String,
+    },
 }
 
 impl ZipError {
@@ -47,6 +92,18 @@ impl ZipError {
     /// # ()
     /// ```
     pub const PASSWORD_REQUIRED: &'static str = "Password required to decrypt file";
+
+    /// Recovers a [`ZipError::DecompressionLimitExceeded`] that was returned through a reader.
+    pub(crate) fn from_read_error(err: io::Error) -> ZipError {
+        if matches!(
+            err.get_ref().and_then(|inner| inner.downcast_ref()),
+            Some(ZipError::DecompressionLimitExceeded)
+        ) {
+            ZipError::DecompressionLimitExceeded
+        } else {
+            ZipError::Io(err)
+        }
+    }
 }
 
 impl From<ZipError> for io::Error {
@@ -57,6 +114,13 @@ impl From<ZipError> for io::Error {
             ZipError::UnsupportedArchive(_) => io::ErrorKind::Unsupported,
             ZipError::FileNotFound => io::ErrorKind::NotFound,
             ZipError::InvalidPassword => io::ErrorKind::InvalidInput,
+            ZipError::LimitExceeded(_) => io::ErrorKind::InvalidData,
+            ZipError::DecompressionLimitExceeded => io::ErrorKind::InvalidData,
+            ZipError::Crc32Mismatch { .. } => io::ErrorKind::InvalidData,
+            ZipError::SizeMismatch { .. } => io::ErrorKind::InvalidData,
+            ZipError::HeaderMismatch { .. } => io::ErrorKind::InvalidData,
+            ZipError::InvalidEntryName { .. } => io::ErrorKind::InvalidData,
+            ZipError::StrongEncryptionUnsupported(_) => io::ErrorKind::Unsupported,
         };
 
         io::Error::new(kind, err)
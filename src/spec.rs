@@ -48,6 +48,13 @@ impl Magic {
     pub const CENTRAL_DIRECTORY_END_SIGNATURE: Self = Self::literal(0x06054b50);
     pub const ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE: Self = Self::literal(0x06064b50);
     pub const ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE: Self = Self::literal(0x07064b50);
+    pub const DATA_DESCRIPTOR_SIGNATURE: Self = Self::literal(0x08074b50);
+    /// Starts the first volume of a split archive (APPNOTE 8.5.3).
+    pub const SPLIT_ARCHIVE_SIGNATURE: Self = Self::DATA_DESCRIPTOR_SIGNATURE;
+    /// Replaces [`Magic::SPLIT_ARCHIVE_SIGNATURE`] when the archive fits in one volume.
+    pub const SINGLE_VOLUME_SPLIT_SIGNATURE: Self = Self::literal(0x30304b50);
+    /// Precedes a central directory encrypted with PKWARE's Strong Encryption (APPNOTE 4.3.11).
+    pub const ARCHIVE_EXTRA_DATA_SIGNATURE: Self = Self::literal(0x08064b50);
 }
 
 /// Similar to [`Magic`], but used for extra field tags as per section 4.5.3 of APPNOTE.TXT.
@@ -289,6 +296,15 @@ pub(crate) struct Zip32CentralDirectoryEnd {
 }
 
 impl Zip32CentralDirectoryEnd {
+    /// Whether a count, size or offset holds its maximum value, which means the real value is in
+    /// the Zip64 end of central directory record.
+    pub(crate) const fn defers_to_zip64(&self) -> bool {
+        self.number_of_files_on_this_disk as usize == ZIP64_ENTRY_THR
+            || self.number_of_files as usize == ZIP64_ENTRY_THR
+            || self.central_directory_size as u64 == ZIP64_BYTES_THR
+            || self.central_directory_offset as u64 == ZIP64_BYTES_THR
+    }
+
     fn block_and_comment(self) -> ZipResult<(Zip32CDEBlock, Box<[u8]>)> {
         let Self {
             disk_number,
@@ -353,7 +369,9 @@ impl Zip32CentralDirectoryEnd {
             return Err(ZipError::InvalidArchive("Invalid zip header"));
         }
 
-        let search_lower_bound = 0;
+        // The record can only be followed by its comment, which is at most 64 KiB long
+        let search_lower_bound =
+            file_length.saturating_sub(u16::MAX as u64 + mem::size_of::<Zip32CDEBlock>() as u64);
 
         const END_WINDOW_SIZE: usize = 512;
         /* TODO: use static_assertions!() */
@@ -699,7 +717,15 @@ pub(crate) fn is_dir(filename: &str) -> bool {
     filename
         .chars()
         .next_back()
-        .map_or(false, |c| c == '/' || c == '\\')
+        .is_some_and(|c| c == '/' || c == '\\')
+}
+
+/// Whether the entry was added by macOS's Archive Utility to hold metadata: either something
+/// under `__MACOSX/` or an AppleDouble file whose name starts with `._`.
+pub(crate) fn is_macos_metadata(filename: &str) -> bool {
+    let mut components = filename.split(['/', '\\']);
+    components.clone().next() == Some("__MACOSX")
+        || components.any(|component| component.starts_with("._"))
 }
 
 #[cfg(test)]
@@ -2,6 +2,7 @@
 use crate::cp437::FromCp437;
 use crate::write::{FileOptionExtension, FileOptions};
 use path::{Component, Path, PathBuf};
+use std::borrow::Cow;
 use std::fmt;
 use std::fmt::{Debug, Formatter};
 use std::mem;
@@ -18,6 +19,7 @@ pub(crate) mod ffi {
     pub const S_IFDIR: u32 = 0o0040000;
     pub const S_IFREG: u32 = 0o0100000;
     pub const S_IFLNK: u32 = 0o0120000;
+    pub const S_IFMT: u32 = 0o0170000;
 }
 
 use crate::extra_fields::ExtraField;
@@ -437,6 +439,13 @@ pub struct ZipFileData {
     pub compression_method: crate::compression::CompressionMethod,
     /// Compression level to store the file
     pub compression_level: Option<i64>,
+    /// Compression hint from the general purpose bit flags, for Deflate entries
+    pub deflate_hint: Option<DeflateHint>,
+    /// General purpose bit flags, as read from the archive. Zero for files being written, whose
+    /// flags are derived from the other fields.
+    pub general_purpose_flags: u16,
+    /// The "version needed to extract" set in the options the file was written with
+    pub explicit_version_needed: Option<u16>,
     /// Last modified time. This will only have a 2 second precision.
     pub last_modified_time: Option<DateTime>,
     /// CRC32 checksum
@@ -478,15 +487,47 @@ pub struct ZipFileData {
     pub extra_fields: Vec<ExtraField>,
 }
 
+/// The MS-DOS attribute bit marking a directory, in the low byte of the external attributes.
+pub(crate) const DOS_DIRECTORY_ATTRIBUTE: u32 = 0x10;
+
+/// The longest entry name, in bytes, that can be extracted: `MAX_PATH` on Windows and
+/// `PATH_MAX` elsewhere.
+#[cfg(windows)]
+pub(crate) const MAX_PATH_LEN: usize = 260;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub(crate) const MAX_PATH_LEN: usize = 1024;
+#[cfg(not(any(windows, target_os = "macos", target_os = "ios")))]
+pub(crate) const MAX_PATH_LEN: usize = 4096;
+
+/// Returns `name` as a path if it's relative and doesn't lead out of the directory it's relative
+/// to.
+fn enclosed_path(name: String) -> Option<PathBuf> {
+    let path = PathBuf::from(name);
+    let mut depth = 0usize;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return None,
+            Component::ParentDir => depth = depth.checked_sub(1)?,
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+        }
+    }
+    Some(path)
+}
+
 impl ZipFileData {
     /// Get the starting offset of the data of the compressed file
     pub fn data_start(&self) -> u64 {
         *self.data_start.get().unwrap()
     }
 
-    #[allow(dead_code)]
+    /// Whether the entry is a directory, either because its name ends with a separator or because
+    /// its external attributes say so.
     pub fn is_dir(&self) -> bool {
         is_dir(&self.file_name)
+            || self
+                .unix_mode()
+                .is_some_and(|mode| mode & ffi::S_IFMT == ffi::S_IFDIR)
     }
 
     pub fn file_name_sanitized(&self) -> PathBuf {
@@ -520,17 +561,48 @@ impl ZipFileData {
         if self.file_name.contains('\0') {
             return None;
         }
-        let path = PathBuf::from(self.file_name.to_string());
-        let mut depth = 0usize;
-        for component in path.components() {
-            match component {
-                Component::Prefix(_) | Component::RootDir => return None,
-                Component::ParentDir => depth = depth.checked_sub(1)?,
-                Component::Normal(_) => depth += 1,
-                Component::CurDir => (),
+        enclosed_path(self.file_name.to_string())
+    }
+
+    /// The path to extract the entry to, relative to the extraction directory.
+    ///
+    /// Backslashes are treated as separators, and drive letters such as `C:` are removed along
+    /// with the separators after them, whatever the platform. Names with control characters or
+    /// longer than [`MAX_PATH_LEN`] are rejected, or fixed up if `sanitize` is set. UNC and
+    /// device paths, and names that would lead out of the extraction directory, are always
+    /// rejected.
+    pub(crate) fn extraction_path(&self, sanitize: bool) -> ZipResult<PathBuf> {
+        let invalid = |reason| ZipError::InvalidEntryName {
+            reason,
+            entry: self.file_name.to_string(),
+        };
+        let mut name = self.file_name.replace('\\', "/");
+        if name.starts_with("//") {
+            // `\\server\share`, `\\?\C:\` and `\\.\device` all start this way
+            return Err(invalid("it's a UNC or device path"));
+        }
+        if let [drive, b':', ..] = name.as_bytes() {
+            if drive.is_ascii_alphabetic() {
+                name = name[2..].trim_start_matches('/').to_string();
+            }
+        }
+        if name.contains(char::is_control) {
+            if !sanitize {
+                return Err(invalid("it contains control characters"));
+            }
+            name.retain(|c| !c.is_control());
+        }
+        if name.len() > MAX_PATH_LEN {
+            if !sanitize {
+                return Err(invalid("it's too long"));
+            }
+            let mut len = MAX_PATH_LEN;
+            while !name.is_char_boundary(len) {
+                len -= 1;
             }
+            name.truncate(len);
         }
-        Some(path)
+        enclosed_path(name).ok_or(ZipError::InvalidArchive("Invalid file path"))
     }
 
     /// Get unix mode for the file
@@ -543,7 +615,7 @@ impl ZipFileData {
             System::Unix => Some(self.external_attributes >> 16),
             System::Dos => {
                 // Interpret MS-DOS directory bit
-                let mut mode = if 0x10 == (self.external_attributes & 0x10) {
+                let mut mode = if self.external_attributes & DOS_DIRECTORY_ATTRIBUTE != 0 {
                     ffi::S_IFDIR | 0o0775
                 } else {
                     ffi::S_IFREG | 0o0664
@@ -558,8 +630,15 @@ impl ZipFileData {
         }
     }
 
-    /// PKZIP version needed to open this file (from APPNOTE 4.4.3.2).
+    /// PKZIP version needed to open this file: the one set when writing it, if any, otherwise
+    /// [`ZipFileData::min_version_needed`].
     pub fn version_needed(&self) -> u16 {
+        self.min_version_needed()
+            .max(self.explicit_version_needed.unwrap_or_default())
+    }
+
+    /// The lowest PKZIP version supporting this file's features (from APPNOTE 4.4.3.2).
+    pub(crate) fn min_version_needed(&self) -> u16 {
         let compression_version: u16 = match self.compression_method {
             CompressionMethod::Stored => MIN_VERSION.into(),
             #[cfg(feature = "_deflate-any")]
@@ -628,16 +707,26 @@ impl ZipFileData {
         S: Into<Box<str>>,
     {
         let permissions = options.permissions.unwrap_or(0o100644);
+        // Also set the MS-DOS directory attribute, for tools that ignore the Unix mode
+        let dos_attributes = if permissions & 0o170000 == S_IFDIR {
+            DOS_DIRECTORY_ATTRIBUTE
+        } else {
+            0
+        };
         let file_name: Box<str> = name.into();
         let file_name_raw: Box<[u8]> = file_name.bytes().collect();
+        let file_comment: Box<str> = options.comment.unwrap_or_default().into();
         let mut local_block = ZipFileData {
             system: System::Unix,
             version_made_by: DEFAULT_VERSION,
             encrypted: options.encrypt_with.is_some(),
             using_data_descriptor: false,
-            is_utf8: !file_name.is_ascii(),
+            is_utf8: !file_name.is_ascii() || !file_comment.is_ascii(),
             compression_method,
             compression_level: options.compression_level,
+            deflate_hint: None,
+            general_purpose_flags: 0,
+            explicit_version_needed: options.version_needed,
             last_modified_time: Some(options.last_modified_time),
             crc32: raw_values.crc32,
             compressed_size: raw_values.compressed_size,
@@ -646,11 +735,11 @@ impl ZipFileData {
             file_name_raw,
             extra_field: Some(extra_field.to_vec().into()),
             central_extra_field: options.extended_options.central_extra_data().cloned(),
-            file_comment: String::with_capacity(0).into_boxed_str(),
+            file_comment,
             header_start,
             data_start: OnceLock::new(),
             central_header_start: 0,
-            external_attributes: permissions << 16,
+            external_attributes: permissions << 16 | dos_attributes,
             large_file: options.large_file,
             aes_mode,
             extra_fields: Vec::new(),
@@ -687,14 +776,8 @@ impl ZipFileData {
             ));
         }
 
-        /* FIXME: these were previously incorrect: add testing! */
         /* flags & (1 << 3) != 0 */
         let using_data_descriptor: bool = flags & (1 << 3) == 1 << 3;
-        if using_data_descriptor {
-            return Err(ZipError::UnsupportedArchive(
-                "The file length is not available in the local header",
-            ));
-        }
 
         /* flags & (1 << 1) != 0 */
         let is_utf8: bool = flags & (1 << 11) != 0;
@@ -722,6 +805,9 @@ impl ZipFileData {
             is_utf8,
             compression_method,
             compression_level: None,
+            deflate_hint: DeflateHint::from_flags(compression_method, flags),
+            general_purpose_flags: flags,
+            explicit_version_needed: None,
             last_modified_time: DateTime::try_from_msdos(last_mod_date, last_mod_time).ok(),
             crc32,
             compressed_size: compressed_size.into(),
@@ -757,14 +843,20 @@ impl ZipFileData {
     }
 
     fn flags(&self) -> u16 {
-        let utf8_bit: u16 = if self.is_utf8() && !self.is_ascii() {
+        let utf8_bit: u16 = if self.is_utf8() && !(self.is_ascii() && self.file_comment.is_ascii())
+        {
             1u16 << 11
         } else {
             0
         };
         let encrypted_bit: u16 = if self.encrypted { 1u16 << 0 } else { 0 };
+        let data_descriptor_bit: u16 = if self.using_data_descriptor {
+            1u16 << 3
+        } else {
+            0
+        };
 
-        utf8_bit | encrypted_bit
+        utf8_bit | encrypted_bit | data_descriptor_bit
     }
 
     fn clamp_size_field(&self, field: u64) -> u32 {
@@ -780,7 +872,7 @@ impl ZipFileData {
         let uncompressed_size: u32 = self.clamp_size_field(self.uncompressed_size);
 
         let extra_block_len: usize = self
-            .zip64_extra_field_block()
+            .local_zip64_extra_field_block()
             .map(|block| block.full_size())
             .unwrap_or(0);
         let extra_field_length: u16 = (self.extra_field_len() + extra_block_len)
@@ -805,8 +897,47 @@ impl ZipFileData {
         })
     }
 
+    /// The extra field written to the central directory before `central_extra_field`.
+    ///
+    /// This is `extra_field`, except that extended timestamp fields (0x5455) only keep the
+    /// modification time, as described in <https://libzip.org/specifications/extrafld.txt>.
+    pub(crate) fn central_directory_extra_field(&self) -> Cow<'_, [u8]> {
+        let Some(extra_field) = &self.extra_field else {
+            return Cow::Borrowed(&[]);
+        };
+        let mut central = Vec::with_capacity(extra_field.len());
+        let mut changed = false;
+        let mut rest = &extra_field[..];
+        while let [id_0, id_1, len_0, len_1, data @ ..] = rest {
+            let id = u16::from_le_bytes([*id_0, *id_1]);
+            let len = u16::from_le_bytes([*len_0, *len_1]) as usize;
+            if data.len() < len {
+                break;
+            }
+            let central_len = match (id, data.first()) {
+                (0x5455, Some(flags)) if flags & 1 != 0 => len.min(5),
+                (0x5455, Some(_)) => 1,
+                _ => len,
+            };
+            changed |= central_len != len;
+            central.extend_from_slice(&id.to_le_bytes());
+            central.extend_from_slice(&(central_len as u16).to_le_bytes());
+            central.extend_from_slice(&data[..central_len]);
+            rest = &data[len..];
+        }
+        if changed && rest.is_empty() {
+            Cow::Owned(central)
+        } else {
+            Cow::Borrowed(extra_field)
+        }
+    }
+
     pub(crate) fn block(&self, zip64_extra_field_length: u16) -> ZipResult<ZipCentralEntryBlock> {
-        let extra_field_len: u16 = self.extra_field_len().try_into().unwrap();
+        let extra_field_len: u16 = self
+            .central_directory_extra_field()
+            .len()
+            .try_into()
+            .unwrap();
         let central_extra_field_len: u16 = self.central_extra_field_len().try_into().unwrap();
         let last_modified_time = self
             .last_modified_time
@@ -837,7 +968,7 @@ impl ZipFileData {
                 .ok_or(ZipError::InvalidArchive(
                     "Extra field length in central directory exceeds 64KiB",
                 ))?,
-            file_comment_length: self.file_comment.as_bytes().len().try_into().unwrap(),
+            file_comment_length: self.file_comment.len().try_into().unwrap(),
             disk_number: 0,
             internal_file_attributes: 0,
             external_file_attributes: self.external_attributes,
@@ -849,35 +980,37 @@ impl ZipFileData {
         })
     }
 
-    pub(crate) fn zip64_extra_field_block(&self) -> Option<Zip64ExtraFieldBlock> {
-        let uncompressed_size: Option<u64> =
-            if self.uncompressed_size >= spec::ZIP64_BYTES_THR || self.large_file {
-                Some(spec::ZIP64_BYTES_THR)
-            } else {
-                None
-            };
-        let compressed_size: Option<u64> =
-            if self.compressed_size >= spec::ZIP64_BYTES_THR || self.large_file {
-                Some(spec::ZIP64_BYTES_THR)
-            } else {
-                None
-            };
-        let header_start: Option<u64> = if self.header_start >= spec::ZIP64_BYTES_THR {
-            Some(spec::ZIP64_BYTES_THR)
-        } else {
-            None
-        };
-
-        let mut size: u16 = 0;
-        if uncompressed_size.is_some() {
-            size += mem::size_of::<u64>() as u16;
-        }
-        if compressed_size.is_some() {
-            size += mem::size_of::<u64>() as u16;
-        }
-        if header_start.is_some() {
-            size += mem::size_of::<u64>() as u16;
+    /// The Zip64 extra field for the local header. When present, it holds both sizes, as
+    /// APPNOTE 4.5.3 requires, so that they can be filled in once the data has been written.
+    pub(crate) fn local_zip64_extra_field_block(&self) -> Option<Zip64ExtraFieldBlock> {
+        if !self.large_file
+            && self.uncompressed_size < spec::ZIP64_BYTES_THR
+            && self.compressed_size < spec::ZIP64_BYTES_THR
+        {
+            return None;
         }
+        Some(Zip64ExtraFieldBlock {
+            magic: spec::ExtraFieldMagic::ZIP64_EXTRA_FIELD_TAG,
+            size: 2 * mem::size_of::<u64>() as u16,
+            uncompressed_size: Some(self.uncompressed_size),
+            compressed_size: Some(self.compressed_size),
+            header_start: None,
+        })
+    }
+
+    /// The Zip64 extra field for the central directory, holding only the values that don't fit
+    /// in their central header fields.
+    pub(crate) fn zip64_extra_field_block(&self) -> Option<Zip64ExtraFieldBlock> {
+        let overflowing = |value: u64| Some(value).filter(|value| *value >= spec::ZIP64_BYTES_THR);
+        let uncompressed_size = overflowing(self.uncompressed_size);
+        let compressed_size = overflowing(self.compressed_size);
+        let header_start = overflowing(self.header_start);
+
+        let size = [uncompressed_size, compressed_size, header_start]
+            .iter()
+            .flatten()
+            .count() as u16
+            * mem::size_of::<u64>() as u16;
         if size == 0 {
             return None;
         }
@@ -1049,6 +1182,39 @@ pub enum AesVendorVersion {
     Ae2 = 0x0002,
 }
 
+/// The compression option a Deflate entry was written with, from bits 1 and 2 of the general
+/// purpose bit flags.
+///
+/// Zip doesn't record a numeric compression level, but this hint tells roughly how hard the
+/// compressor tried.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeflateHint {
+    /// Normal compression
+    Normal,
+    /// Maximum compression
+    Maximum,
+    /// Fast compression
+    Fast,
+    /// Super fast compression
+    SuperFast,
+}
+
+impl DeflateHint {
+    /// Decodes the hint from the general purpose bit flags. The bits only have this meaning for
+    /// Deflate and Deflate64 entries, so `None` is returned for any other method.
+    pub(crate) fn from_flags(compression_method: CompressionMethod, flags: u16) -> Option<Self> {
+        if !matches!(compression_method.serialize_to_u16(), 8 | 9) {
+            return None;
+        }
+        Some(match (flags >> 1) & 0b11 {
+            0b00 => Self::Normal,
+            0b01 => Self::Maximum,
+            0b10 => Self::Fast,
+            _ => Self::SuperFast,
+        })
+    }
+}
+
 /// AES variant used.
 #[derive(Copy, Clone, Debug)]
 #[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
@@ -1106,6 +1272,9 @@ mod test {
             is_utf8: true,
             compression_method: crate::compression::CompressionMethod::Stored,
             compression_level: None,
+            deflate_hint: None,
+            general_purpose_flags: 0,
+            explicit_version_needed: None,
             last_modified_time: None,
             crc32: 0,
             compressed_size: 0,
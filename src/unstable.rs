@@ -8,6 +8,7 @@ use std::path::{Component, Path, MAIN_SEPARATOR};
 /// Provides high level API for reading from a stream.
 pub mod stream {
     pub use crate::read::stream::*;
+    pub use crate::write::stream::ZipStreamWriter;
 }
 /// Types for creating ZIP archives.
 pub mod write {
@@ -20,7 +21,7 @@ pub mod write {
         fn with_deprecated_encryption(self, password: &[u8]) -> Self;
     }
     impl<'k, T: FileOptionExtension> FileOptionsExt for FileOptions<'k, T> {
-        fn with_deprecated_encryption(self, password: &[u8]) -> FileOptions<'static, T> {
+        fn with_deprecated_encryption(self, password: &[u8]) -> Self {
             self.with_deprecated_encryption(password)
         }
     }
@@ -2,9 +2,10 @@
 
 #[cfg(feature = "aes-crypto")]
 use crate::aes::AesWriter;
-use crate::compression::CompressionMethod;
+use crate::compression::{CompressionMethod, CompressionRegistry, EncoderFactory};
 use crate::read::{
-    find_content, parse_single_extra_field, Config, ZipArchive, ZipFile, ZipFileReader,
+    find_content, make_crypto_reader, make_reader, parse_single_extra_field, Config, ReadOptions,
+    ZipArchive, ZipFile, ZipFileReader,
 };
 use crate::result::{ZipError, ZipResult};
 use crate::spec::{self, FixedSizeBlock, Zip32CDEBlock};
@@ -18,7 +19,7 @@ use crate::write::ffi::S_IFLNK;
 use core::num::NonZeroU64;
 use crc32fast::Hasher;
 use indexmap::IndexMap;
-use std::borrow::ToOwned;
+use std::borrow::{Cow, ToOwned};
 use std::default::Default;
 use std::fmt::{Debug, Formatter};
 use std::io;
@@ -28,7 +29,7 @@ use std::io::{BufReader, SeekFrom};
 use std::marker::PhantomData;
 use std::mem;
 use std::str::{from_utf8, Utf8Error};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[cfg(feature = "deflate-flate2")]
 use flate2::{write::DeflateEncoder, Compression};
@@ -47,6 +48,10 @@ use std::path::Path;
 #[cfg(feature = "zstd")]
 use zstd::stream::write::Encoder as ZstdEncoder;
 
+pub mod name_transform;
+mod split;
+pub(crate) mod stream;
+
 enum MaybeEncrypted<W> {
     Unencrypted(W),
     #[cfg(feature = "aes-crypto")]
@@ -98,6 +103,74 @@ enum GenericZipWriter<W: Write + Seek> {
     Bzip2(BzEncoder<MaybeEncrypted<W>>),
     #[cfg(feature = "zstd")]
     Zstd(ZstdEncoder<'static, MaybeEncrypted<W>>),
+    Custom(CustomEncoder<MaybeEncrypted<W>>),
+}
+
+/// An encoder from a [`CompressionRegistry`]. It writes to a buffer, which is moved to the
+/// underlying writer after each call, since it can't own a writer that isn't `'static`.
+struct CustomEncoder<W> {
+    encoder: Box<dyn Write + Send>,
+    buffer: Arc<Mutex<Vec<u8>>>,
+    inner: W,
+}
+
+/// The writer given to a custom encoder.
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Calls `factory` before switching writers, so that its errors leave the archive usable.
+fn prepare_custom_writer<W: Write + Seek>(
+    factory: &EncoderFactory,
+) -> ZipResult<SwitchWriterFunction<W>> {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let encoder = factory(Box::new(SharedBuffer(buffer.clone())))?;
+    Ok(Box::new(move |inner| {
+        GenericZipWriter::Custom(CustomEncoder {
+            encoder,
+            buffer,
+            inner,
+        })
+    }))
+}
+
+impl<W: Write> CustomEncoder<W> {
+    fn write_buffered(&mut self) -> io::Result<()> {
+        let buffered = mem::take(&mut *self.buffer.lock().unwrap_or_else(|e| e.into_inner()));
+        self.inner.write_all(&buffered)
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        drop(mem::replace(&mut self.encoder, Box::new(io::sink())));
+        self.write_buffered()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for CustomEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.encoder.write(buf)?;
+        self.write_buffered()?;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.flush()?;
+        self.write_buffered()?;
+        self.inner.flush()
+    }
 }
 
 impl<W: Write + Seek> Debug for GenericZipWriter<W> {
@@ -117,6 +190,7 @@ impl<W: Write + Seek> Debug for GenericZipWriter<W> {
             GenericZipWriter::Bzip2(w) => f.write_fmt(format_args!("Bzip2({:?})", w.get_ref())),
             #[cfg(feature = "zstd")]
             GenericZipWriter::Zstd(w) => f.write_fmt(format_args!("Zstd({:?})", w.get_ref())),
+            GenericZipWriter::Custom(w) => f.write_fmt(format_args!("Custom({:?})", w.inner)),
         }
     }
 }
@@ -159,15 +233,28 @@ pub(crate) mod zip_writer {
         pub(super) writing_to_file: bool,
         pub(super) writing_raw: bool,
         pub(super) comment: Box<[u8]>,
-        pub(super) flush_on_finish_file: bool,
+        pub(super) flush_policy: FlushPolicy,
+        /// Entries are followed by a data descriptor instead of seeking back to update their
+        /// local headers.
+        pub(super) streaming: bool,
+        pub(super) zip64_mode: Zip64Mode,
+        /// The size of each volume, if the archive is written by a [`SplitWriter`].
+        pub(super) volume_size: Option<u64>,
+        /// The time every entry is given, if the output is made reproducible.
+        pub(super) reproducible_time: Option<DateTime>,
+        pub(super) name_transform: Option<NameTransform>,
+        pub(super) registry: Option<Arc<CompressionRegistry>>,
     }
 
     impl<W: Write + Seek> Debug for ZipWriter<W> {
         fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
             f.write_fmt(format_args!(
-                "ZipWriter {{files: {:?}, stats: {:?}, writing_to_file: {}, writing_raw: {}, comment: {:?}, flush_on_finish_file: {}}}",
+                "ZipWriter {{files: {:?}, stats: {:?}, writing_to_file: {}, writing_raw: {}, comment: {:?}, flush_policy: {:?}, streaming: {}, zip64_mode: {:?}, volume_size: {:?}, reproducible_time: {:?}, name_transform: {}, registry: {:?}}}",
                 self.files, self.stats, self.writing_to_file, self.writing_raw,
-                self.comment, self.flush_on_finish_file))
+                self.comment, self.flush_policy, self.streaming, self.zip64_mode,
+                self.volume_size, self.reproducible_time,
+                if self.name_transform.is_some() { "Some(..)" } else { "None" },
+                self.registry))
         }
     }
 }
@@ -181,8 +268,51 @@ use crate::unstable::LittleEndianWriteExt;
 use crate::write::GenericZipWriter::{Closed, Storer};
 use crate::zipcrypto::ZipCryptoKeys;
 use crate::CompressionMethod::Stored;
+use name_transform::NameTransform;
+pub use split::SplitWriter;
 pub use zip_writer::ZipWriter;
 
+/// When a [`ZipWriter`] uses the Zip64 extensions for archives and entries beyond the limits of
+/// the original format: 65,535 entries, and 4 GiB for sizes and offsets.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Zip64Mode {
+    /// Use Zip64 for every entry, and write the Zip64 end of central directory records even if
+    /// the archive is small.
+    Always,
+    /// Use Zip64 only where a limit is crossed, or for entries with
+    /// [`FileOptions::large_file`] set.
+    #[default]
+    Auto,
+    /// Never use Zip64. Crossing a limit, or setting [`FileOptions::large_file`], is an error.
+    Never,
+}
+
+/// What [`ZipWriter::merge_archive_with`] does with an entry whose name is already in the
+/// archive.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NameCollision {
+    /// Replace the existing entry, keeping its place in the central directory.
+    #[default]
+    Overwrite,
+    /// Keep the existing entry and leave out the new one.
+    Skip,
+    /// Add the new entry under a free name, made by appending ` (1)`, ` (2)` and so on to its
+    /// file stem, such as `dir/file (1).txt`. Directories are merged instead, as with
+    /// [`NameCollision::Skip`].
+    Rename,
+}
+
+/// When a [`ZipWriter`] flushes its inner writer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Flush after each entry is finished, so that its bytes reach the inner writer before the
+    /// next one starts. Suits writers that send the archive on as it's written.
+    EachEntry,
+    /// Flush only once the archive is finished.
+    #[default]
+    OnFinish,
+}
+
 #[derive(Default, Debug)]
 struct ZipWriterStats {
     hasher: Hasher,
@@ -264,8 +394,22 @@ pub struct FileOptions<'k, T: FileOptionExtension> {
     pub(crate) encrypt_with: Option<EncryptWith<'k>>,
     pub(crate) extended_options: T,
     pub(crate) alignment: u16,
+    pub(crate) unix_metadata: Option<UnixMetadata>,
+    pub(crate) version_needed: Option<u16>,
+    pub(crate) comment: Option<&'k str>,
     #[cfg(feature = "deflate-zopfli")]
     pub(super) zopfli_buffer_size: Option<usize>,
+    #[cfg(feature = "zstd")]
+    pub(super) zstd_window_log: Option<u32>,
+}
+
+/// Unix ownership and access time, written in the extended timestamp (0x5455) and Info-ZIP Unix
+/// (0x7875) extra fields.
+#[derive(Clone, Debug, Copy)]
+pub(crate) struct UnixMetadata {
+    uid: u32,
+    gid: u32,
+    atime: u32,
 }
 /// Simple File Options. Can be copied and good for simple writing zip files
 pub type SimpleFileOptions = FileOptions<'static, ()>;
@@ -306,7 +450,7 @@ impl ExtendedFileOptions {
                 }
             };
             Self::add_extra_data_unchecked(vec, header_id, data)?;
-            Self::validate_extra_data(vec, 0)?;
+            Self::validate_extra_data(vec, 0, false)?;
             Ok(())
         }
     }
@@ -323,7 +467,11 @@ impl ExtendedFileOptions {
         Ok(())
     }
 
-    fn validate_extra_data(data: &[u8], reserved: u64) -> ZipResult<()> {
+    /// Checks that `data` is a well-formed sequence of extra fields. Unless `copied` is true, the
+    /// fields must not use an ID reserved for other tools; fields copied from an existing archive
+    /// are allowed to.
+    #[cfg_attr(feature = "unreserved", allow(unused_variables))]
+    fn validate_extra_data(data: &[u8], reserved: u64, copied: bool) -> ZipResult<()> {
         let len = data.len() as u64;
         if len == 0 {
             return Ok(());
@@ -347,10 +495,7 @@ impl ExtendedFileOptions {
             {
                 use crate::unstable::LittleEndianReadExt;
                 let header_id = data.read_u16_le()?;
-                if EXTRA_FIELD_MAPPING
-                    .iter()
-                    .any(|&mapped| mapped == header_id)
-                {
+                if !copied && EXTRA_FIELD_MAPPING.contains(&header_id) {
                     return Err(ZipError::Io(io::Error::new(
                         io::ErrorKind::Other,
                         format!(
@@ -389,8 +534,13 @@ impl<'a> arbitrary::Arbitrary<'a> for FileOptions<'a, ExtendedFileOptions> {
             large_file: bool::arbitrary(u)?,
             encrypt_with: Option::<EncryptWith>::arbitrary(u)?,
             alignment: u16::arbitrary(u)?,
+            unix_metadata: None,
+            version_needed: None,
+            comment: None,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
             ..Default::default()
         };
         #[cfg(feature = "deflate-zopfli")]
@@ -432,8 +582,8 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
     /// Range of values depends on compression method:
     /// * `Deflated`: 10 - 264 for Zopfli, 0 - 9 for other encoders. Default is 24 if Zopfli is the
     ///   only encoder, or 6 otherwise.
-    /// * `Bzip2`: 0 - 9. Default is 6
-    /// * `Zstd`: -7 - 22, with zero being mapped to default level. Default is 3
+    /// * `Bzip2`: 1 - 9, the block size in units of 100,000 bytes. Default is 6
+    /// * `Zstd`: -131072 - 22, with zero being mapped to default level. Default is 3
     /// * others: only `None` is allowed
     #[must_use]
     pub const fn compression_level(mut self, level: Option<i64>) -> Self {
@@ -466,18 +616,60 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self
     }
 
+    /// Set the owner and last access time of the new file, as found on Unix systems.
+    ///
+    /// This adds an extended timestamp extra field with the last modified time, taken as UTC, and
+    /// `atime`, in seconds since the UNIX epoch. Unlike the MS-DOS timestamp in the headers, it
+    /// has a 1-second precision and no time zone ambiguity. `uid` and `gid` are stored in an
+    /// Info-ZIP Unix extra field. Following the Info-ZIP convention, the central directory only
+    /// repeats the modification time.
+    #[must_use]
+    pub const fn unix_metadata(mut self, uid: u32, gid: u32, atime: u32) -> Self {
+        self.unix_metadata = Some(UnixMetadata { uid, gid, atime });
+        self
+    }
+
+    /// Set the "version needed to extract" written for the new file, for extractors that expect
+    /// a particular value. By default it's the lowest version supporting the file's features,
+    /// such as 20 for Deflate, directories and ZipCrypto, 45 for Zip64 and 51 for AES, and
+    /// starting the file fails if the version set is lower than that.
+    #[must_use]
+    pub const fn version_needed(mut self, version: u16) -> Self {
+        self.version_needed = Some(version);
+        self
+    }
+
     /// Set whether the new file's compressed and uncompressed size is less than 4 GiB.
     ///
     /// If set to `false` and the file exceeds the limit, an I/O error is thrown and the file is
     /// aborted. If set to `true`, readers will require ZIP64 support and if the file does not
     /// exceed the limit, 20 B are wasted. The default is `false`.
+    ///
+    /// The writer's [`Zip64Mode`] takes precedence: [`Zip64Mode::Always`] sets this for every
+    /// entry, and [`Zip64Mode::Never`] rejects entries that have it set.
     #[must_use]
     pub const fn large_file(mut self, large: bool) -> Self {
         self.large_file = large;
         self
     }
 
-    pub(crate) fn with_deprecated_encryption(self, password: &[u8]) -> FileOptions<'static, T> {
+    /// Set the comment stored for the file in the central directory. It's encoded as UTF-8, and
+    /// if it isn't ASCII, the entry's UTF-8 flag (general purpose bit 11) is set so that readers
+    /// decode it, and the file's name, as UTF-8.
+    ///
+    /// The comment can't be longer than 65535 bytes.
+    #[must_use]
+    pub fn comment<'c>(self, comment: &'c str) -> FileOptions<'c, T>
+    where
+        'k: 'c,
+    {
+        FileOptions {
+            comment: Some(comment),
+            ..self
+        }
+    }
+
+    pub(crate) fn with_deprecated_encryption(self, password: &[u8]) -> FileOptions<'k, T> {
         FileOptions {
             encrypt_with: Some(EncryptWith::ZipCrypto(
                 ZipCryptoKeys::derive(password),
@@ -489,7 +681,10 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
 
     /// Set the AES encryption parameters.
     #[cfg(feature = "aes-crypto")]
-    pub fn with_aes_encryption(self, mode: AesMode, password: &str) -> FileOptions<'_, T> {
+    pub fn with_aes_encryption<'p>(self, mode: AesMode, password: &'p str) -> FileOptions<'p, T>
+    where
+        'k: 'p,
+    {
         FileOptions {
             encrypt_with: Some(EncryptWith::Aes { mode, password }),
             ..self
@@ -507,6 +702,70 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self
     }
 
+    /// Compresses the file with Zstandard, using the given level and, optionally, the base-2
+    /// logarithm of the window size.
+    ///
+    /// The level may be anything in zstd's range, including the negative fast levels. Setting a
+    /// window log also enables long-distance matching, which helps with large, repetitive data.
+    /// It must be between 10 and 31 (30 on 32-bit targets), or [`ZipWriter::start_file`] will
+    /// fail. Decoders refuse windows larger than 2^27 bytes by default, so to read the file
+    /// back with a larger window, set
+    /// [`ReadOptions::zstd_window_log_max`](crate::read::ReadOptions::zstd_window_log_max).
+    #[must_use]
+    #[cfg(feature = "zstd")]
+    pub const fn zstd_options(mut self, level: i32, window_log: Option<u32>) -> Self {
+        self.compression_method = CompressionMethod::Zstd;
+        self.compression_level = Some(level as i64);
+        self.zstd_window_log = window_log;
+        self
+    }
+
+    /// Compresses the file with bzip2, using blocks of `size` × 100,000 bytes.
+    ///
+    /// Larger blocks compress big, repetitive files better, but take more memory to compress and
+    /// decompress. `size` must be between 1 and 9, or [`ZipWriter::start_file`] will fail. This
+    /// is the bzip2 compression level, so it replaces any level set before.
+    #[must_use]
+    #[cfg(feature = "bzip2")]
+    pub const fn bzip2_block_size(mut self, size: u8) -> Self {
+        self.compression_method = CompressionMethod::Bzip2;
+        self.compression_level = Some(size as i64);
+        self
+    }
+
+    /// Checks that the options don't contradict each other, returning the error
+    /// [`ZipWriter::start_file`] would fail with.
+    ///
+    /// Encryption is set with [`FileOptions::with_aes_encryption`], not the AES compression
+    /// method, and a compression level or Zstd window log must suit the compression method. Whether
+    /// [`FileOptions::large_file`] is allowed depends on the writer's [`Zip64Mode`], so it's
+    /// checked when the file is started.
+    pub fn validate(&self) -> ZipResult<()> {
+        if self.compression_method == CompressionMethod::AES {
+            return Err(ZipError::UnsupportedArchive(
+                "AES encryption is enabled through FileOptions::with_aes_encryption",
+            ));
+        }
+        if self
+            .comment
+            .is_some_and(|comment| comment.len() > u16::MAX as usize)
+        {
+            return Err(InvalidArchive("File comment is too long"));
+        }
+        if self.compression_method == Stored && self.compression_level.is_some() {
+            return Err(ZipError::UnsupportedArchive(
+                "Unsupported compression level",
+            ));
+        }
+        #[cfg(feature = "zstd")]
+        if self.zstd_window_log.is_some() && self.compression_method != CompressionMethod::Zstd {
+            return Err(InvalidArchive(
+                "A Zstd window log needs the Zstd compression method",
+            ));
+        }
+        Ok(())
+    }
+
     /// Returns the compression level currently set.
     pub const fn get_compression_level(&self) -> Option<i64> {
         self.compression_level
@@ -533,10 +792,10 @@ impl<'k> FileOptions<'k, ExtendedFileOptions> {
     /// Removes the extra data fields.
     #[must_use]
     pub fn clear_extra_data(mut self) -> Self {
-        if self.extended_options.extra_data.len() > 0 {
+        if !self.extended_options.extra_data.is_empty() {
             self.extended_options.extra_data = Arc::new(vec![]);
         }
-        if self.extended_options.central_extra_data.len() > 0 {
+        if !self.extended_options.central_extra_data.is_empty() {
             self.extended_options.central_extra_data = Arc::new(vec![]);
         }
         self
@@ -554,8 +813,13 @@ impl<'k, T: FileOptionExtension> Default for FileOptions<'k, T> {
             encrypt_with: None,
             extended_options: T::default(),
             alignment: 1,
+            unix_metadata: None,
+            version_needed: None,
+            comment: None,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: Some(1 << 15),
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
         }
     }
 }
@@ -576,8 +840,9 @@ impl<W: Write + Seek> Write for ZipWriter<W> {
                 let write_result = w.write(buf);
                 if let Ok(count) = write_result {
                     self.stats.update(&buf[0..count]);
+                    let file = &self.files.last().unwrap().1;
                     if self.stats.bytes_written > spec::ZIP64_BYTES_THR
-                        && !self.files.last_mut().unwrap().1.large_file
+                        && (self.zip64_mode == Zip64Mode::Never || !file.large_file)
                     {
                         let _ = self.abort_file();
                         return Err(io::Error::new(
@@ -626,15 +891,25 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
     /// This uses the given read configuration to initially read the archive.
     pub fn new_append_with_config(config: Config, mut readwriter: A) -> ZipResult<ZipWriter<A>> {
         readwriter.seek(SeekFrom::Start(0))?;
-        if let Ok((footer, shared)) = ZipArchive::get_metadata(config, &mut readwriter) {
+        if let Ok((footer, shared)) = ZipArchive::get_metadata(config, &mut readwriter, &[]) {
             Ok(ZipWriter {
                 inner: Storer(MaybeEncrypted::Unencrypted(readwriter)),
-                files: shared.files,
+                files: shared
+                    .files
+                    .into_iter()
+                    .map(|file| (file.file_name.clone(), file))
+                    .collect(),
                 stats: Default::default(),
                 writing_to_file: false,
                 comment: footer.zip_file_comment,
                 writing_raw: true, // avoid recomputing the last file's header
-                flush_on_finish_file: false,
+                flush_policy: FlushPolicy::OnFinish,
+                streaming: false,
+                zip64_mode: Zip64Mode::Auto,
+                volume_size: None,
+                reproducible_time: None,
+                name_transform: None,
+                registry: None,
             })
         } else {
             Err(InvalidArchive("No central-directory end header found"))
@@ -653,15 +928,25 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
     /// [BufWriter::flush], and ZipWriter needs to seek backward to update each file's header with
     /// the size and checksum after writing the body.
     ///
-    /// This setting is false by default.
+    /// This setting is false by default. Setting it is the same as calling
+    /// [`ZipWriter::set_flush_policy`] with [`FlushPolicy::EachEntry`] or
+    /// [`FlushPolicy::OnFinish`].
     pub fn set_flush_on_finish_file(&mut self, flush_on_finish_file: bool) {
-        self.flush_on_finish_file = flush_on_finish_file;
+        self.flush_policy = if flush_on_finish_file {
+            FlushPolicy::EachEntry
+        } else {
+            FlushPolicy::OnFinish
+        };
     }
 }
 
 impl<A: Read + Write + Seek> ZipWriter<A> {
     /// Adds another copy of a file already in this archive. This will produce a larger but more
     /// widely-compatible archive compared to [Self::shallow_copy_file]. Does not copy alignment.
+    ///
+    /// The compressed data is copied as is, with the CRC-32 and sizes of the original, so it isn't
+    /// recompressed. It can't be shared with the original, since each local header must be
+    /// followed by the data it describes. Encrypted files stay encrypted with the same password.
     pub fn deep_copy_file(&mut self, src_name: &str, dest_name: &str) -> ZipResult<()> {
         self.finish_file()?;
         if src_name == dest_name || self.files.contains_key(dest_name) {
@@ -677,6 +962,7 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
             src_data.compressed_size = compressed_size;
         }
         let uncompressed_size = src_data.uncompressed_size;
+        let encryption = src_data.encrypted.then_some(src_data.aes_mode);
 
         let raw_values = ZipRawValues {
             crc32: src_data.crc32,
@@ -708,8 +994,13 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                     central_extra_data: src_data.central_extra_field.clone().unwrap_or_default(),
                 },
                 alignment: 1,
+                unix_metadata: None,
+                version_needed: None,
+                comment: None,
                 #[cfg(feature = "deflate-zopfli")]
                 zopfli_buffer_size: None,
+                #[cfg(feature = "zstd")]
+                zstd_window_log: None,
             };
             if let Some(perms) = src_data.unix_mode() {
                 options = options.unix_permissions(perms);
@@ -728,8 +1019,13 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                 encrypt_with: None,
                 extended_options: (),
                 alignment: 1,
+                unix_metadata: None,
+                version_needed: None,
+                comment: None,
                 #[cfg(feature = "deflate-zopfli")]
                 zopfli_buffer_size: None,
+                #[cfg(feature = "zstd")]
+                zstd_window_log: None,
             };
             if let Some(perms) = src_data.unix_mode() {
                 options = options.unix_permissions(perms);
@@ -737,6 +1033,23 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
             Self::normalize_options(&mut options);
             self.start_entry(dest_name, options, Some(raw_values))?;
         }
+        if let Some(aes_mode) = encryption {
+            // The data is copied along with its encryption header, so mark the copy as encrypted
+            let (_, file) = self.files.last_mut().unwrap();
+            file.encrypted = true;
+            file.aes_mode = aes_mode;
+            #[cfg(feature = "aes-crypto")]
+            if aes_mode.is_some() {
+                file.compression_method = CompressionMethod::Aes;
+            }
+            file.version_made_by = file.version_made_by.max(file.version_needed() as u8);
+            let block = file.local_block()?;
+            let header_start = file.header_start;
+            let writer = self.inner.get_plain();
+            writer.seek(SeekFrom::Start(header_start))?;
+            block.write(writer)?;
+            writer.seek(SeekFrom::Start(self.stats.start))?;
+        }
 
         self.writing_to_file = true;
         self.writing_raw = true;
@@ -760,6 +1073,169 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
         self.deep_copy_file(&src, &dest)
     }
 
+    /// Removes a file from the archive, including one that was already present when the archive
+    /// was opened with [`ZipWriter::new_append`].
+    ///
+    /// The local header and data of every later entry are moved back to fill the gap, and their
+    /// offsets are updated. If a shallow copy of the file remains, its data is kept.
+    ///
+    /// The underlying stream can't be truncated through [`Write`](io::Write) and [`Seek`], so
+    /// its size stays the same: [`ZipWriter::finish`] writes the central directory right after
+    /// the remaining entries, and leaves the stale bytes after it in place. To shrink the
+    /// archive, truncate the stream to the position `finish` leaves it at, e.g. with
+    /// [`File::set_len`](std::fs::File::set_len):
+    ///
+    /// ```
+    /// # fn main() -> Result<(), zip::result::ZipError> {
+    /// # use std::io::{Cursor, Seek, Write};
+    /// # use zip::write::SimpleFileOptions;
+    /// # let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    /// # writer.start_file("large.bin", SimpleFileOptions::default())?;
+    /// # writer.write_all(&[0; 1000])?;
+    /// # let mut writer = zip::ZipWriter::new_append(writer.finish()?)?;
+    /// writer.remove_file("large.bin")?;
+    /// let mut cursor = writer.finish()?;
+    /// let len = cursor.stream_position()?;
+    /// cursor.get_mut().truncate(len as usize);
+    /// assert!(zip::ZipArchive::new(cursor)?.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remove_file(&mut self, name: &str) -> ZipResult<()> {
+        self.finish_file()?;
+        let index = self.index_by_name(name)?;
+        let (_, removed) = self.files.shift_remove_index(index).unwrap();
+        if self
+            .files
+            .values()
+            .any(|file| file.header_start == removed.header_start)
+        {
+            return Ok(());
+        }
+        let writer = self.inner.get_plain();
+        let data_end = writer.stream_position()?;
+        let removed_end = self
+            .files
+            .values()
+            .map(|file| file.header_start)
+            .filter(|&start| start > removed.header_start)
+            .min()
+            .unwrap_or(data_end);
+        let gap = removed_end - removed.header_start;
+
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut read_position = removed_end;
+        while read_position < data_end {
+            let len = buf.len().min((data_end - read_position) as usize);
+            writer.seek(SeekFrom::Start(read_position))?;
+            writer.read_exact(&mut buf[..len])?;
+            writer.seek(SeekFrom::Start(read_position - gap))?;
+            writer.write_all(&buf[..len])?;
+            read_position += len as u64;
+        }
+        writer.seek(SeekFrom::Start(data_end - gap))?;
+
+        for file in self.files.values_mut() {
+            if file.header_start > removed.header_start {
+                file.header_start -= gap;
+                if let Some(extra_data_start) = &mut file.extra_data_start {
+                    *extra_data_start -= gap;
+                }
+                if let Some(data_start) = file.data_start.take() {
+                    file.data_start.get_or_init(|| data_start - gap);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decompresses a file already in the archive and writes it again with the compression
+    /// method, level, alignment and Zip64 setting of `options`, e.g. to switch an archive to
+    /// [`CompressionMethod::Zstd`].
+    ///
+    /// The file keeps its name, position in the central directory, modification time,
+    /// permissions, comment and extra fields. The data's CRC-32 is checked while it's read, and
+    /// the new CRC-32 is computed from the decompressed data. Like [`ZipWriter::remove_file`], this
+    /// moves the later entries back, and the rewritten file goes after them. Encrypted files
+    /// can't be recompressed, since no password is given.
+    ///
+    /// The decompressed data is held in memory while the file is rewritten, so it must fit there.
+    /// Decompression stops with an error once it produces more than the file's declared size.
+    pub fn recompress_file<T: FileOptionExtension>(
+        &mut self,
+        name: &str,
+        options: FileOptions<T>,
+    ) -> ZipResult<()> {
+        self.finish_file()?;
+        let index = self.index_by_name(name)?;
+        let src_data = self.files[index].clone();
+        if src_data.encrypted {
+            return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED));
+        }
+        let registry = self.registry.clone();
+        let writer = self.inner.get_plain();
+        let write_position = writer.stream_position()?;
+        let mut contents = Vec::new();
+        let result = (|| {
+            let content = find_content(&src_data, &mut *writer)?;
+            let crypto_reader =
+                make_crypto_reader(&src_data, content, None, None, registry.as_deref())?;
+            make_reader(
+                &src_data,
+                true,
+                true,
+                Some(src_data.uncompressed_size),
+                crypto_reader,
+                ReadOptions::default(),
+                registry.as_deref(),
+            )?
+            .read_to_end(&mut contents)?;
+            Ok::<_, ZipError>(())
+        })();
+        writer.seek(SeekFrom::Start(write_position))?;
+        result?;
+
+        let comment = src_data.file_comment.clone();
+        let mut new_options = FullFileOptions {
+            compression_method: options.compression_method,
+            compression_level: options.compression_level,
+            large_file: options.large_file
+                || src_data.compressed_size.max(src_data.uncompressed_size) > spec::ZIP64_BYTES_THR,
+            alignment: options.alignment,
+            #[cfg(feature = "deflate-zopfli")]
+            zopfli_buffer_size: options.zopfli_buffer_size,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: options.zstd_window_log,
+            ..FullFileOptions::default()
+        }
+        .last_modified_time(
+            src_data
+                .last_modified_time
+                .unwrap_or_else(DateTime::default_for_write),
+        )
+        .comment(&comment);
+        new_options.extended_options.extra_data = Arc::new(copyable_extra_data(
+            src_data.extra_field.as_deref().map_or(&[], |data| data),
+        ));
+        if let Some(perms) = src_data.unix_mode() {
+            new_options = new_options.unix_permissions(perms);
+        }
+        Self::normalize_options(&mut new_options);
+
+        self.remove_file(name)?;
+        // The entry already has its final name
+        let name_transform = self.name_transform.take();
+        let result = self.start_compressed_entry(name, new_options);
+        self.name_transform = name_transform;
+        result?;
+        let result = self.write_all(&contents);
+        self.ok_or_abort_file(result)?;
+        self.finish_file()?;
+        let last = self.files.len() - 1;
+        self.files.move_index(last, index);
+        Ok(())
+    }
+
     /// Write the zip file into the backing stream, then produce a readable archive of that data.
     ///
     /// This method avoids parsing the central directory records at the end of the stream for
@@ -785,15 +1261,40 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
     /// # }
     ///```
     pub fn finish_into_readable(mut self) -> ZipResult<ZipArchive<A>> {
-        let central_start = self.finalize()?;
+        let central_start = self.finalize_or_close()?;
         let inner = mem::replace(&mut self.inner, Closed).unwrap();
         let comment = mem::take(&mut self.comment);
         let files = mem::take(&mut self.files);
-        let archive = ZipArchive::from_finalized_writer(files, comment, inner, central_start)?;
+        let registry = self.registry.take();
+        let archive =
+            ZipArchive::from_finalized_writer(files, comment, inner, central_start, registry)?;
         Ok(archive)
     }
 }
 
+impl ZipWriter<SplitWriter> {
+    /// Initializes an archive split into volumes of at most `volume_size` bytes, for media with
+    /// a size limit.
+    ///
+    /// The last volume is written to `path`, and the ones before it get the same name with the
+    /// extensions `.z01`, `.z02` and so on. Entry data may span volumes, but headers are moved to
+    /// the next volume rather than split. `volume_size` must be between 64 KiB and 4 GiB - 1, as
+    /// APPNOTE 8.5.5 requires. The archive can be read back with
+    /// [`ZipArchive::new_multi_volume`].
+    pub fn new_split<P: AsRef<Path>>(path: P, volume_size: u64) -> ZipResult<Self> {
+        if !(split::MIN_VOLUME_SIZE..spec::ZIP64_BYTES_THR).contains(&volume_size) {
+            return Err(InvalidArchive(
+                "Volume size must be between 64 KiB and 4 GiB - 1",
+            ));
+        }
+        let mut inner = SplitWriter::create(path.as_ref(), volume_size)?;
+        inner.write_all(&spec::Magic::SPLIT_ARCHIVE_SIGNATURE.to_le_bytes())?;
+        let mut writer = ZipWriter::new(inner);
+        writer.volume_size = Some(volume_size);
+        Ok(writer)
+    }
+}
+
 impl<W: Write + Seek> ZipWriter<W> {
     /// Initializes the archive.
     ///
@@ -808,7 +1309,13 @@ impl<W: Write + Seek> ZipWriter<W> {
             writing_to_file: false,
             writing_raw: false,
             comment: Box::new([]),
-            flush_on_finish_file: false,
+            flush_policy: FlushPolicy::OnFinish,
+            streaming: false,
+            zip64_mode: Zip64Mode::Auto,
+            volume_size: None,
+            reproducible_time: None,
+            name_transform: None,
+            registry: None,
         }
     }
 
@@ -818,6 +1325,9 @@ impl<W: Write + Seek> ZipWriter<W> {
     }
 
     /// Set ZIP archive comment.
+    ///
+    /// The comment is written as UTF-8. The end of central directory record has no flag to mark
+    /// it as such, but [`ZipArchive::comment_str`] decodes it as UTF-8 when it's valid.
     pub fn set_comment<S>(&mut self, comment: S)
     where
         S: Into<Box<str>>,
@@ -846,6 +1356,71 @@ impl<W: Write + Seek> ZipWriter<W> {
         &self.comment
     }
 
+    /// Set when the Zip64 extensions are used. The default is [`Zip64Mode::Auto`].
+    ///
+    /// The mode applies to the entries started afterwards, and to the end of central directory.
+    pub fn set_zip64_mode(&mut self, mode: Zip64Mode) {
+        self.zip64_mode = mode;
+    }
+
+    /// Set when the inner writer is flushed. The default is [`FlushPolicy::OnFinish`].
+    ///
+    /// With [`FlushPolicy::EachEntry`], see [`ZipWriter::set_flush_on_finish_file`] for which
+    /// writers are suitable.
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.flush_policy = policy;
+    }
+
+    /// Set whether the archive is made to depend only on the entries' names, options and
+    /// contents, so that building it twice gives the same bytes. Off by default.
+    ///
+    /// When on, entries started afterwards get the time set by
+    /// [`ZipWriter::set_reproducible_time`], 1980-01-01 by default, instead of their own, and
+    /// lose the extra fields holding other timestamps or owner IDs. The central directory lists
+    /// entries sorted by name. The entries' data is still written in the order they're added,
+    /// so add them in a fixed order as well.
+    pub fn set_reproducible(&mut self, reproducible: bool) {
+        self.reproducible_time = reproducible.then(|| self.reproducible_time.unwrap_or_default());
+    }
+
+    /// Set the time given to every entry when the output is reproducible, such as the time of
+    /// the last commit, and turn on reproducible output. See [`ZipWriter::set_reproducible`].
+    pub fn set_reproducible_time(&mut self, time: DateTime) {
+        self.reproducible_time = Some(time);
+    }
+
+    /// Set a transform applied to the names of the entries added afterwards, before they're
+    /// checked and stored, such as one normalizing paths from a directory walk. An error from the
+    /// transform fails adding the entry. See [`name_transform`] for ready-made ones.
+    ///
+    /// Names passed to [`ZipWriter::add_directory`] are transformed after a trailing slash is
+    /// added.
+    pub fn set_name_transform<F>(&mut self, transform: F)
+    where
+        F: Fn(&str) -> ZipResult<Cow<'_, str>> + Send + Sync + 'static,
+    {
+        self.name_transform = Some(Box::new(transform));
+    }
+
+    /// Compresses entries with the encoders in `registry`, falling back to the built-in
+    /// implementations for any other method. [`ZipWriter::finish_into_readable`] passes the
+    /// registry on to the returned archive.
+    pub fn set_compression_registry(&mut self, registry: CompressionRegistry) {
+        self.registry = Some(Arc::new(registry));
+    }
+
+    /// Removes the transform set by [`ZipWriter::set_name_transform`].
+    pub fn clear_name_transform(&mut self) {
+        self.name_transform = None;
+    }
+
+    fn transform_name(&self, name: Box<str>) -> ZipResult<Box<str>> {
+        match &self.name_transform {
+            Some(transform) => Ok(transform(&name)?.into()),
+            None => Ok(name),
+        }
+    }
+
     /// Set the file length and crc32 manually.
     ///
     /// # Safety
@@ -878,7 +1453,7 @@ impl<W: Write + Seek> ZipWriter<W> {
     fn start_entry<S, SToOwned, T: FileOptionExtension>(
         &mut self,
         name: S,
-        options: FileOptions<T>,
+        mut options: FileOptions<T>,
         raw_values: Option<ZipRawValues>,
     ) -> ZipResult<()>
     where
@@ -887,6 +1462,19 @@ impl<W: Write + Seek> ZipWriter<W> {
     {
         self.finish_file()?;
 
+        if let Some(time) = self.reproducible_time {
+            options.last_modified_time = time;
+            options.unix_metadata = None;
+        }
+        match self.zip64_mode {
+            Zip64Mode::Always => options.large_file = true,
+            Zip64Mode::Never if options.large_file => {
+                return Err(InvalidArchive("Zip64 is disabled, but the entry needs it"));
+            }
+            _ => {}
+        }
+
+        let copied = raw_values.is_some();
         let raw_values = raw_values.unwrap_or(ZipRawValues {
             crc32: 0,
             compressed_size: 0,
@@ -894,10 +1482,37 @@ impl<W: Write + Seek> ZipWriter<W> {
         });
 
         let mut extra_data = match options.extended_options.extra_data() {
+            Some(data) if self.reproducible_time.is_some() => without_volatile_extra_data(data),
             Some(data) => data.to_vec(),
             None => vec![],
         };
-        let central_extra_data = options.extended_options.central_extra_data();
+        let central_extra_data = match options.extended_options.central_extra_data() {
+            Some(data) if self.reproducible_time.is_some() => {
+                Some(Arc::new(without_volatile_extra_data(data)))
+            }
+            data => data.cloned(),
+        };
+
+        if let Some(UnixMetadata { uid, gid, atime }) = options.unix_metadata {
+            let mtime = unix_timestamp(options.last_modified_time);
+            let mut timestamps = vec![0b011];
+            timestamps.extend_from_slice(&mtime.to_le_bytes());
+            timestamps.extend_from_slice(&atime.to_le_bytes());
+            ExtendedFileOptions::add_extra_data_unchecked(
+                &mut extra_data,
+                0x5455,
+                timestamps.into_boxed_slice(),
+            )?;
+            let mut ids = vec![1, 4];
+            ids.extend_from_slice(&uid.to_le_bytes());
+            ids.push(4);
+            ids.extend_from_slice(&gid.to_le_bytes());
+            ExtendedFileOptions::add_extra_data_unchecked(
+                &mut extra_data,
+                0x7875,
+                ids.into_boxed_slice(),
+            )?;
+        }
 
         // Write AES encryption extra data.
         #[allow(unused_mut)]
@@ -914,7 +1529,28 @@ impl<W: Write + Seek> ZipWriter<W> {
             )?;
         }
         {
-            let header_start = self.inner.get_plain().stream_position()?;
+            let name = self.transform_name(name.into())?;
+            let header_start = match self.volume_size {
+                Some(volume_size) => {
+                    // Keep the local header in one volume, counting the Zip64 extra field and
+                    // any alignment padding
+                    let header_len = size_of::<ZipLocalEntryBlock>()
+                        + name.len()
+                        + 20
+                        + extra_data.len()
+                        + options.alignment as usize
+                        + 6;
+                    split::reserve(self.inner.get_plain(), volume_size, header_len as u64)?
+                }
+                None => self.inner.get_plain().stream_position()?,
+            };
+            let header_offset = match self.volume_size {
+                Some(volume_size) => header_start % volume_size,
+                None => header_start,
+            };
+            if self.zip64_mode == Zip64Mode::Never && header_offset >= spec::ZIP64_BYTES_THR {
+                return Err(InvalidArchive("Zip64 is disabled, but the entry needs it"));
+            }
 
             let (compression_method, aes_mode) = match options.encrypt_with {
                 #[cfg(feature = "aes-crypto")]
@@ -935,7 +1571,16 @@ impl<W: Write + Seek> ZipWriter<W> {
                 aes_mode,
                 &extra_data,
             );
+            if file
+                .explicit_version_needed
+                .is_some_and(|version| version < file.min_version_needed())
+            {
+                return Err(InvalidArchive(
+                    "The version needed to extract is too low for the entry's features",
+                ));
+            }
             file.version_made_by = file.version_made_by.max(file.version_needed() as u8);
+            file.using_data_descriptor = self.streaming;
             let block = file.local_block();
             let index = self.insert_file_data(file)?;
             let writer = self.inner.get_plain();
@@ -977,6 +1622,7 @@ impl<W: Write + Seek> ZipWriter<W> {
                     ExtendedFileOptions::validate_extra_data(
                         &extra_data,
                         header_end - zip64_start,
+                        copied,
                     )?;
                     writer.write_all(&extra_data)?;
                     extra_data_end = writer.stream_position()?;
@@ -993,13 +1639,16 @@ impl<W: Write + Seek> ZipWriter<W> {
                 self.stats.start = extra_data_end;
             }
             if let Some(data) = central_extra_data {
-                let validation_result =
-                    ExtendedFileOptions::validate_extra_data(data, extra_data_end - zip64_start);
+                let validation_result = ExtendedFileOptions::validate_extra_data(
+                    &data,
+                    extra_data_end - zip64_start,
+                    copied,
+                );
                 if let Err(e) = validation_result {
                     let _ = self.abort_file();
                     return Err(e);
                 }
-                file.central_extra_field = Some(data.clone());
+                file.central_extra_field = Some(data);
             }
             match options.encrypt_with {
                 #[cfg(feature = "aes-crypto")]
@@ -1054,6 +1703,8 @@ impl<W: Write + Seek> ZipWriter<W> {
             None,
             #[cfg(feature = "deflate-zopfli")]
             None,
+            #[cfg(feature = "zstd")]
+            None,
         )?;
         self.inner.switch_to(make_plain_writer)?;
         self.switch_to_non_encrypting_writer()?;
@@ -1073,10 +1724,11 @@ impl<W: Write + Seek> ZipWriter<W> {
             if let Some(aes_mode) = &mut file.aes_mode {
                 // We prefer using AE-1 which provides an extra CRC check, but for small files we
                 // switch to AE-2 to prevent being able to use the CRC value to to reconstruct the
-                // unencrypted contents.
+                // unencrypted contents. A stream can't go back to change the version, so it keeps
+                // the AE-2 it started with.
                 //
                 // C.f. https://www.winzip.com/en/support/aes-encryption/#crc-faq
-                aes_mode.1 = if self.stats.bytes_written < 20 {
+                aes_mode.1 = if self.stats.bytes_written < 20 || file.using_data_descriptor {
                     crc = false;
                     AesVendorVersion::Ae2
                 } else {
@@ -1088,11 +1740,15 @@ impl<W: Write + Seek> ZipWriter<W> {
             } else {
                 0
             };
-            update_aes_extra_data(writer, file)?;
-            update_local_file_header(writer, file)?;
-            writer.seek(SeekFrom::Start(file_end))?;
+            if file.using_data_descriptor {
+                write_data_descriptor(writer, file)?;
+            } else {
+                update_aes_extra_data(writer, file)?;
+                update_local_file_header(writer, file)?;
+                writer.seek(SeekFrom::Start(file_end))?;
+            }
         }
-        if self.flush_on_finish_file {
+        if self.flush_policy == FlushPolicy::EachEntry {
             let result = writer.flush();
             self.ok_or_abort_file(result)?;
         }
@@ -1108,7 +1764,19 @@ impl<W: Write + Seek> ZipWriter<W> {
                 self.inner = Storer(MaybeEncrypted::Unencrypted(writer.finish()?));
             }
             Storer(MaybeEncrypted::ZipCrypto(writer)) => {
-                let crc32 = self.stats.hasher.clone().finalize();
+                let mut crc32 = self.stats.hasher.clone().finalize();
+                if let Some((_, file)) = self
+                    .files
+                    .last()
+                    .filter(|(_, file)| file.using_data_descriptor)
+                {
+                    // With a data descriptor, the password check byte comes from the
+                    // modification time instead
+                    let time = file
+                        .last_modified_time
+                        .unwrap_or_else(DateTime::default_for_write);
+                    crc32 = (time.timepart() as u32) << 16;
+                }
                 self.inner = Storer(MaybeEncrypted::Unencrypted(writer.finish(crc32)?))
             }
             Storer(MaybeEncrypted::Unencrypted(w)) => {
@@ -1121,6 +1789,13 @@ impl<W: Write + Seek> ZipWriter<W> {
 
     /// Removes the file currently being written from the archive if there is one, or else removes
     /// the file most recently written.
+    ///
+    /// This is how to recover when writing a file fails partway, e.g. because the source of its
+    /// data returned an error: the writer seeks back to the file's local header, so the next
+    /// file started with [`ZipWriter::start_file`] overwrites it. The underlying stream isn't
+    /// truncated, so if nothing overwrites the aborted data it stays before the central
+    /// directory. A [`ZipStreamWriter`](crate::unstable::stream::ZipStreamWriter) can't seek
+    /// back, so it doesn't support this.
     pub fn abort_file(&mut self) -> ZipResult<()> {
         let (_, last_file) = self.files.pop().ok_or(ZipError::FileNotFound)?;
         let make_plain_writer = self.inner.prepare_next_writer(
@@ -1128,12 +1803,16 @@ impl<W: Write + Seek> ZipWriter<W> {
             None,
             #[cfg(feature = "deflate-zopfli")]
             None,
+            #[cfg(feature = "zstd")]
+            None,
         )?;
         self.inner.switch_to(make_plain_writer)?;
         self.switch_to_non_encrypting_writer()?;
         // Make sure this is the last file, and that no shallow copies of it remain; otherwise we'd
         // overwrite a valid file and corrupt the archive
         let rewind_safe: bool = match last_file.data_start.get() {
+            // Data after the header may be in later volumes, which can't be rewritten
+            _ if self.streaming || self.volume_size.is_some() => false,
             None => self.files.is_empty(),
             Some(last_file_start) => self.files.values().all(|file| {
                 file.data_start
@@ -1154,6 +1833,12 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// same name as a file already in the archive.
     ///
     /// The data should be written using the [`Write`] implementation on this [`ZipWriter`]
+    ///
+    /// The CRC-32 and sizes don't need to be known in advance: they're computed as the data is
+    /// written, and the local header is updated once the file is finished, so the data only
+    /// has to be read once even when it's [`CompressionMethod::Stored`]. A
+    /// [`ZipStreamWriter`](crate::unstable::stream::ZipStreamWriter), which can't seek back, writes
+    /// them in a data descriptor instead.
     pub fn start_file<S, T: FileOptionExtension, SToOwned>(
         &mut self,
         name: S,
@@ -1164,12 +1849,36 @@ impl<W: Write + Seek> ZipWriter<W> {
         SToOwned: Into<Box<str>>,
     {
         Self::normalize_options(&mut options);
-        let make_new_self = self.inner.prepare_next_writer(
-            options.compression_method,
-            options.compression_level,
-            #[cfg(feature = "deflate-zopfli")]
-            options.zopfli_buffer_size,
-        )?;
+        options.validate()?;
+        self.start_compressed_entry(name, options)
+    }
+
+    /// Starts an entry whose data is compressed as it's written, with options that have already
+    /// been normalized and validated.
+    fn start_compressed_entry<S, T: FileOptionExtension, SToOwned>(
+        &mut self,
+        name: S,
+        options: FileOptions<T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        let custom_encoder = self
+            .registry
+            .as_ref()
+            .and_then(|registry| registry.encoder(options.compression_method));
+        let make_new_self = match custom_encoder {
+            Some(factory) => prepare_custom_writer(factory)?,
+            None => self.inner.prepare_next_writer(
+                options.compression_method,
+                options.compression_level,
+                #[cfg(feature = "deflate-zopfli")]
+                options.zopfli_buffer_size,
+                #[cfg(feature = "zstd")]
+                options.zstd_window_log,
+            )?,
+        };
         self.start_entry(name, options, None)?;
         let result = self.inner.switch_to(make_new_self);
         self.ok_or_abort_file(result)?;
@@ -1177,6 +1886,49 @@ impl<W: Write + Seek> ZipWriter<W> {
         Ok(())
     }
 
+    /// Like [`ZipWriter::start_file`], but the CRC-32 and sizes always go in a data descriptor
+    /// after the file's data, as in a [`ZipStreamWriter`](crate::unstable::stream::ZipStreamWriter),
+    /// so the writer never seeks back to the local header. This suits data that's generated as
+    /// it's written, when the output is forwarded as it's produced.
+    ///
+    /// The data should be written using the [`Write`] implementation on this [`ZipWriter`]. There's
+    /// no separate handle for the entry, as with [`ZipWriter::start_file`]: the writer can only
+    /// write one entry at a time, so it's finished the same way whichever method started it.
+    /// Entries that exceed 4 GiB need [`FileOptions::large_file`], so that the local header has a
+    /// Zip64 extra field telling readers that the descriptor's sizes take 8 bytes.
+    ///
+    ///```
+    /// # fn main() -> Result<(), zip::result::ZipError> {
+    /// use std::io::{Cursor, Read, Write};
+    /// use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+    ///
+    /// let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    /// zip.start_file_streaming("report.txt", SimpleFileOptions::default())?;
+    /// for line in 1..=3 {
+    ///     writeln!(zip, "line {line}")?;
+    /// }
+    /// let mut zip = zip.finish_into_readable()?;
+    /// let mut report = String::new();
+    /// zip.by_name("report.txt")?.read_to_string(&mut report)?;
+    /// assert_eq!(report, "line 1\nline 2\nline 3\n");
+    /// # Ok(())
+    /// # }
+    ///```
+    pub fn start_file_streaming<S, T: FileOptionExtension, SToOwned>(
+        &mut self,
+        name: S,
+        options: FileOptions<T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        let streaming = mem::replace(&mut self.streaming, true);
+        let result = self.start_file(name, options);
+        self.streaming = streaming;
+        result
+    }
+
     /* TODO: link to/use Self::finish_into_readable() from https://github.com/zip-rs/zip/pull/400 in
      * this docstring. */
     /// Copy over the entire contents of another archive verbatim.
@@ -1186,6 +1938,9 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// decompression or decryption. This is more performant than the equivalent operation of
     /// calling [`Self::raw_copy_file()`] for each entry from the `source` archive in sequence.
     ///
+    /// An entry with the same name as one already in the archive replaces it; use
+    /// [`Self::merge_archive_with()`] to choose otherwise.
+    ///
     ///```
     /// # fn main() -> Result<(), zip::result::ZipError> {
     /// use std::io::{Cursor, prelude::*};
@@ -1218,7 +1973,24 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// # Ok(())
     /// # }
     ///```
-    pub fn merge_archive<R>(&mut self, mut source: ZipArchive<R>) -> ZipResult<()>
+    pub fn merge_archive<R>(&mut self, source: ZipArchive<R>) -> ZipResult<()>
+    where
+        R: Read + io::Seek,
+    {
+        self.merge_archive_with(source, NameCollision::Overwrite)
+    }
+
+    /// Copy over the entire contents of another archive verbatim, like [`Self::merge_archive()`],
+    /// handling entries whose names are already in the archive as `on_collision` says.
+    ///
+    /// The data of entries that are skipped or overwritten stays in the archive, unreferenced.
+    /// Renamed entries are copied again with [`Self::raw_copy_file_rename()`], so that their
+    /// local headers carry the new name.
+    pub fn merge_archive_with<R>(
+        &mut self,
+        mut source: ZipArchive<R>,
+        on_collision: NameCollision,
+    ) -> ZipResult<()>
     where
         R: Read + io::Seek,
     {
@@ -1234,11 +2006,43 @@ impl<W: Write + Seek> ZipWriter<W> {
         let new_files = source.merge_contents(writer)?;
 
         /* These file entries are now ours! */
-        self.files.extend(new_files);
+        let mut renamed = Vec::new();
+        for (index, file) in new_files.into_iter().enumerate() {
+            let name = file.file_name.clone();
+            match on_collision {
+                _ if !self.files.contains_key(&name) => {
+                    self.files.insert(name, file);
+                }
+                NameCollision::Overwrite => {
+                    self.files.insert(name, file);
+                }
+                NameCollision::Skip => {}
+                NameCollision::Rename if file.is_dir() => {}
+                NameCollision::Rename => renamed.push(index),
+            }
+        }
+        for index in renamed {
+            let file = source.by_index_raw(index)?;
+            let name = self.free_name(file.name());
+            self.raw_copy_file_rename(file, name)?;
+        }
 
         Ok(())
     }
 
+    /// Finds a name for a copy of `name` that isn't in the archive yet.
+    fn free_name(&self, name: &str) -> String {
+        let (dir, file_name) = name.split_at(name.rfind('/').map_or(0, |slash| slash + 1));
+        let (stem, extension) = match file_name.rfind('.') {
+            Some(dot) if dot > 0 => file_name.split_at(dot),
+            _ => (file_name, ""),
+        };
+        (1..)
+            .map(|n| format!("{dir}{stem} ({n}){extension}"))
+            .find(|candidate| !self.files.contains_key(candidate.as_str()))
+            .unwrap()
+    }
+
     fn normalize_options<T: FileOptionExtension>(options: &mut FileOptions<T>) {
         if options.permissions.is_none() {
             options.permissions = Some(0o644);
@@ -1262,10 +2066,46 @@ impl<W: Write + Seek> ZipWriter<W> {
         self.start_file(path_to_string(path), options)
     }
 
-    /// Add a new file using the already compressed data from a ZIP file being read and renames it, this
-    /// allows faster copies of the `ZipFile` since there is no need to decompress and compress it again.
-    /// Any `ZipFile` metadata is copied and not checked, for example the file CRC.
+    /// Adds a file with the contents of `reader`, storing it uncompressed if it looks like it
+    /// won't compress.
+    ///
+    /// The first 64 KiB are sampled to estimate how many bits of information each byte holds.
+    /// Data that is already compressed, such as JPEG or PNG images, holds nearly 8 and is written
+    /// with [`CompressionMethod::Stored`]; anything else uses the method in `options`. The
+    /// estimate can't tell much from a few hundred bytes, so small files keep that method too.
+    /// Use [`ZipWriter::start_file`] to choose the method yourself.
+    pub fn add_file_auto<S, T: FileOptionExtension, SToOwned, R: Read>(
+        &mut self,
+        name: S,
+        mut options: FileOptions<T>,
+        mut reader: R,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        let mut sample = Vec::with_capacity(AUTO_METHOD_SAMPLE_SIZE);
+        (&mut reader)
+            .take(AUTO_METHOD_SAMPLE_SIZE as u64)
+            .read_to_end(&mut sample)?;
+        if looks_incompressible(&sample) {
+            options.compression_method = Stored;
+            options.compression_level = None;
+        }
+        self.start_file(name, options)?;
+        self.write_all(&sample)?;
+        io::copy(&mut reader, self)?;
+        Ok(())
+    }
 
+    /// Add a new file using the already compressed data from a ZIP file being read and renames it, this
+    /// allows faster copies of the `ZipFile` since there is no need to decompress and compress it again.
+    /// Any `ZipFile` metadata is copied and not checked, for example the file CRC.
+    ///
+    /// The compression method is kept as is, so this works even for methods that can't be written,
+    /// and so are the extra fields, except those the writer generates itself (Zip64 and AES) and
+    /// the Unicode path field, which would no longer match the new name.
+    ///
     /// ```no_run
     /// use std::fs::File;
     /// use std::io::{Read, Seek, Write};
@@ -1293,13 +2133,15 @@ impl<W: Write + Seek> ZipWriter<W> {
         S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
         SToOwned: Into<Box<str>>,
     {
-        let mut options = SimpleFileOptions::default()
+        let mut options = FullFileOptions::default()
             .large_file(file.compressed_size().max(file.size()) > spec::ZIP64_BYTES_THR)
             .last_modified_time(
                 file.last_modified()
                     .unwrap_or_else(DateTime::default_for_write),
             )
             .compression_method(file.compression());
+        options.extended_options.extra_data =
+            Arc::new(copyable_extra_data(file.extra_data().unwrap_or_default()));
         if let Some(perms) = file.unix_mode() {
             options = options.unix_permissions(perms);
         }
@@ -1361,8 +2203,66 @@ impl<W: Write + Seek> ZipWriter<W> {
         self.raw_copy_file_rename(file, name)
     }
 
+    /// Add a new file whose data was already compressed with `method`, for example by another
+    /// process. The bytes of `compressed` are written as they are, like with
+    /// [`ZipWriter::raw_copy_file`], and `crc32` and `uncompressed_size` are recorded for the
+    /// entry without being checked.
+    ///
+    /// ```
+    /// # fn doit() -> zip::result::ZipResult<()>
+    /// # {
+    /// use std::io::Cursor;
+    /// use zip::{CompressionMethod, ZipWriter};
+    ///
+    /// let data = b"Compressed somewhere else";
+    /// let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    /// zip.write_precompressed(
+    ///     "file.txt",
+    ///     CompressionMethod::Stored,
+    ///     crc32fast::hash(data),
+    ///     &data[..],
+    ///     data.len() as u64,
+    /// )?;
+    /// zip.finish()?;
+    /// # Ok(())
+    /// # }
+    /// # doit().unwrap();
+    /// ```
+    pub fn write_precompressed<S, SToOwned, R: Read>(
+        &mut self,
+        name: S,
+        method: CompressionMethod,
+        crc32: u32,
+        mut compressed: R,
+        uncompressed_size: u64,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        let mut options = FullFileOptions::default()
+            .large_file(uncompressed_size > spec::ZIP64_BYTES_THR)
+            .compression_method(method);
+        Self::normalize_options(&mut options);
+        options.validate()?;
+
+        self.start_entry(name, options, None)?;
+        self.writing_to_file = true;
+        let result = io::copy(&mut compressed, self.inner.get_plain());
+        self.ok_or_abort_file(result)?;
+        // Finishing the entry fills in its header from these, as for data written through it
+        self.stats.hasher = Hasher::new_with_initial_len(crc32, uncompressed_size);
+        self.stats.bytes_written = uncompressed_size;
+        self.finish_file()
+    }
+
     /// Add a directory entry.
     ///
+    /// The entry is stored with no data, and its name is given a trailing `/` if it doesn't end
+    /// with one, replacing a trailing `\\`. Its Unix mode and MS-DOS attributes both mark it as a
+    /// directory, so that [`ZipArchive::extract`](crate::ZipArchive::extract) and other tools
+    /// recreate it even if it's empty.
+    ///
     /// As directories have no content, you must not call [`ZipWriter::write`] before adding a new file.
     pub fn add_directory<S, T: FileOptionExtension>(
         &mut self,
@@ -1379,14 +2279,22 @@ impl<W: Write + Seek> ZipWriter<W> {
         options.compression_method = Stored;
         options.encrypt_with = None;
 
-        let name_as_string = name.into();
+        let mut name_with_slash = name.into();
         // Append a slash to the filename if it does not end with it.
-        let name_with_slash = match name_as_string.chars().last() {
-            Some('/') | Some('\\') => name_as_string,
-            _ => name_as_string + "/",
-        };
+        match name_with_slash.pop() {
+            Some('/') | Some('\\') => {}
+            Some(c) => name_with_slash.push(c),
+            None => {}
+        }
+        name_with_slash.push('/');
 
-        self.start_entry(name_with_slash, options, None)?;
+        // A directory's local header is complete as written, so even a stream doesn't need a
+        // data descriptor for it
+        self.finish_file()?;
+        let streaming = mem::replace(&mut self.streaming, false);
+        let result = self.start_entry(name_with_slash, options, None);
+        self.streaming = streaming;
+        result?;
         self.writing_to_file = false;
         self.switch_to_non_encrypting_writer()?;
         Ok(())
@@ -1410,7 +2318,7 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// This will return the writer, but one should normally not append any data to the end of the file.
     /// Note that the zipfile will also be finished on drop.
     pub fn finish(mut self) -> ZipResult<W> {
-        let _central_start = self.finalize()?;
+        let _central_start = self.finalize_or_close()?;
         let inner = mem::replace(&mut self.inner, Closed);
         Ok(inner.unwrap())
     }
@@ -1470,6 +2378,16 @@ impl<W: Write + Seek> ZipWriter<W> {
         self.add_symlink(path_to_string(path), path_to_string(target), options)
     }
 
+    /// Like [`ZipWriter::finalize`], but closes the writer if that fails, so that dropping it
+    /// doesn't try again.
+    fn finalize_or_close(&mut self) -> ZipResult<u64> {
+        let result = self.finalize();
+        if result.is_err() {
+            self.inner = Closed;
+        }
+        result
+    }
+
     fn finalize(&mut self) -> ZipResult<u64> {
         self.finish_file()?;
 
@@ -1493,58 +2411,147 @@ impl<W: Write + Seek> ZipWriter<W> {
             writer.seek(SeekFrom::End(-(central_and_footer_size as i64)))?;
             central_start = self.write_central_and_footer()?;
         }
+        self.inner.get_plain().flush()?;
 
         Ok(central_start)
     }
 
     fn write_central_and_footer(&mut self) -> Result<u64, ZipError> {
+        let volume_size = self.volume_size;
         let writer = self.inner.get_plain();
 
         let mut version_needed = MIN_VERSION as u16;
-        let central_start = writer.stream_position()?;
-        for file in self.files.values() {
-            write_central_directory_header(writer, file)?;
+        let mut central_start = writer.stream_position()?;
+        // The disk holding the last central header, and how many headers it holds
+        let mut last_disk = (0, 0);
+        let mut files: Vec<_> = self.files.values().collect();
+        if self.reproducible_time.is_some() {
+            files.sort_by(|a, b| a.file_name_raw.cmp(&b.file_name_raw));
+        }
+        for (index, file) in files.into_iter().enumerate() {
+            if self.zip64_mode == Zip64Mode::Never && file.zip64_extra_field_block().is_some() {
+                return Err(InvalidArchive("Zip64 is disabled, but an entry needs it"));
+            }
+            match volume_size {
+                Some(volume_size) => {
+                    // Offsets are relative to the volume holding the local header
+                    let (disk, header_offset) =
+                        split::volume_position(volume_size, file.header_start)?;
+                    let mut file = file.clone();
+                    file.header_start = header_offset;
+                    let mut header = Vec::new();
+                    write_central_directory_header(&mut header, &file, disk)?;
+                    let position = split::reserve(writer, volume_size, header.len() as u64)?;
+                    if index == 0 {
+                        central_start = position;
+                    }
+                    let (header_disk, _) = split::volume_position(volume_size, position)?;
+                    last_disk = match last_disk {
+                        (disk, count) if disk == header_disk => (disk, count + 1),
+                        _ => (header_disk, 1),
+                    };
+                    writer.write_all(&header)?;
+                }
+                None => write_central_directory_header(writer, file, 0)?,
+            }
             version_needed = version_needed.max(file.version_needed());
         }
         let central_size = writer.stream_position()? - central_start;
+        let (central_disk, central_offset) = match volume_size {
+            Some(volume_size) => split::volume_position(volume_size, central_start)?,
+            None => (0, central_start),
+        };
 
-        if self.files.len() > spec::ZIP64_ENTRY_THR
-            || central_size.max(central_start) > spec::ZIP64_BYTES_THR
+        // The Zip64 records are needed once a count, size or offset doesn't fit in the end of
+        // central directory record. The maximum values are reserved to mean "see the Zip64
+        // record", so they count as not fitting. Entries larger than 4 GiB always push the
+        // central directory past that offset.
+        let zip64_needed = self.files.len() >= spec::ZIP64_ENTRY_THR
+            || central_size >= spec::ZIP64_BYTES_THR
+            || central_offset >= spec::ZIP64_BYTES_THR;
+        // Without Zip64, exactly 65,535 entries can still be recorded, since readers accept the
+        // maximum count when there's no Zip64 record to defer to
+        if self.zip64_mode == Zip64Mode::Never
+            && (self.files.len() > u16::MAX as usize
+                || central_size >= spec::ZIP64_BYTES_THR
+                || central_offset >= spec::ZIP64_BYTES_THR)
         {
+            return Err(InvalidArchive(
+                "Zip64 is disabled, but the central directory needs it",
+            ));
+        }
+        let write_zip64 = match self.zip64_mode {
+            Zip64Mode::Always => true,
+            Zip64Mode::Auto => zip64_needed,
+            Zip64Mode::Never => false,
+        };
+
+        // The end records are kept together in the last volume
+        let (end_disk, end_offset) = match volume_size {
+            Some(volume_size) => {
+                let end_len = if write_zip64 {
+                    size_of::<spec::Zip64CDEBlock>() + size_of::<spec::Zip64CDELocatorBlock>()
+                } else {
+                    0
+                } + size_of::<Zip32CDEBlock>()
+                    + self.comment.len();
+                if end_len as u64 > volume_size {
+                    return Err(InvalidArchive(
+                        "The end of central directory doesn't fit in a volume",
+                    ));
+                }
+                let position = split::reserve(writer, volume_size, end_len as u64)?;
+                split::volume_position(volume_size, position)?
+            }
+            None => (0, central_start + central_size),
+        };
+        let files_on_end_disk = match last_disk {
+            _ if volume_size.is_none() => self.files.len(),
+            (disk, count) if disk == end_disk => count,
+            _ => 0,
+        };
+
+        if write_zip64 {
             let zip64_footer = spec::Zip64CentralDirectoryEnd {
                 version_made_by: version_needed,
                 version_needed_to_extract: version_needed,
-                disk_number: 0,
-                disk_with_central_directory: 0,
-                number_of_files_on_this_disk: self.files.len() as u64,
+                disk_number: end_disk as u32,
+                disk_with_central_directory: central_disk as u32,
+                number_of_files_on_this_disk: files_on_end_disk as u64,
                 number_of_files: self.files.len() as u64,
                 central_directory_size: central_size,
-                central_directory_offset: central_start,
+                central_directory_offset: central_offset,
             };
 
             zip64_footer.write(writer)?;
 
             let zip64_footer = spec::Zip64CentralDirectoryEndLocator {
-                disk_with_central_directory: 0,
-                end_of_central_directory_offset: central_start + central_size,
-                number_of_disks: 1,
+                disk_with_central_directory: end_disk as u32,
+                end_of_central_directory_offset: end_offset,
+                number_of_disks: end_disk as u32 + 1,
             };
 
             zip64_footer.write(writer)?;
         }
 
-        let number_of_files = self.files.len().min(spec::ZIP64_ENTRY_THR) as u16;
         let footer = spec::Zip32CentralDirectoryEnd {
-            disk_number: 0,
-            disk_with_central_directory: 0,
+            disk_number: end_disk,
+            disk_with_central_directory: central_disk,
             zip_file_comment: self.comment.clone(),
-            number_of_files_on_this_disk: number_of_files,
-            number_of_files,
+            number_of_files_on_this_disk: files_on_end_disk.min(spec::ZIP64_ENTRY_THR) as u16,
+            number_of_files: self.files.len().min(spec::ZIP64_ENTRY_THR) as u16,
             central_directory_size: central_size.min(spec::ZIP64_BYTES_THR) as u32,
-            central_directory_offset: central_start.min(spec::ZIP64_BYTES_THR) as u32,
+            central_directory_offset: central_offset.min(spec::ZIP64_BYTES_THR) as u32,
         };
 
         footer.write(writer)?;
+        if end_disk == 0 && volume_size.is_some() {
+            // An archive that fits in one volume isn't split after all, per APPNOTE 8.5.4
+            let end = writer.stream_position()?;
+            writer.seek(SeekFrom::Start(0))?;
+            writer.write_all(&spec::Magic::SINGLE_VOLUME_SPLIT_SIGNATURE.to_le_bytes())?;
+            writer.seek(SeekFrom::Start(end))?;
+        }
         Ok(central_start)
     }
 
@@ -1559,13 +2566,14 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// some other software (e.g. Minecraft) will refuse to extract a file copied this way.
     pub fn shallow_copy_file(&mut self, src_name: &str, dest_name: &str) -> ZipResult<()> {
         self.finish_file()?;
-        if src_name == dest_name {
+        let dest_name = self.transform_name(dest_name.into())?;
+        if src_name == &*dest_name {
             return Err(InvalidArchive("Trying to copy a file to itself"));
         }
         let src_index = self.index_by_name(src_name)?;
         let mut dest_data = self.files[src_index].to_owned();
-        dest_data.file_name = dest_name.to_string().into();
-        dest_data.file_name_raw = dest_name.to_string().into_bytes().into();
+        dest_data.file_name_raw = dest_name.as_bytes().into();
+        dest_data.file_name = dest_name;
         self.insert_file_data(dest_data)?;
         Ok(())
     }
@@ -1602,6 +2610,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
         compression: CompressionMethod,
         compression_level: Option<i64>,
         #[cfg(feature = "deflate-zopfli")] zopfli_buffer_size: Option<usize>,
+        #[cfg(feature = "zstd")] zstd_window_log: Option<u32>,
     ) -> ZipResult<SwitchWriterFunction<W>> {
         if let Closed = self {
             return Err(
@@ -1684,6 +2693,12 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                 CompressionMethod::Deflate64 => Err(ZipError::UnsupportedArchive(
                     "Compressing Deflate64 is not supported",
                 )),
+                #[cfg(feature = "legacy")]
+                CompressionMethod::Shrink
+                | CompressionMethod::Reduce(_)
+                | CompressionMethod::Implode => Err(ZipError::UnsupportedArchive(
+                    "Compressing with the legacy PKZIP methods is not supported",
+                )),
                 #[cfg(feature = "bzip2")]
                 CompressionMethod::Bzip2 => {
                     let level = clamp_opt(
@@ -1691,7 +2706,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                         bzip2_compression_level_range(),
                     )
                     .ok_or(ZipError::UnsupportedArchive(
-                        "Unsupported compression level",
+                        "Bzip2 block size must be between 1 and 9",
                     ))? as u32;
                     Ok(Box::new(move |bare| {
                         GenericZipWriter::Bzip2(BzEncoder::new(
@@ -1712,8 +2727,17 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                     .ok_or(ZipError::UnsupportedArchive(
                         "Unsupported compression level",
                     ))?;
+                    if let Some(window_log) = zstd_window_log {
+                        clamp_opt(window_log, zstd_window_log_range())
+                            .ok_or(ZipError::UnsupportedArchive("Unsupported Zstd window log"))?;
+                    }
                     Ok(Box::new(move |bare| {
-                        GenericZipWriter::Zstd(ZstdEncoder::new(bare, level as i32).unwrap())
+                        let mut encoder = ZstdEncoder::new(bare, level as i32).unwrap();
+                        if let Some(window_log) = zstd_window_log {
+                            encoder.long_distance_matching(true).unwrap();
+                            encoder.window_log(window_log).unwrap();
+                        }
+                        GenericZipWriter::Zstd(encoder)
                     }))
                 }
                 #[cfg(feature = "lzma")]
@@ -1747,6 +2771,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
             GenericZipWriter::Bzip2(w) => w.finish()?,
             #[cfg(feature = "zstd")]
             GenericZipWriter::Zstd(w) => w.finish()?,
+            GenericZipWriter::Custom(w) => w.finish()?,
             Closed => {
                 return Err(io::Error::new(
                     io::ErrorKind::BrokenPipe,
@@ -1772,6 +2797,7 @@ impl<W: Write + Seek> GenericZipWriter<W> {
             GenericZipWriter::Bzip2(ref mut w) => Some(w as &mut dyn Write),
             #[cfg(feature = "zstd")]
             GenericZipWriter::Zstd(ref mut w) => Some(w as &mut dyn Write),
+            GenericZipWriter::Custom(ref mut w) => Some(w as &mut dyn Write),
             Closed => None,
         }
     }
@@ -1820,6 +2846,36 @@ fn bzip2_compression_level_range() -> std::ops::RangeInclusive<i64> {
     min..=max
 }
 
+#[cfg(feature = "zstd")]
+fn zstd_window_log_range() -> std::ops::RangeInclusive<u32> {
+    10..=crate::compression::ZSTD_WINDOW_LOG_MAX
+}
+
+/// How much of a file [`ZipWriter::add_file_auto`] looks at.
+const AUTO_METHOD_SAMPLE_SIZE: usize = 64 * 1024;
+
+/// Whether `sample` holds so close to 8 bits of information per byte, by its Shannon entropy,
+/// that compressing it would gain nothing.
+fn looks_incompressible(sample: &[u8]) -> bool {
+    if sample.len() < 1024 {
+        return false;
+    }
+    let mut counts = [0usize; 256];
+    for &byte in sample {
+        counts[byte as usize] += 1;
+    }
+    let len = sample.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+    entropy > 7.5
+}
+
 #[cfg(any(feature = "_deflate-any", feature = "bzip2", feature = "zstd"))]
 fn clamp_opt<T: Ord + Copy, U: Ord + Copy + TryFrom<T>>(
     value: T,
@@ -1897,21 +2953,24 @@ fn update_local_file_header<T: Write + Seek>(writer: &mut T, file: &ZipFileData)
     Ok(())
 }
 
-fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+fn write_central_directory_header<T: Write>(
+    writer: &mut T,
+    file: &ZipFileData,
+    disk_number: u16,
+) -> ZipResult<()> {
     // buffer zip64 extra field to determine its variable length
     let mut zip64_extra_field = [0; 28];
     let zip64_extra_field_length =
         write_central_zip64_extra_field(&mut zip64_extra_field.as_mut(), file)?;
-    let block = file.block(zip64_extra_field_length)?;
+    let mut block = file.block(zip64_extra_field_length)?;
+    block.disk_number = disk_number;
     block.write(writer)?;
     // file name
     writer.write_all(&file.file_name_raw)?;
     // zip64 extra field
     writer.write_all(&zip64_extra_field[..zip64_extra_field_length as usize])?;
     // extra field
-    if let Some(extra_field) = &file.extra_field {
-        writer.write_all(extra_field)?;
-    }
+    writer.write_all(&file.central_directory_extra_field())?;
     if let Some(central_extra_field) = &file.central_extra_field {
         writer.write_all(central_extra_field)?;
     }
@@ -1921,10 +2980,47 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
     Ok(())
 }
 
+fn write_data_descriptor<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+    writer.write_all(&spec::Magic::DATA_DESCRIPTOR_SIGNATURE.to_le_bytes())?;
+    writer.write_u32_le(file.crc32)?;
+    // 8-byte sizes are only recognized when the local header has a Zip64 extra field
+    if file.large_file {
+        writer.write_u64_le(file.compressed_size)?;
+        writer.write_u64_le(file.uncompressed_size)?;
+    } else {
+        if file.compressed_size > spec::ZIP64_BYTES_THR {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                "Large file option has not been set",
+            )));
+        }
+        writer.write_u32_le(file.compressed_size as u32)?;
+        writer.write_u32_le(file.uncompressed_size as u32)?;
+    }
+    Ok(())
+}
+
+/// Converts a [`DateTime`], taken as UTC, to seconds since the UNIX epoch.
+fn unix_timestamp(time: DateTime) -> u32 {
+    // Days since 1970-01-01, using the `days_from_civil` algorithm by Howard Hinnant
+    let month = time.month() as i64;
+    let year = time.year() as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + time.day() as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let seconds = days * 86_400
+        + time.hour() as i64 * 3600
+        + time.minute() as i64 * 60
+        + time.second() as i64;
+    seconds.clamp(0, u32::MAX as i64) as u32
+}
+
 fn write_local_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
     // This entry in the Local header MUST include BOTH original
     // and compressed file size fields.
-    let Some(block) = file.zip64_extra_field_block() else {
+    let Some(block) = file.local_zip64_extra_field_block() else {
         return Err(ZipError::InvalidArchive(
             "Attempted to write a ZIP64 extra field for a file that's within zip32 limits",
         ));
@@ -1950,7 +3046,7 @@ fn update_local_zip64_extra_field<T: Write + Seek>(
 
     writer.seek(SeekFrom::Start(zip64_extra_field))?;
 
-    let block = file.zip64_extra_field_block().unwrap();
+    let block = file.local_zip64_extra_field_block().unwrap();
     let block = block.serialize();
     writer.write_all(&block)?;
     Ok(())
@@ -1972,6 +3068,47 @@ fn write_central_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData)
     }
 }
 
+/// Returns the extra fields of a copied entry that still apply to the copy. The Zip64 and AES
+/// fields are left out since the writer generates its own, as is the Unicode path field since
+/// the copy may have been renamed.
+fn copyable_extra_data(data: &[u8]) -> Vec<u8> {
+    let mut copy = Vec::with_capacity(data.len());
+    let mut rest = data;
+    while rest.len() >= 4 {
+        let header_id = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize + 4;
+        if len > rest.len() {
+            break;
+        }
+        if !matches!(header_id, 0x0001 | 0x9901 | 0x7075) {
+            copy.extend_from_slice(&rest[..len]);
+        }
+        rest = &rest[len..];
+    }
+    copy
+}
+
+/// Removes the extra fields holding timestamps or owner IDs, which would make the output depend on
+/// when and by whom it was made: NTFS (0x000a), extended timestamp (0x5455), Info-ZIP Unix
+/// (0x5855), Info-ZIP Unix2 (0x7855) and Info-ZIP new Unix (0x7875).
+fn without_volatile_extra_data(data: &[u8]) -> Vec<u8> {
+    let mut copy = Vec::with_capacity(data.len());
+    let mut rest = data;
+    while rest.len() >= 4 {
+        let header_id = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize + 4;
+        if len > rest.len() {
+            break;
+        }
+        if !matches!(header_id, 0x000a | 0x5455 | 0x5855 | 0x7855 | 0x7875) {
+            copy.extend_from_slice(&rest[..len]);
+        }
+        rest = &rest[len..];
+    }
+    copy.extend_from_slice(rest);
+    copy
+}
+
 #[cfg(not(feature = "unreserved"))]
 const EXTRA_FIELD_MAPPING: [u16; 43] = [
     0x0007, 0x0008, 0x0009, 0x000a, 0x000c, 0x000d, 0x000e, 0x000f, 0x0014, 0x0015, 0x0016, 0x0017,
@@ -1988,6 +3125,7 @@ mod test {
     use super::{ExtendedFileOptions, FileOptions, FullFileOptions, ZipWriter};
     use crate::compression::CompressionMethod;
     use crate::result::ZipResult;
+    use crate::spec;
     use crate::types::DateTime;
     use crate::write::EncryptWith::ZipCrypto;
     use crate::write::SimpleFileOptions;
@@ -2040,7 +3178,7 @@ mod test {
                 80u8, 75, 3, 4, 20, 0, 0, 0, 0, 0, 163, 165, 15, 77, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
                 0, 0, 5, 0, 0, 0, 116, 101, 115, 116, 47, 80, 75, 1, 2, 20, 3, 20, 0, 0, 0, 0, 0,
                 163, 165, 15, 77, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                0, 0, 237, 65, 0, 0, 0, 0, 116, 101, 115, 116, 47, 80, 75, 5, 6, 0, 0, 0, 0, 1, 0,
+                16, 0, 237, 65, 0, 0, 0, 0, 116, 101, 115, 116, 47, 80, 75, 5, 6, 0, 0, 0, 0, 1, 0,
                 1, 0, 51, 0, 0, 0, 35, 0, 0, 0, 0, 0,
             ] as &[u8]
         );
@@ -2135,8 +3273,13 @@ mod test {
             encrypt_with: None,
             extended_options: (),
             alignment: 1,
+            unix_metadata: None,
+            version_needed: None,
+            comment: None,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
         };
         writer.start_file("mimetype", options).unwrap();
         writer
@@ -2172,8 +3315,13 @@ mod test {
             encrypt_with: None,
             extended_options: (),
             alignment: 1,
+            unix_metadata: None,
+            version_needed: None,
+            comment: None,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
         };
 
         // GB18030
@@ -2224,8 +3372,13 @@ mod test {
             encrypt_with: None,
             extended_options: (),
             alignment: 0,
+            unix_metadata: None,
+            version_needed: None,
+            comment: None,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
         };
         writer.start_file(RT_TEST_FILENAME, options).unwrap();
         writer.write_all(RT_TEST_TEXT.as_ref()).unwrap();
@@ -2274,8 +3427,13 @@ mod test {
             encrypt_with: None,
             extended_options: (),
             alignment: 0,
+            unix_metadata: None,
+            version_needed: None,
+            comment: None,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_window_log: None,
         };
         writer.start_file(RT_TEST_FILENAME, options).unwrap();
         writer.write_all(RT_TEST_TEXT.as_ref()).unwrap();
@@ -3516,4 +4674,1209 @@ mod test {
         assert!(archive.comment().starts_with(&[33]));
         Ok(())
     }
+
+    #[test]
+    fn raw_copy_keeps_method_crc_and_extra_fields() -> ZipResult<()> {
+        let mut source = ZipArchive::new(Cursor::new(
+            &include_bytes!("../tests/data/deflate64.zip")[..],
+        ))?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        // Deflate64 can't be written, but copying doesn't need a compressor
+        let file = source.by_name_raw("binary.wmv")?;
+        let crc32 = file.crc32();
+        let extra_data = file.extra_data().unwrap().to_vec();
+        writer.raw_copy_file(file)?;
+        let mut copy = writer.finish_into_readable()?;
+
+        let file = copy.by_name_raw("binary.wmv")?;
+        assert_eq!(file.compression(), CompressionMethod::DEFLATE64);
+        assert_eq!(file.crc32(), crc32);
+        // The NTFS timestamps (0x000a) are kept
+        assert_eq!(file.extra_data(), Some(&extra_data[..]));
+        drop(file);
+        #[cfg(feature = "deflate64")]
+        {
+            let mut original = Vec::new();
+            source.by_name("binary.wmv")?.read_to_end(&mut original)?;
+            let mut copied = Vec::new();
+            copy.by_name("binary.wmv")?.read_to_end(&mut copied)?;
+            assert_eq!(original, copied);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn raw_copy_rename_drops_unicode_path() -> ZipResult<()> {
+        let mut source = ZipArchive::new(Cursor::new(
+            &include_bytes!("../tests/data/windows-7zip.zip")[..],
+        ))?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.raw_copy_file_rename(source.by_index_raw(0)?, "renamed.txt")?;
+        let mut copy = writer.finish_into_readable()?;
+        assert_eq!(copy.file_names().collect::<Vec<_>>(), ["renamed.txt"]);
+        assert!(copy.by_name("renamed.txt").is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn unix_timestamp() {
+        use super::unix_timestamp;
+        assert_eq!(unix_timestamp(DateTime::default()), 315532800);
+        let time = DateTime::from_date_and_time(2024, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(unix_timestamp(time), 1704164645);
+        let time = DateTime::from_date_and_time(2000, 2, 29, 23, 59, 58).unwrap();
+        assert_eq!(unix_timestamp(time), 951868798);
+    }
+
+    #[test]
+    fn write_unix_metadata() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .last_modified_time(DateTime::from_date_and_time(2024, 1, 1, 0, 0, 0).unwrap())
+            .unix_metadata(1234, 5678, 1704164645);
+        writer.start_file("owned.txt", options)?;
+        writer.write_all(b"owned")?;
+        let bytes = writer.finish()?.into_inner();
+
+        // The central directory only holds the modification time
+        let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+        let file = archive.by_name("owned.txt")?;
+        let unix_extra = file.unix_extra().unwrap();
+        assert_eq!(unix_extra.mtime(), Some(1704067200));
+        assert_eq!(unix_extra.atime(), None);
+        assert_eq!(unix_extra.uid(), Some(1234));
+        assert_eq!(unix_extra.gid(), Some(5678));
+        assert_eq!(
+            &file.extra_data().unwrap()[..9],
+            b"UT\x05\x00\x03\x80\x00\x92\x65"
+        );
+        drop(file);
+
+        let mut reader = Cursor::new(&bytes);
+        let file = crate::read::read_zipfile_from_stream(&mut reader)?.unwrap();
+        let unix_extra = file.unix_extra().unwrap();
+        assert_eq!(unix_extra.mtime(), Some(1704067200));
+        assert_eq!(unix_extra.atime(), Some(1704164645));
+        assert_eq!(unix_extra.uid(), Some(1234));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "_deflate-any")]
+    fn append_preserves_existing_entries() -> ZipResult<()> {
+        for (original, name) in [
+            (
+                &include_bytes!("../tests/data/data_descriptor.zip")[..],
+                "hello.txt",
+            ),
+            (&include_bytes!("../tests/data/zip64_demo.zip")[..], "-"),
+        ] {
+            let mut archive = ZipArchive::new(Cursor::new(original))?;
+            let mut expected = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut expected)?;
+            let data_end = original
+                .windows(4)
+                .position(|magic| magic == b"PK\x01\x02")
+                .unwrap();
+
+            let mut writer = ZipWriter::new_append(Cursor::new(original.to_vec()))?;
+            writer.start_file("appended.txt", SimpleFileOptions::default())?;
+            writer.write_all(b"appended")?;
+            let appended = writer.finish()?.into_inner();
+
+            // The existing local headers and data are left untouched
+            assert_eq!(&appended[..data_end], &original[..data_end]);
+            let mut archive = ZipArchive::new(Cursor::new(appended))?;
+            assert_eq!(archive.len(), 2);
+            let mut contents = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut contents)?;
+            assert_eq!(contents, expected);
+            contents.clear();
+            archive
+                .by_name("appended.txt")?
+                .read_to_end(&mut contents)?;
+            assert_eq!(contents, b"appended");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn remove_file() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["first.txt", "second.txt", "third.txt"] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(name.as_bytes())?;
+        }
+        writer.shallow_copy_file("third.txt", "copy.txt")?;
+        let mut writer = ZipWriter::new_append(writer.finish()?)?;
+        writer.remove_file("second.txt")?;
+        writer.start_file("fourth.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"fourth.txt")?;
+        writer.remove_file("third.txt")?;
+        assert!(matches!(
+            writer.remove_file("second.txt"),
+            Err(crate::result::ZipError::FileNotFound)
+        ));
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["first.txt", "copy.txt", "fourth.txt"]
+        );
+        for (name, contents) in [
+            ("first.txt", "first.txt"),
+            ("copy.txt", "third.txt"),
+            ("fourth.txt", "fourth.txt"),
+        ] {
+            let mut read_contents = String::new();
+            archive.by_name(name)?.read_to_string(&mut read_contents)?;
+            assert_eq!(read_contents, contents);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn remove_file_with_data_descriptor() -> ZipResult<()> {
+        let mut writer = ZipWriter::new_append(Cursor::new(
+            include_bytes!("../tests/data/data_descriptor.zip").to_vec(),
+        ))?;
+        writer.start_file("appended.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"appended")?;
+        writer.remove_file("hello.txt")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.len(), 1);
+        let file = archive.by_index(0)?;
+        assert_eq!(file.name(), "appended.txt");
+        assert_eq!(file.header_start(), 0);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd_options() -> ZipResult<()> {
+        let mut data = Vec::new();
+        for i in 0..1024u32 {
+            data.extend_from_slice(format!("line {}\n", i % 100).as_bytes());
+        }
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, level, window_log) in [
+            ("fast.txt", -5, None),
+            ("long.txt", 19, Some(28)),
+            ("small_window.txt", 3, Some(10)),
+        ] {
+            writer.start_file(
+                name,
+                SimpleFileOptions::default().zstd_options(level, window_log),
+            )?;
+            writer.write_all(&data)?;
+        }
+        for window_log in [9, 32] {
+            assert!(writer
+                .start_file(
+                    "invalid.txt",
+                    SimpleFileOptions::default().zstd_options(3, Some(window_log)),
+                )
+                .is_err());
+        }
+        let mut archive = writer.finish_into_readable()?;
+        assert!(archive
+            .by_name("long.txt")?
+            .read_to_end(&mut Vec::new())
+            .is_err());
+        let options = crate::read::ReadOptions::default().zstd_window_log_max(28);
+        for name in ["fast.txt", "long.txt", "small_window.txt"] {
+            let mut file = archive.by_name_with_options(name, options)?;
+            assert_eq!(file.compression(), CompressionMethod::Zstd);
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            assert_eq!(contents, data);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn zip64_only_when_needed() -> ZipResult<()> {
+        const ZIP64_END_SIGNATURE: &[u8] = b"PK\x06\x06";
+        let options = SimpleFileOptions::default().compression_method(Stored);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("small.txt", options)?;
+        writer.write_all(b"small")?;
+        writer.start_file("large.txt", options.large_file(true))?;
+        writer.write_all(b"large")?;
+        let bytes = writer.finish()?.into_inner();
+        assert!(!bytes
+            .windows(ZIP64_END_SIGNATURE.len())
+            .any(|window| window == ZIP64_END_SIGNATURE));
+        // The local header of the large entry has a Zip64 field with both sizes, but its central
+        // header doesn't need one
+        let large_start = 30 + "small.txt".len() + "small".len();
+        let zip64_field_start = large_start + 30 + "large.txt".len();
+        assert_eq!(
+            &bytes[zip64_field_start..][..20],
+            b"\x01\x00\x10\x00\x05\0\0\0\0\0\0\0\x05\0\0\0\0\0\0\0"
+        );
+        let central_start = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        assert!(!bytes[central_start..]
+            .windows(4)
+            .any(|window| window == b"\x01\x00\x10\x00"));
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.by_name("large.txt")?.size(), 5);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..=spec::ZIP64_ENTRY_THR {
+            writer.start_file(i.to_string(), options)?;
+            writer.write_all(&[i as u8])?;
+        }
+        let bytes = writer.finish()?.into_inner();
+        assert!(bytes
+            .windows(ZIP64_END_SIGNATURE.len())
+            .any(|window| window == ZIP64_END_SIGNATURE));
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.len(), spec::ZIP64_ENTRY_THR + 1);
+        let mut file = archive.by_name("65535")?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        assert_eq!(content, [255]);
+        Ok(())
+    }
+
+    #[test]
+    fn zip64_mode() -> ZipResult<()> {
+        use super::Zip64Mode;
+        let options = SimpleFileOptions::default().compression_method(Stored);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_zip64_mode(Zip64Mode::Always);
+        writer.start_file("small.txt", options)?;
+        writer.write_all(b"small")?;
+        let bytes = writer.finish()?.into_inner();
+        // Zip64 field in the local header
+        assert_eq!(&bytes[30 + "small.txt".len()..][..4], b"\x01\x00\x10\x00");
+        // Zip64 end of central directory record
+        assert!(bytes.windows(4).any(|window| window == b"PK\x06\x06"));
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut content = String::new();
+        archive.by_name("small.txt")?.read_to_string(&mut content)?;
+        assert_eq!(content, "small");
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_zip64_mode(crate::write::Zip64Mode::Never);
+        assert!(matches!(
+            writer.start_file("large.txt", options.large_file(true)),
+            Err(crate::result::ZipError::InvalidArchive(_))
+        ));
+        for i in 0..spec::ZIP64_ENTRY_THR {
+            writer.start_file(i.to_string(), options)?;
+        }
+        let bytes = writer.finish()?.into_inner();
+        assert!(!bytes.windows(4).any(|window| window == b"PK\x06\x06"));
+        assert_eq!(
+            ZipArchive::new(Cursor::new(bytes))?.len(),
+            spec::ZIP64_ENTRY_THR
+        );
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_zip64_mode(crate::write::Zip64Mode::Never);
+        for i in 0..=spec::ZIP64_ENTRY_THR {
+            writer.start_file(i.to_string(), options)?;
+        }
+        assert!(matches!(
+            writer.finish(),
+            Err(crate::result::ZipError::InvalidArchive(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn split_archive() -> ZipResult<()> {
+        use crate::read::MultiVolumeReader;
+
+        let dir = tempdir::TempDir::new("split_archive")?;
+        let path = dir.path().join("split.zip");
+        // Hard to compress, so that it spans volumes
+        let mut state = 1u32;
+        let large: Vec<u8> = (0..150_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let mut writer = ZipWriter::new_split(&path, 64 * 1024)?;
+        writer.start_file("small.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"small")?;
+        writer.start_file(
+            "large.bin",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(&large)?;
+        writer.start_file("tail.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"tail")?;
+        writer.set_comment("split");
+        assert_eq!(writer.finish()?.volume_count(), 3);
+        assert!(dir.path().join("split.z01").exists());
+        assert!(dir.path().join("split.z02").exists());
+        assert_eq!(
+            std::fs::read(dir.path().join("split.z01"))?[..4],
+            *b"PK\x07\x08"
+        );
+
+        let volumes = MultiVolumeReader::open(&path, dir.path())?;
+        let mut archive = ZipArchive::new_multi_volume(volumes)?;
+        assert_eq!(archive.comment(), b"split");
+        let mut content = Vec::new();
+        archive.by_name("large.bin")?.read_to_end(&mut content)?;
+        assert_eq!(content, large);
+        for (name, expected) in [("small.txt", &b"small"[..]), ("tail.txt", b"tail")] {
+            content.clear();
+            archive.by_name(name)?.read_to_end(&mut content)?;
+            assert_eq!(content, expected);
+        }
+
+        // Fits in one volume, so it's an ordinary archive
+        let path = dir.path().join("single.zip");
+        let mut writer = ZipWriter::new_split(&path, 64 * 1024)?;
+        writer.start_file("small.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"small")?;
+        assert_eq!(writer.finish()?.volume_count(), 1);
+        assert_eq!(std::fs::read(&path)?[..4], *b"PK00");
+        let mut archive = ZipArchive::new(std::fs::File::open(&path)?)?;
+        content.clear();
+        archive.by_name("small.txt")?.read_to_end(&mut content)?;
+        assert_eq!(content, b"small");
+
+        assert!(ZipWriter::new_split(dir.path().join("tiny.zip"), 1024).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn merge_archive_name_collisions() -> ZipResult<()> {
+        use super::NameCollision;
+
+        let source = || -> ZipResult<ZipArchive<Cursor<Vec<u8>>>> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.add_directory("dir", SimpleFileOptions::default())?;
+            writer.start_file("dir/a.txt", SimpleFileOptions::default())?;
+            writer.write_all(b"new")?;
+            writer.start_file("b.txt", SimpleFileOptions::default())?;
+            writer.write_all(b"b")?;
+            writer.finish_into_readable()
+        };
+        for (policy, names, content) in [
+            (
+                NameCollision::Overwrite,
+                &["dir/", "dir/a.txt", "b.txt"][..],
+                "new",
+            ),
+            (NameCollision::Skip, &["dir/", "dir/a.txt", "b.txt"], "old"),
+            (
+                NameCollision::Rename,
+                &["dir/", "dir/a.txt", "b.txt", "dir/a (1).txt"],
+                "old",
+            ),
+        ] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.add_directory("dir", SimpleFileOptions::default())?;
+            writer.start_file("dir/a.txt", SimpleFileOptions::default())?;
+            writer.write_all(b"old")?;
+            writer.merge_archive_with(source()?, policy)?;
+            let mut archive = ZipArchive::new(writer.finish()?)?;
+            assert_eq!(archive.file_names().collect::<Vec<_>>(), names);
+            let mut read = String::new();
+            archive.by_name("dir/a.txt")?.read_to_string(&mut read)?;
+            assert_eq!(read, content);
+            if policy == NameCollision::Rename {
+                read.clear();
+                let mut renamed = archive.by_name("dir/a (1).txt")?;
+                renamed.read_to_string(&mut read)?;
+                assert_eq!(read, "new");
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn bzip2_block_size() -> ZipResult<()> {
+        let content = b"bzip2 ".repeat(1000);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "small.txt",
+            SimpleFileOptions::default().bzip2_block_size(1),
+        )?;
+        writer.write_all(&content)?;
+        writer.start_file(
+            "large.txt",
+            SimpleFileOptions::default().bzip2_block_size(9),
+        )?;
+        writer.write_all(&content)?;
+        assert!(matches!(
+            writer.start_file(
+                "invalid.txt",
+                SimpleFileOptions::default().bzip2_block_size(10)
+            ),
+            Err(crate::result::ZipError::UnsupportedArchive(_))
+        ));
+        let mut archive = writer.finish_into_readable()?;
+        for (index, name, header) in [(0, "small.txt", b"BZh1"), (1, "large.txt", b"BZh9")] {
+            let mut raw = [0; 4];
+            archive.by_index_raw(index)?.read_exact(&mut raw)?;
+            assert_eq!(&raw, header);
+            let mut read = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut read)?;
+            assert_eq!(read, content);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn add_file_auto() -> ZipResult<()> {
+        let mut state = 1u32;
+        let random: Vec<u8> = (0..100_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let text = b"Lorem ipsum dolor sit amet. ".repeat(4000);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_file_auto("random.bin", options, &random[..])?;
+        writer.add_file_auto("text.txt", options, &text[..])?;
+        writer.add_file_auto("short.bin", options, &random[..100])?;
+        let bytes = writer.finish()?.into_inner();
+
+        // The local header agrees with the central directory
+        assert_eq!(&bytes[8..10], &[0, 0]);
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        for (name, method, content) in [
+            ("random.bin", Stored, &random[..]),
+            ("text.txt", CompressionMethod::Deflated, &text[..]),
+            ("short.bin", CompressionMethod::Deflated, &random[..100]),
+        ] {
+            let mut file = archive.by_name(name)?;
+            assert_eq!(file.compression(), method);
+            let mut read = Vec::new();
+            file.read_to_end(&mut read)?;
+            assert_eq!(read, content);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn validate_options() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let aes_method = SimpleFileOptions::default().compression_method(CompressionMethod::AES);
+        assert!(matches!(
+            aes_method.validate(),
+            Err(crate::result::ZipError::UnsupportedArchive(_))
+        ));
+        let stored_level = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .compression_level(Some(3));
+        assert!(matches!(
+            writer.start_file("stored.txt", stored_level),
+            Err(crate::result::ZipError::UnsupportedArchive(
+                "Unsupported compression level"
+            ))
+        ));
+        #[cfg(feature = "zstd")]
+        {
+            let window_log = SimpleFileOptions::default()
+                .zstd_options(3, Some(20))
+                .compression_method(Stored);
+            assert!(window_log.validate().is_err());
+        }
+
+        writer.set_zip64_mode(crate::write::Zip64Mode::Never);
+        let large = SimpleFileOptions::default().large_file(true);
+        assert!(large.validate().is_ok());
+        assert!(writer.start_file("large.txt", large).is_err());
+        assert!(!writer.is_writing_file());
+        writer.start_file("ok.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"ok")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive.by_index(0)?.name(), "ok.txt");
+        Ok(())
+    }
+
+    #[test]
+    fn flush_policy() -> ZipResult<()> {
+        use crate::write::FlushPolicy;
+
+        /// Records how many bytes had been written at each flush.
+        struct FlushRecorder {
+            inner: Cursor<Vec<u8>>,
+            flushes: Vec<usize>,
+        }
+
+        impl Write for FlushRecorder {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.inner.write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushes.push(self.inner.get_ref().len());
+                Ok(())
+            }
+        }
+
+        impl std::io::Seek for FlushRecorder {
+            fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        for policy in [FlushPolicy::EachEntry, FlushPolicy::OnFinish] {
+            let mut writer = ZipWriter::new(FlushRecorder {
+                inner: Cursor::new(Vec::new()),
+                flushes: Vec::new(),
+            });
+            writer.set_flush_policy(policy);
+            writer.start_file("a.txt", options)?;
+            writer.write_all(b"first")?;
+            writer.start_file("b.txt", options)?;
+            writer.write_all(b"second")?;
+            let recorder = writer.finish()?;
+
+            let first_end = 30 + 5 + 5;
+            let second_end = first_end + 30 + 5 + 6;
+            let end = recorder.inner.get_ref().len();
+            match policy {
+                FlushPolicy::EachEntry => {
+                    assert_eq!(recorder.flushes, vec![first_end, second_end, end])
+                }
+                FlushPolicy::OnFinish => assert_eq!(recorder.flushes, vec![end]),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn write_precompressed() -> ZipResult<()> {
+        let data = b"Compressed by another process. ".repeat(20);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.write_precompressed(
+            "stored.txt",
+            Stored,
+            crc32fast::hash(&data),
+            &data[..],
+            data.len() as u64,
+        )?;
+        #[cfg(feature = "_deflate-any")]
+        {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&data)?;
+            let compressed = encoder.finish()?;
+            writer.write_precompressed(
+                "deflated.txt",
+                CompressionMethod::Deflated,
+                crc32fast::hash(&data),
+                &compressed[..],
+                data.len() as u64,
+            )?;
+        }
+        // A wrong CRC-32 is recorded as given, and caught on reading
+        writer.write_precompressed("bad_crc.txt", Stored, 0, &data[..], data.len() as u64)?;
+        assert!(!writer.is_writing_file());
+
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        let mut content = Vec::new();
+        archive.by_name("stored.txt")?.read_to_end(&mut content)?;
+        assert_eq!(content, data);
+        #[cfg(feature = "_deflate-any")]
+        {
+            let mut file = archive.by_name("deflated.txt")?;
+            assert_eq!(file.compression(), CompressionMethod::Deflated);
+            content.clear();
+            file.read_to_end(&mut content)?;
+            assert_eq!(content, data);
+        }
+        let mut file = archive.by_name("bad_crc.txt")?;
+        assert_eq!(file.crc32(), 0);
+        assert!(file.read_to_end(&mut Vec::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn reproducible() -> ZipResult<()> {
+        let build = |time: DateTime, uid: u32| -> ZipResult<Vec<u8>> {
+            let mut options = FullFileOptions::default()
+                .compression_method(Stored)
+                .last_modified_time(time)
+                .unix_metadata(uid, uid, uid);
+            let mut timestamp = vec![1];
+            timestamp.extend(uid.to_le_bytes());
+            options.add_extra_data(0x5455, timestamp.into_boxed_slice(), false)?;
+            options.add_extra_data(0xbeef, Box::new([1, 2, 3]), false)?;
+
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.set_reproducible(true);
+            writer.start_file("b.txt", options.clone())?;
+            writer.write_all(b"second by name")?;
+            writer.add_directory("a/", options)?;
+            Ok(writer.finish()?.into_inner())
+        };
+        let first = build(DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6)?, 1000)?;
+        let second = build(DateTime::from_date_and_time(2024, 5, 6, 7, 8, 10)?, 0)?;
+        assert_eq!(crc32fast::hash(&first), crc32fast::hash(&second));
+        assert_eq!(first, second);
+
+        let mut archive = ZipArchive::new(Cursor::new(first))?;
+        assert_eq!(archive.by_index(0)?.name(), "a/");
+        let file = archive.by_index(1)?;
+        assert_eq!(file.name(), "b.txt");
+        assert_eq!(file.last_modified(), Some(DateTime::default()));
+        assert_eq!(file.extra_data(), Some(&[0xef, 0xbe, 3, 0, 1, 2, 3][..]));
+        drop(file);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let time = DateTime::from_date_and_time(2021, 6, 7, 8, 9, 10)?;
+        writer.set_reproducible_time(time);
+        writer.start_file("file.txt", SimpleFileOptions::default())?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.by_index(0)?.last_modified(), Some(time));
+        Ok(())
+    }
+
+    #[test]
+    fn explicit_version_needed() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file("default.txt", options)?;
+        writer.start_file("explicit.txt", options.version_needed(20))?;
+        writer.add_directory("dir", options.version_needed(30))?;
+        assert!(matches!(
+            writer.start_file("too_low.txt", options.large_file(true).version_needed(20)),
+            Err(crate::result::ZipError::InvalidArchive(_))
+        ));
+        assert!(!writer.is_writing_file());
+        let bytes = writer.finish()?.into_inner();
+
+        // The local header's version needed is just after its signature
+        let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+        for (name, version) in [("default.txt", 10), ("explicit.txt", 20), ("dir/", 30)] {
+            let header_start = archive.by_name(name)?.header_start() as usize;
+            assert_eq!(
+                u16::from_le_bytes([bytes[header_start + 4], bytes[header_start + 5]]),
+                version,
+                "{name}"
+            );
+        }
+        assert_eq!(archive.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn name_transform() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_name_transform(|name| match name.strip_prefix("tmp/") {
+            Some(rest) => Ok(rest.to_uppercase().into()),
+            None => Err(crate::result::ZipError::InvalidArchive("Not under tmp/")),
+        });
+        writer.start_file("tmp/file.txt", SimpleFileOptions::default())?;
+        writer.add_directory("tmp/dir", SimpleFileOptions::default())?;
+        writer.shallow_copy_file("FILE.TXT", "tmp/copy.txt")?;
+        assert!(writer
+            .start_file("other.txt", SimpleFileOptions::default())
+            .is_err());
+        assert!(!writer.is_writing_file());
+        writer.clear_name_transform();
+        writer.start_file("other.txt", SimpleFileOptions::default())?;
+
+        let archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["FILE.TXT", "DIR/", "COPY.TXT", "other.txt"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn add_empty_directories() -> ZipResult<()> {
+        use crate::HasZipMetadata;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("empty", options)?;
+        writer.add_directory("backslash\\", options)?;
+        writer.add_directory("parent/nested/", options)?;
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["empty/", "backslash/", "parent/nested/"]
+        );
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            assert!(file.is_dir());
+            assert_eq!(file.compression(), Stored);
+            assert_eq!(
+                (file.crc32(), file.size(), file.compressed_size()),
+                (0, 0, 0)
+            );
+            assert_eq!(file.get_metadata().external_attributes & 0x10, 0x10);
+            assert_eq!(file.unix_mode(), Some(0o40755));
+        }
+
+        let tempdir = tempdir::TempDir::new("add_empty_directories")?;
+        archive.extract(tempdir.path())?;
+        for name in ["empty", "backslash", "parent/nested"] {
+            let path = tempdir.path().join(name);
+            assert!(path.is_dir());
+            assert_eq!(std::fs::read_dir(path)?.count(), 0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn deep_copy_keeps_compressed_data() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("original.txt", SimpleFileOptions::default())?;
+        writer.write_all(&b"duplicated asset ".repeat(100))?;
+        writer.start_file(
+            "encrypted.txt",
+            SimpleFileOptions::default().with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(b"secret")?;
+        writer.deep_copy_file("original.txt", "copy.txt")?;
+        writer.deep_copy_file("encrypted.txt", "encrypted copy.txt")?;
+        writer.start_file("after.txt", SimpleFileOptions::default())?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut raw_values = |index| -> ZipResult<_> {
+            let file = archive.by_index_raw(index)?;
+            Ok((file.crc32(), file.compressed_size(), file.size()))
+        };
+        assert_eq!(raw_values(0)?, raw_values(2)?);
+        let mut contents = Vec::new();
+        archive.by_name("copy.txt")?.read_to_end(&mut contents)?;
+        assert_eq!(contents, b"duplicated asset ".repeat(100));
+        contents.clear();
+        archive
+            .by_name_decrypt("encrypted copy.txt", b"password")?
+            .read_to_end(&mut contents)?;
+        assert_eq!(contents, b"secret");
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            [
+                "original.txt",
+                "encrypted.txt",
+                "copy.txt",
+                "encrypted copy.txt",
+                "after.txt"
+            ]
+        );
+
+        #[cfg(feature = "aes-crypto")]
+        {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.start_file(
+                "aes.txt",
+                SimpleFileOptions::default().with_aes_encryption(crate::AesMode::Aes256, "pw"),
+            )?;
+            writer.write_all(b"secret")?;
+            writer.deep_copy_file("aes.txt", "aes copy.txt")?;
+            let mut archive = ZipWriter::new_append(writer.finish()?)?.finish_into_readable()?;
+            let mut contents = Vec::new();
+            archive
+                .by_name_decrypt("aes copy.txt", b"pw")?
+                .read_to_end(&mut contents)?;
+            assert_eq!(contents, b"secret");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn stored_file_is_read_once() -> ZipResult<()> {
+        struct CountingReader<R> {
+            inner: R,
+            bytes_read: u64,
+        }
+        impl<R: Read> Read for CountingReader<R> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let count = self.inner.read(buf)?;
+                self.bytes_read += count as u64;
+                Ok(count)
+            }
+        }
+
+        let data: Vec<u8> = (0..1 << 20).map(|i: u32| (i * 7 % 251) as u8).collect();
+        let mut source = CountingReader {
+            inner: &data[..],
+            bytes_read: 0,
+        };
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "big.bin",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        io::copy(&mut source, &mut writer)?;
+        assert_eq!(source.bytes_read, data.len() as u64);
+
+        // Opening the entry checks that the local header was updated with the sizes
+        let mut archive = ZipArchive::new_strict(writer.finish()?)?;
+        let mut file = archive.by_name("big.bin")?;
+        assert_eq!(file.crc32(), crc32fast::hash(&data));
+        assert_eq!(file.compressed_size(), data.len() as u64);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        assert!(contents == data);
+        Ok(())
+    }
+
+    #[test]
+    fn abort_file_after_source_error() -> ZipResult<()> {
+        struct FailingReader(usize);
+        impl Read for FailingReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.0 == 0 {
+                    return Err(io::Error::new(io::ErrorKind::Other, "source failed"));
+                }
+                let count = buf.len().min(self.0);
+                buf[..count].fill(b'x');
+                self.0 -= count;
+                Ok(count)
+            }
+        }
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("first.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"first")?;
+        writer.start_file("partial.txt", SimpleFileOptions::default())?;
+        assert!(io::copy(&mut FailingReader(100_000), &mut writer).is_err());
+        writer.abort_file()?;
+        assert!(!writer.is_writing_file());
+        writer.start_file("second.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"second")?;
+
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["first.txt", "second.txt"]
+        );
+        let mut contents = String::new();
+        archive
+            .by_name("second.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "second");
+        // The second file took the place of the aborted one
+        let layout = archive.entry_layout()?;
+        assert_eq!(
+            layout[1].header_start,
+            layout[0].data_start + layout[0].compressed_size
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unicode_comments() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment("archive 📦");
+        writer.start_file(
+            "ascii.txt",
+            SimpleFileOptions::default().comment("café ☕ 🎉"),
+        )?;
+        writer.write_all(b"contents")?;
+        let comment = String::from("plain");
+        writer.start_file("plain.txt", SimpleFileOptions::default().comment(&comment))?;
+        let bytes = writer.finish()?.into_inner();
+
+        // Only the entry with a non-ASCII comment has the UTF-8 flag in its central header
+        let flags: Vec<u16> = bytes
+            .windows(4)
+            .enumerate()
+            .filter(|(_, window)| *window == b"PK\x01\x02")
+            .map(|(start, _)| u16::from_le_bytes([bytes[start + 8], bytes[start + 9]]))
+            .collect();
+        assert_eq!(flags.len(), 2);
+        assert_ne!(flags[0] & (1 << 11), 0);
+        assert_eq!(flags[1] & (1 << 11), 0);
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.comment_str(), "archive 📦");
+        assert_eq!(archive.by_name("ascii.txt")?.comment(), "café ☕ 🎉");
+        assert_eq!(archive.by_name("plain.txt")?.comment(), "plain");
+
+        assert!(SimpleFileOptions::default()
+            .comment(&"x".repeat(u16::MAX as usize + 1))
+            .validate()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn custom_compression_registry() -> ZipResult<()> {
+        use crate::CompressionRegistry;
+        use std::io::Read;
+
+        struct Reversed<W: Write>(W, Vec<u8>);
+        impl<W: Write> Write for Reversed<W> {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.1.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        impl<W: Write> Drop for Reversed<W> {
+            fn drop(&mut self) {
+                self.1.reverse();
+                let _ = self.0.write_all(&self.1);
+            }
+        }
+
+        let reversed = CompressionMethod::parse_from_u16(0x4242);
+        let failing = CompressionMethod::parse_from_u16(0x4243);
+        let registry = CompressionRegistry::default()
+            .with_encoder(reversed, |writer| {
+                Ok(Box::new(Reversed(writer, Vec::new())))
+            })
+            .with_encoder(failing, |_| {
+                Err(io::Error::new(io::ErrorKind::Other, "no encoder"))
+            })
+            .with_decoder(reversed, |mut reader| {
+                let mut contents = Vec::new();
+                reader.read_to_end(&mut contents)?;
+                contents.reverse();
+                Ok(Box::new(Cursor::new(contents)))
+            });
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_compression_registry(registry);
+        let options = SimpleFileOptions::default();
+        assert!(writer
+            .start_file("failed.txt", options.compression_method(failing))
+            .is_err());
+        writer.start_file("reversed.txt", options.compression_method(reversed))?;
+        writer.write_all(b"abcdef")?;
+        writer.start_file("stored.txt", options)?;
+        writer.write_all(b"ghi")?;
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(archive.len(), 2);
+        let mut file = archive.by_name("reversed.txt")?;
+        assert_eq!(file.compression(), reversed);
+        assert_eq!(file.compressed_size(), 6);
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, "abcdef");
+        drop(file);
+        contents.clear();
+        archive
+            .by_name("stored.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "ghi");
+        Ok(())
+    }
+
+    #[test]
+    fn raw_copy_unsupported_method() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "ppmd.bin",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"not really ppmd")?;
+        let mut bytes = writer.finish()?.into_inner();
+        // Relabel the entry in its local header and central directory entry as PPMd
+        for (signature, method_offset) in [
+            (spec::Magic::LOCAL_FILE_HEADER_SIGNATURE, 8),
+            (spec::Magic::CENTRAL_DIRECTORY_HEADER_SIGNATURE, 10),
+        ] {
+            let signature = signature.to_le_bytes();
+            let start = bytes
+                .windows(4)
+                .position(|window| window == signature)
+                .unwrap()
+                + method_offset;
+            assert_eq!(bytes[start..start + 2], [0, 0]);
+            bytes[start..start + 2].copy_from_slice(&98u16.to_le_bytes());
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.by_index_raw(0)?.compression().to_u16(), 98);
+        assert!(matches!(
+            archive.by_index(0),
+            Err(crate::result::ZipError::UnsupportedArchive(_))
+        ));
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.raw_copy_file(archive.by_index_raw(0)?)?;
+        let mut copy = ZipArchive::new(writer.finish()?)?;
+        let file = copy.by_index_raw(0)?;
+        assert_eq!(file.compression(), CompressionMethod::PPMD);
+        assert_eq!(file.compressed_size(), 15);
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_method_with_custom_decoder() -> ZipResult<()> {
+        use crate::CompressionRegistry;
+        use std::io::Read;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        writer.start_file(
+            "encrypted.bin",
+            options.with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(b"secret")?;
+        writer.start_file("plain.bin", options)?;
+        writer.write_all(b"plain")?;
+        let mut bytes = writer.finish()?.into_inner();
+        // Relabel every entry as PPMd
+        for (signature, method_offset) in [
+            (spec::Magic::LOCAL_FILE_HEADER_SIGNATURE, 8),
+            (spec::Magic::CENTRAL_DIRECTORY_HEADER_SIGNATURE, 10),
+        ] {
+            let signature = signature.to_le_bytes();
+            for start in 0..bytes.len() - 4 {
+                if bytes[start..start + 4] == signature {
+                    let start = start + method_offset;
+                    bytes[start..start + 2].copy_from_slice(&98u16.to_le_bytes());
+                }
+            }
+        }
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert!(matches!(
+            archive.by_name_decrypt("encrypted.bin", b"wrong"),
+            Err(crate::result::ZipError::UnsupportedArchive(_))
+        ));
+
+        // A decoder that never gives its reader back fails to be taken apart, without panicking
+        archive.set_compression_registry(CompressionRegistry::default().with_decoder(
+            CompressionMethod::PPMD,
+            |reader| {
+                std::mem::forget(reader);
+                Ok(Box::new(Cursor::new(b"plain".to_vec())))
+            },
+        ));
+        let mut contents = Vec::new();
+        let mut file = archive.by_name("plain.bin")?;
+        file.read_to_end(&mut contents)?;
+        assert_eq!(contents, b"plain");
+        assert!(file.take_raw_reader().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn start_file_streaming_uses_data_descriptor() -> ZipResult<()> {
+        use crate::HasZipMetadata;
+        use std::io::Read;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file_streaming("streamed.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"generated on the fly")?;
+        writer.start_file("seeked.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"patched afterwards")?;
+        let bytes = writer.finish()?.into_inner();
+
+        // The local header of the streamed file is never patched
+        assert_ne!(bytes[6] & 0b1000, 0);
+        assert_eq!(bytes[14..26], [0; 12]);
+        let descriptor = spec::Magic::DATA_DESCRIPTOR_SIGNATURE.to_le_bytes();
+        assert!(bytes.windows(4).any(|window| window == descriptor));
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut contents = String::new();
+        let mut file = archive.by_name("streamed.txt")?;
+        assert!(file.get_metadata().using_data_descriptor);
+        file.read_to_string(&mut contents)?;
+        assert_eq!(contents, "generated on the fly");
+        drop(file);
+        let file = archive.by_name("seeked.txt")?;
+        assert!(!file.get_metadata().using_data_descriptor);
+
+        // Without a Zip64 extra field in the local header, 8-byte descriptor sizes can't be told
+        // apart from 4-byte ones, so exceeding 4 GiB needs large_file as usual
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file_streaming("huge.bin", SimpleFileOptions::default())?;
+        writer.stats.bytes_written = spec::ZIP64_BYTES_THR;
+        assert!(writer.write_all(b"!").is_err());
+        assert_eq!(writer.files.len(), 0);
+        let options = SimpleFileOptions::default().large_file(true);
+        writer.start_file_streaming("huge.bin", options)?;
+        writer.stats.bytes_written = spec::ZIP64_BYTES_THR;
+        writer.write_all(b"!")?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "deflate-flate2")]
+    fn recompress_file() -> ZipResult<()> {
+        use std::io::Read;
+
+        let time = DateTime::from_date_and_time(2020, 2, 29, 12, 34, 56)?;
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .last_modified_time(time);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("a.txt", options)?;
+        writer.write_all(b"first")?;
+        writer.start_file("b.txt", options.comment("kept").unix_permissions(0o600))?;
+        writer.write_all(&b"compressible ".repeat(100))?;
+        writer.start_file("c.txt", options)?;
+        writer.write_all(b"last")?;
+        let bytes = writer.finish()?.into_inner();
+
+        // Corrupt data is left as it was
+        let mut corrupt = bytes.clone();
+        let data = corrupt.windows(5).position(|w| w == b"compr").unwrap();
+        corrupt[data] ^= 0xff;
+        let mut writer = ZipWriter::new_append(Cursor::new(corrupt))?;
+        assert!(matches!(
+            writer.recompress_file("b.txt", SimpleFileOptions::default()),
+            Err(crate::result::ZipError::Io(_))
+        ));
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.len(), 3);
+        assert_eq!(archive.by_name("b.txt")?.compression(), Stored);
+
+        // A declared size isn't trusted for allocation, and more data than declared is an error
+        for declared in [1 << 62, 10] {
+            let mut writer = ZipWriter::new_append(Cursor::new(bytes.clone()))?;
+            let index = writer.index_by_name("b.txt")?;
+            writer.files[index].uncompressed_size = declared;
+            assert!(writer
+                .recompress_file("b.txt", SimpleFileOptions::default())
+                .is_err());
+            assert_eq!(writer.files[index].compression_method, Stored);
+        }
+
+        let mut writer = ZipWriter::new_append(Cursor::new(bytes))?;
+        writer.recompress_file(
+            "b.txt",
+            SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+        )?;
+        assert!(matches!(
+            writer.recompress_file("missing.txt", SimpleFileOptions::default()),
+            Err(crate::result::ZipError::FileNotFound)
+        ));
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["a.txt", "b.txt", "c.txt"]
+        );
+        let mut file = archive.by_name("b.txt")?;
+        assert_eq!(file.compression(), CompressionMethod::Deflated);
+        assert!(file.compressed_size() < file.size());
+        assert_eq!(file.comment(), "kept");
+        assert_eq!(file.last_modified(), Some(time));
+        assert_eq!(file.unix_mode(), Some(0o100600));
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        assert_eq!(contents, b"compressible ".repeat(100));
+        drop(file);
+        for (name, expected) in [("a.txt", "first"), ("c.txt", "last")] {
+            let mut contents = String::new();
+            archive.by_name(name)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, expected);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,97 @@
+//! Ready-made transforms for [`ZipWriter::set_name_transform`](super::ZipWriter::set_name_transform)
+//!
+//! Transforms are plain functions, so they can be combined in a closure:
+//!
+//! ```
+//! # fn doit() -> zip::result::ZipResult<()>
+//! # {
+//! use std::io::Cursor;
+//! use zip::write::name_transform::{forward_slashes, reject_escaping, strip_base};
+//! use zip::write::SimpleFileOptions;
+//! use zip::ZipWriter;
+//!
+//! let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+//! let strip = strip_base("/srv/site");
+//! zip.set_name_transform(move |name| {
+//!     let name = forward_slashes(name)?;
+//!     let name = strip(&name)?;
+//!     Ok(reject_escaping(&name)?.into_owned().into())
+//! });
+//! zip.start_file("/srv/site/index.html", SimpleFileOptions::default())?;
+//! assert!(zip.start_file("/etc/passwd", SimpleFileOptions::default()).is_err());
+//! let archive = zip::ZipArchive::new(zip.finish()?)?;
+//! assert_eq!(archive.file_names().collect::<Vec<_>>(), ["index.html"]);
+//! # Ok(())
+//! # }
+//! # doit().unwrap();
+//! ```
+
+use crate::result::{ZipError, ZipResult};
+use std::borrow::Cow;
+
+/// A transform applied to entry names before they're stored.
+pub type NameTransform = Box<dyn Fn(&str) -> ZipResult<Cow<'_, str>> + Send + Sync>;
+
+/// Replaces backslashes, the path separator on Windows, with forward slashes.
+pub fn forward_slashes(name: &str) -> ZipResult<Cow<'_, str>> {
+    Ok(match name.contains('\\') {
+        true => Cow::Owned(name.replace('\\', "/")),
+        false => Cow::Borrowed(name),
+    })
+}
+
+/// Returns a transform removing the directory `base` from the start of names, and failing for
+/// names outside it. `base` uses forward slashes, and may end with one.
+pub fn strip_base<S: Into<String>>(
+    base: S,
+) -> impl Fn(&str) -> ZipResult<Cow<'_, str>> + Send + Sync + 'static {
+    let mut base = base.into();
+    while base.len() > 1 && base.ends_with('/') {
+        base.pop();
+    }
+    move |name| {
+        let rest = name
+            .strip_prefix(base.as_str())
+            .filter(|rest| rest.is_empty() || rest.starts_with('/') || base.ends_with('/'))
+            .ok_or(ZipError::InvalidArchive(
+                "Entry name is outside the base directory",
+            ))?;
+        Ok(Cow::Borrowed(rest.trim_start_matches('/')))
+    }
+}
+
+/// Fails for absolute names, including ones starting with a Windows drive letter, and names
+/// with `..` components, which could be extracted outside the destination directory.
+pub fn reject_escaping(name: &str) -> ZipResult<Cow<'_, str>> {
+    let bytes = name.as_bytes();
+    let absolute = name.starts_with(['/', '\\'])
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':');
+    if absolute || name.split(['/', '\\']).any(|component| component == "..") {
+        return Err(ZipError::InvalidArchive(
+            "Entry name is absolute or has a `..` component",
+        ));
+    }
+    Ok(Cow::Borrowed(name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{forward_slashes, reject_escaping, strip_base};
+
+    #[test]
+    fn helpers() {
+        assert_eq!(forward_slashes("a\\b/c").unwrap(), "a/b/c");
+
+        let strip = strip_base("base/dir/");
+        assert_eq!(strip("base/dir/file.txt").unwrap(), "file.txt");
+        assert_eq!(strip("base/dir//sub/").unwrap(), "sub/");
+        assert!(strip("base/directory/file.txt").is_err());
+        assert!(strip("other/file.txt").is_err());
+        assert_eq!(strip_base("/")("/etc/hosts").unwrap(), "etc/hosts");
+
+        assert_eq!(reject_escaping("a/..b/c..").unwrap(), "a/..b/c..");
+        for name in ["/etc/passwd", "\\share", "C:/Windows", "a/../../b", "..\\b"] {
+            assert!(reject_escaping(name).is_err(), "{name}");
+        }
+    }
+}
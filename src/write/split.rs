@@ -0,0 +1,128 @@
+//! Writing archives split into volumes of a fixed size
+
+use crate::result::{ZipError, ZipResult};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// The smallest volume size allowed by APPNOTE 8.5.5.
+pub(crate) const MIN_VOLUME_SIZE: u64 = 64 * 1024;
+
+/// Writes the volumes of a split archive, created by [`ZipWriter::new_split`](super::ZipWriter::new_split).
+///
+/// The last volume is always named after the archive, and the ones before it get the extensions
+/// `.z01`, `.z02` and so on. Volume `n` starts at offset `n * volume_size` of the stream, so that
+/// the disk number and the offset within it can be told from a position; a volume may be shorter
+/// than `volume_size` if a header didn't fit at its end.
+#[derive(Debug)]
+pub struct SplitWriter {
+    path: PathBuf,
+    volume_size: u64,
+    /// The last volume, open at `path`
+    current: File,
+    disk: u64,
+    position: u64,
+}
+
+impl SplitWriter {
+    pub(crate) fn create(path: &Path, volume_size: u64) -> io::Result<Self> {
+        Ok(SplitWriter {
+            path: path.to_owned(),
+            volume_size,
+            current: File::create(path)?,
+            disk: 0,
+            position: 0,
+        })
+    }
+
+    /// Returns the number of volumes written so far.
+    pub fn volume_count(&self) -> u64 {
+        self.disk + 1
+    }
+
+    fn volume_path(&self, disk: u64) -> PathBuf {
+        self.path.with_extension(format!("z{:02}", disk + 1))
+    }
+
+    /// Moves the last volume to its numbered name and starts the next one.
+    fn next_volume(&mut self) -> io::Result<()> {
+        self.current.flush()?;
+        fs::rename(&self.path, self.volume_path(self.disk))?;
+        self.current = File::create(&self.path)?;
+        self.disk += 1;
+        Ok(())
+    }
+}
+
+impl Write for SplitWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let disk = self.position / self.volume_size;
+        let offset = self.position % self.volume_size;
+        if disk == self.disk + 1 && offset == 0 {
+            self.next_volume()?;
+        }
+        let limit = buf.len().min((self.volume_size - offset) as usize);
+        let count = if disk == self.disk {
+            self.current.seek(SeekFrom::Start(offset))?;
+            self.current.write(&buf[..limit])?
+        } else if disk < self.disk {
+            // Updating a header in an earlier volume
+            let mut volume = OpenOptions::new()
+                .write(true)
+                .open(self.volume_path(disk))?;
+            volume.seek(SeekFrom::Start(offset))?;
+            volume.write(&buf[..limit])?
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Can't write past the start of the next volume",
+            ));
+        };
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.flush()
+    }
+}
+
+impl Seek for SplitWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => {
+                let end = self.disk * self.volume_size + self.current.seek(SeekFrom::End(0))?;
+                end.checked_add_signed(offset)
+            }
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = target.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// The disk number and offset within it of a position in a [`SplitWriter`].
+pub(crate) fn volume_position(volume_size: u64, position: u64) -> ZipResult<(u16, u64)> {
+    let disk = u16::try_from(position / volume_size)
+        .ok()
+        .filter(|disk| *disk < u16::MAX)
+        .ok_or(ZipError::InvalidArchive("Too many volumes"))?;
+    Ok((disk, position % volume_size))
+}
+
+/// Moves to the start of the next volume if a record of `len` bytes wouldn't fit in the current
+/// one, since headers mustn't be split across volumes. Returns the position to write it at.
+pub(crate) fn reserve<W: Seek>(writer: &mut W, volume_size: u64, len: u64) -> io::Result<u64> {
+    let position = writer.stream_position()?;
+    let offset = position % volume_size;
+    if offset == 0 || offset + len <= volume_size {
+        return Ok(position);
+    }
+    writer.seek(SeekFrom::Start(position - offset + volume_size))
+}
@@ -0,0 +1,377 @@
+//! Writing archives to streams that can't seek
+
+use super::{FileOptionExtension, FileOptions, FlushPolicy, Zip64Mode, ZipWriter};
+use crate::result::ZipResult;
+use crate::CompressionMethod;
+use std::borrow::Cow;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// Counts the bytes written to a stream, so that [`ZipWriter`] can ask for its position. Seeking
+/// anywhere else fails.
+#[derive(Debug)]
+pub(crate) struct PositionTracker<W> {
+    inner: W,
+    position: u64,
+}
+
+impl<W: Write> Write for PositionTracker<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W> Seek for PositionTracker<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // Everything is appended, so the end of the stream is always the current position.
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) | SeekFrom::Current(offset) => {
+                self.position.checked_add_signed(offset)
+            }
+        };
+        if target != Some(self.position) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Can't seek in a streamed archive",
+            ));
+        }
+        Ok(self.position)
+    }
+}
+
+/// ZIP archive generator for writers that can't seek, such as pipes and sockets.
+///
+/// Since the local header of an entry can't be updated once its data is written, every entry
+/// that has data is followed by a data descriptor holding its CRC-32 and sizes. As with a
+/// [`ZipWriter`], an entry that exceeds 4 GiB must have [`FileOptions::large_file`] set, which
+/// writes a Zip64 extra field in its local header and gives its descriptor 8-byte sizes. The
+/// central directory is written by [`ZipStreamWriter::finish`], or when the writer is dropped.
+///
+/// ```
+/// # fn doit() -> zip::result::ZipResult<()>
+/// # {
+/// use std::io::Write;
+/// use zip::unstable::stream::ZipStreamWriter;
+/// use zip::write::SimpleFileOptions;
+///
+/// let mut zip = ZipStreamWriter::new(Vec::new());
+/// zip.start_file("hello_world.txt", SimpleFileOptions::default())?;
+/// zip.write_all(b"Hello, World!")?;
+/// let bytes: Vec<u8> = zip.finish()?;
+/// # Ok(())
+/// # }
+/// # doit().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ZipStreamWriter<W: Write>(ZipWriter<PositionTracker<W>>);
+
+impl<W: Write> ZipStreamWriter<W> {
+    /// Initializes the archive.
+    pub fn new(inner: W) -> Self {
+        let mut writer = ZipWriter::new(PositionTracker { inner, position: 0 });
+        writer.streaming = true;
+        ZipStreamWriter(writer)
+    }
+
+    /// Returns true if a file is currently open for writing.
+    pub const fn is_writing_file(&self) -> bool {
+        self.0.is_writing_file()
+    }
+
+    /// Set ZIP archive comment.
+    pub fn set_comment<S>(&mut self, comment: S)
+    where
+        S: Into<Box<str>>,
+    {
+        self.0.set_comment(comment)
+    }
+
+    /// Set ZIP archive comment.
+    ///
+    /// This sets the raw bytes of the comment. The comment
+    /// is typically expected to be encoded in UTF-8.
+    pub fn set_raw_comment(&mut self, comment: Box<[u8]>) {
+        self.0.set_raw_comment(comment)
+    }
+
+    /// Set when the Zip64 extensions are used. See [`ZipWriter::set_zip64_mode`].
+    pub fn set_zip64_mode(&mut self, mode: Zip64Mode) {
+        self.0.set_zip64_mode(mode)
+    }
+
+    /// Set when the inner writer is flushed. See [`ZipWriter::set_flush_policy`].
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.0.set_flush_policy(policy)
+    }
+
+    /// Set a transform applied to the names of the entries added afterwards. See
+    /// [`ZipWriter::set_name_transform`].
+    pub fn set_name_transform<F>(&mut self, transform: F)
+    where
+        F: Fn(&str) -> ZipResult<Cow<'_, str>> + Send + Sync + 'static,
+    {
+        self.0.set_name_transform(transform)
+    }
+
+    /// Create a file in the archive and start writing its contents. The file must not have the
+    /// same name as a file already in the archive.
+    ///
+    /// See [`ZipWriter::start_file`].
+    pub fn start_file<S, T: FileOptionExtension, SToOwned>(
+        &mut self,
+        name: S,
+        options: FileOptions<T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        self.0.start_file(name, options)
+    }
+
+    /// Add a directory entry.
+    ///
+    /// See [`ZipWriter::add_directory`].
+    pub fn add_directory<S, T: FileOptionExtension>(
+        &mut self,
+        name: S,
+        options: FileOptions<T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<String>,
+    {
+        self.0.add_directory(name, options)
+    }
+
+    /// Add a symlink entry.
+    ///
+    /// See [`ZipWriter::add_symlink`].
+    pub fn add_symlink<N, NToOwned, T, E: FileOptionExtension>(
+        &mut self,
+        name: N,
+        target: T,
+        options: FileOptions<E>,
+    ) -> ZipResult<()>
+    where
+        N: Into<Box<str>> + ToOwned<Owned = NToOwned>,
+        NToOwned: Into<Box<str>>,
+        T: Into<Box<str>>,
+    {
+        self.0.add_symlink(name, target, options)
+    }
+
+    /// Add a file whose data was already compressed with `method`.
+    ///
+    /// See [`ZipWriter::write_precompressed`].
+    pub fn write_precompressed<S, SToOwned, R: Read>(
+        &mut self,
+        name: S,
+        method: CompressionMethod,
+        crc32: u32,
+        compressed: R,
+        uncompressed_size: u64,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        self.0
+            .write_precompressed(name, method, crc32, compressed, uncompressed_size)
+    }
+
+    /// Fails, since the data already written for a file can't be taken back without seeking.
+    /// Unlike [`ZipWriter::abort_file`], the file stays in the archive: it's finished with what
+    /// has been written so far when the next file is started or the archive is finished.
+    pub fn abort_file(&mut self) -> ZipResult<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Can't abort a file in a streamed archive",
+        )
+        .into())
+    }
+
+    /// Finish the last file and write the central directory, returning the inner writer.
+    pub fn finish(self) -> ZipResult<W> {
+        Ok(self.0.finish()?.inner)
+    }
+}
+
+impl<W: Write> Write for ZipStreamWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ZipStreamWriter;
+    use crate::result::ZipResult;
+    use crate::write::SimpleFileOptions;
+    use crate::ZipArchive;
+    use std::io::{Cursor, Read, Write};
+
+    /// Only allows writing, like a pipe.
+    struct Pipe(Vec<u8>);
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_stream() -> ZipResult<()> {
+        let mut writer = ZipStreamWriter::new(Pipe(Vec::new()));
+        writer.start_file(
+            "stored.txt",
+            SimpleFileOptions::default().compression_method(crate::CompressionMethod::Stored),
+        )?;
+        writer.write_all(b"stored")?;
+        writer.add_directory("dir", SimpleFileOptions::default())?;
+        writer.start_file(
+            "dir/large.txt",
+            SimpleFileOptions::default().large_file(true),
+        )?;
+        writer.write_all(b"declared large")?;
+        writer.start_file(
+            "encrypted.txt",
+            SimpleFileOptions::default().with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(b"secret")?;
+        writer.set_comment("streamed");
+        let bytes = writer.finish()?.0;
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        assert_eq!(archive.comment(), b"streamed");
+        assert_eq!(archive.len(), 4);
+        let mut content = String::new();
+        archive
+            .by_name("stored.txt")?
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "stored");
+        assert!(archive.by_name("dir/")?.is_dir());
+        content.clear();
+        archive
+            .by_name("dir/large.txt")?
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "declared large");
+        content.clear();
+        archive
+            .by_name_decrypt("encrypted.txt", b"password")?
+            .read_to_string(&mut content)?;
+        assert_eq!(content, "secret");
+
+        // The first data descriptor, with 4-byte sizes
+        let header_len = 30 + "stored.txt".len();
+        assert_eq!(
+            &bytes[header_len + 6..][..16],
+            b"PK\x07\x08\x0b\xf9\x43\x56\x06\x00\x00\x00\x06\x00\x00\x00"
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "_deflate-any")]
+    #[test]
+    fn write_stream_read_stream() -> ZipResult<()> {
+        use crate::read::stream::{ZipStreamFileMetadata, ZipStreamReader, ZipStreamVisitor};
+        use crate::read::ZipFile;
+
+        let options =
+            SimpleFileOptions::default().compression_method(crate::CompressionMethod::Deflated);
+        let mut writer = ZipStreamWriter::new(Pipe(Vec::new()));
+        writer.start_file("first.txt", options)?;
+        writer.write_all(&b"first".repeat(100))?;
+        writer.start_file("second.txt", options.large_file(true))?;
+        writer.write_all(b"second")?;
+        let bytes = writer.finish()?.0;
+
+        #[derive(Default)]
+        struct V(Vec<(String, Vec<u8>)>);
+        impl ZipStreamVisitor for V {
+            fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
+                let mut content = Vec::new();
+                file.read_to_end(&mut content)?;
+                self.0.push((file.name().to_string(), content));
+                Ok(())
+            }
+
+            fn visit_additional_metadata(
+                &mut self,
+                _metadata: &ZipStreamFileMetadata,
+            ) -> ZipResult<()> {
+                Ok(())
+            }
+        }
+        let mut visitor = V::default();
+        ZipStreamReader::new(Cursor::new(bytes)).visit(&mut visitor)?;
+        assert_eq!(
+            visitor.0,
+            vec![
+                ("first.txt".to_string(), b"first".repeat(100)),
+                ("second.txt".to_string(), b"second".to_vec()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn abort_file_unsupported() -> ZipResult<()> {
+        let mut writer = ZipStreamWriter::new(Pipe(Vec::new()));
+        writer.start_file("partial.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"partial")?;
+        assert!(writer.abort_file().is_err());
+        writer.start_file("next.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"next")?;
+        let archive = ZipArchive::new(Cursor::new(writer.finish()?.0))?;
+        assert_eq!(archive.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn write_stream_precompressed() -> ZipResult<()> {
+        let data = b"compressed elsewhere";
+        let mut writer = ZipStreamWriter::new(Pipe(Vec::new()));
+        writer.write_precompressed(
+            "stored.txt",
+            crate::CompressionMethod::Stored,
+            crc32fast::hash(data),
+            &data[..],
+            data.len() as u64,
+        )?;
+        let bytes = writer.finish()?.0;
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut content = Vec::new();
+        archive.by_name("stored.txt")?.read_to_end(&mut content)?;
+        assert_eq!(content, data);
+        Ok(())
+    }
+
+    #[test]
+    fn large_entry_needs_large_file() -> ZipResult<()> {
+        let mut writer = ZipStreamWriter::new(Pipe(Vec::new()));
+        writer.start_file("huge.bin", SimpleFileOptions::default())?;
+        writer.0.stats.bytes_written = crate::spec::ZIP64_BYTES_THR;
+        assert!(writer.write_all(b"!").is_err());
+
+        let mut writer = ZipStreamWriter::new(Pipe(Vec::new()));
+        writer.start_file("huge.bin", SimpleFileOptions::default().large_file(true))?;
+        writer.0.stats.bytes_written = crate::spec::ZIP64_BYTES_THR;
+        writer.write_all(b"!")?;
+        Ok(())
+    }
+}
@@ -19,7 +19,7 @@ pub(crate) struct ZipCryptoKeys {
 }
 
 impl Debug for ZipCryptoKeys {
-    #[allow(unreachable_code)]
+    #[allow(unreachable_code, clippy::needless_return)]
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         #[cfg(not(any(test, fuzzing)))]
         {
@@ -152,3 +152,60 @@ fn test_extract_encrypted_file<R: io::Read + io::Seek>(
         assert_eq!(SECRET_CONTENT, content);
     }
 }
+
+#[test]
+fn aes256_tampered_data_fails_authentication() {
+    let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    zip.start_file(
+        "test.txt",
+        SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, "some password"),
+    )
+    .unwrap();
+    zip.write_all(SECRET_CONTENT.as_bytes()).unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+
+    let data_start = ZipArchive::new(io::Cursor::new(&bytes))
+        .unwrap()
+        .by_index_raw(0)
+        .unwrap()
+        .data_start();
+    // Skip the 16-byte salt and the 2-byte password verification value.
+    bytes[data_start as usize + 18] ^= 1;
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+    let mut file = archive
+        .by_name_decrypt("test.txt", b"some password")
+        .expect("password check should still pass");
+    let err = file.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn aes_vendor_version_and_crc() {
+    let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, "password");
+    zip.start_file("short.txt", options).unwrap();
+    zip.write_all(b"short").unwrap();
+    zip.start_file("long.txt", options).unwrap();
+    zip.write_all(SECRET_CONTENT.as_bytes()).unwrap();
+    let mut archive = ZipArchive::new(zip.finish().unwrap()).unwrap();
+
+    // Returns the vendor version from the AES extra field and the stored CRC.
+    let mut inspect = |name: &str| {
+        let file = archive.by_name_raw(name).unwrap();
+        let extra = file.extra_data().unwrap();
+        let field = extra
+            .windows(4)
+            .position(|w| w == [0x01, 0x99, 7, 0])
+            .unwrap();
+        let version = u16::from_le_bytes([extra[field + 4], extra[field + 5]]);
+        (version, file.crc32())
+    };
+
+    // Entries shorter than 20 bytes use AE-2, which doesn't store the CRC.
+    assert_eq!(inspect("short.txt"), (2, 0));
+    assert_eq!(
+        inspect("long.txt"),
+        (1, crc32fast::hash(SECRET_CONTENT.as_bytes()))
+    );
+}
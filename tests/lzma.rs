@@ -19,3 +19,27 @@ fn decompress_lzma() {
         .expect("couldn't read encrypted and compressed file");
     assert_eq!("Hello world\n", String::from_utf8(content).unwrap());
 }
+
+#[test]
+fn decompress_lzma_method_14() {
+    let mut archive = ZipArchive::new(io::Cursor::new(include_bytes!("data/lzma_method_14.zip")))
+        .expect("couldn't open test zip file");
+
+    let mut file = archive.by_name("hello.txt").unwrap();
+    assert_eq!(file.compression(), zip::CompressionMethod::Lzma);
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!("Hello world\n", content);
+    drop(file);
+
+    let mut file = archive.by_name("words.txt").unwrap();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    assert_eq!(content.len(), 273718);
+    drop(file);
+
+    let mut file = archive.by_name("empty.txt").unwrap();
+    let mut content = Vec::new();
+    file.read_to_end(&mut content).unwrap();
+    assert!(content.is_empty());
+}
@@ -1,7 +1,9 @@
 #![cfg(feature = "xz")]
 
 use std::io::{self, Read};
-use zip::ZipArchive;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
 
 #[test]
 fn decompress_xz() -> io::Result<()> {
@@ -17,3 +19,13 @@ fn decompress_xz() -> io::Result<()> {
     assert_eq!("Hello world\n", String::from_utf8(content).unwrap());
     Ok(())
 }
+
+#[test]
+fn compress_xz_unsupported() {
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    let result = writer.start_file(
+        "hello.txt",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Xz),
+    );
+    assert!(matches!(result, Err(ZipError::UnsupportedArchive(_))));
+}
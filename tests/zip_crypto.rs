@@ -119,3 +119,27 @@ fn buffered_read() {
     let mut data = Vec::new();
     file.read_to_end(&mut data).unwrap();
 }
+
+/// Info-ZIP writes a data descriptor when zipping from stdin, so the check
+/// byte of the encryption header comes from the modification time instead
+/// of the CRC.
+#[test]
+fn data_descriptor_check_byte() {
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(include_bytes!(
+        "data/zip_crypto_data_descriptor.zip"
+    )))
+    .unwrap();
+
+    match archive.by_index_decrypt(0, b"wrong") {
+        Err(ZipError::InvalidPassword) => (),
+        Err(e) => panic!("Expected InvalidPassword, got {e:?}"),
+        Ok(_) => panic!("Opened the file with the wrong password"),
+    }
+
+    let mut file = archive.by_index_decrypt(0, b"test").unwrap();
+    let mut data = String::new();
+    file.read_to_string(&mut data).unwrap();
+    assert_eq!(data, "streamed secret\n");
+}